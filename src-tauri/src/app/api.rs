@@ -18,22 +18,33 @@
  */
 
 use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 use serde::de::DeserializeOwned;
 
 use crate::auth::ClientAccount;
+use crate::error::LauncherError;
 use crate::minecraft::java::JavaDistribution;
 use crate::HTTP_CLIENT;
-use crate::utils::get_maven_artifact_path;
+use crate::utils::{get_cached, get_cached_read_only, get_maven_artifact_path};
 
 /// API endpoint url
 pub const LAUNCHER_API: &str = "https://api.liquidbounce.net";
 pub const API_V1: &str = "api/v1";
 pub const API_V3: &str = "api/v3";
 
+/// Launch manifests of a build rarely change, but fixes should still reach the users quickly
+const LAUNCH_MANIFEST_TTL: Duration = Duration::from_secs(10 * 60);
+/// Shortest abbreviation of a commit id accepted to select a build
+const MIN_COMMIT_ID_LENGTH: usize = 7;
+
+pub const MODRINTH_API: &str = "https://api.modrinth.com/v2";
+pub const CURSEFORGE_API: &str = "https://api.curseforge.com/v1";
+
 pub const CONTENT_DELIVERY: &str = "https://cloud.liquidbounce.net";
 pub const CONTENT_FOLDER: &str = "LiquidLauncher";
 
@@ -104,14 +115,46 @@ impl ApiEndpoints {
         Self::request_from_endpoint("version/builds").await
     }
 
+    /// Find the build of a commit, the commit id may be abbreviated as long as it is unambiguous
+    pub async fn resolve_build_by_commit(commit_id: &str) -> Result<Build> {
+        let commit_id = commit_id.trim().to_lowercase();
+        if commit_id.len() < MIN_COMMIT_ID_LENGTH {
+            return Err(LauncherError::Validation(format!(
+                "Commit id {} is too short, at least {} characters are required",
+                commit_id, MIN_COMMIT_ID_LENGTH
+            )).into());
+        }
+
+        let mut builds = Self::builds().await?
+            .into_iter()
+            .filter(|build| build.commit_id.to_lowercase().starts_with(&commit_id))
+            .collect::<Vec<_>>();
+
+        match builds.len() {
+            0 => Err(LauncherError::UnknownCommit { commit_id }.into()),
+            1 => Ok(builds.remove(0)),
+            _ => Err(LauncherError::AmbiguousCommit {
+                commit_id,
+                builds: builds.iter().map(|build| build.build_id).collect(),
+            }.into()),
+        }
+    }
+
     /// Request all builds of branch
     pub async fn builds_by_branch(branch: &str) -> Result<Vec<Build>> {
         Self::request_from_endpoint(&format!("version/builds/{}", branch)).await
     }
 
-    /// Request launch manifest of specific build
-    pub async fn launch_manifest(build_id: u32) -> Result<LaunchManifest> {
-        Self::request_from_endpoint(&format!("version/launch/{}", build_id)).await
+    /// Request launch manifest of specific build, which is cached for a short time.
+    /// A dry run only reads the cache, a newer manifest is not stored.
+    pub async fn launch_manifest(build_id: u32, dry_run: bool) -> Result<LaunchManifest> {
+        let url = format!("{}/{}/version/launch/{}", LAUNCHER_API, API_V1, build_id);
+        let name = format!("launch_manifest_{}.json", build_id);
+        let content = match dry_run {
+            false => get_cached(&url, &name, LAUNCH_MANIFEST_TTL).await?,
+            true => get_cached_read_only(&url, &name, LAUNCH_MANIFEST_TTL).await?,
+        };
+        LaunchManifest::from_value(serde_json::from_slice(&content)?)
     }
 
     /// Request list of downloadable mods for mc_version and used subsystem
@@ -155,6 +198,166 @@ impl ApiEndpoints {
 
 }
 
+/// Placeholder struct for Modrinth API implementation
+pub struct ModrinthApi;
+
+///
+/// Modrinth API v2
+/// https://docs.modrinth.com/api/
+///
+impl ModrinthApi {
+
+    /// Request a specific version of a project
+    pub async fn version(version_id: &str) -> Result<ModrinthVersion> {
+        Ok(HTTP_CLIENT.get(format!("{}/version/{}", MODRINTH_API, version_id))
+            .send().await?
+            .error_for_status()?
+            .json::<ModrinthVersion>()
+            .await?
+        )
+    }
+
+    /// Request the versions of a project compatible with the Minecraft version and loader, newest first
+    pub async fn project_versions(project_id: &str, mc_version: &str, loader: &str) -> Result<Vec<ModrinthVersion>> {
+        Ok(HTTP_CLIENT.get(format!("{}/project/{}/version", MODRINTH_API, project_id))
+            .query(&[
+                ("game_versions", serde_json::to_string(&[mc_version])?),
+                ("loaders", serde_json::to_string(&[loader])?),
+            ])
+            .send().await?
+            .error_for_status()?
+            .json::<Vec<ModrinthVersion>>()
+            .await?
+        )
+    }
+
+    /// Request a project, its slug usually matches the mod id
+    pub async fn project(project_id: &str) -> Result<ModrinthProject> {
+        Ok(HTTP_CLIENT.get(format!("{}/project/{}", MODRINTH_API, project_id))
+            .send().await?
+            .error_for_status()?
+            .json::<ModrinthProject>()
+            .await?
+        )
+    }
+
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ModrinthProject {
+    pub id: String,
+    pub slug: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ModrinthVersion {
+    pub id: String,
+    pub project_id: String,
+    pub version_number: String,
+    pub files: Vec<ModrinthFile>,
+    #[serde(default)]
+    pub dependencies: Vec<ModrinthDependency>,
+}
+
+impl ModrinthVersion {
+    /// The primary file of the version, falls back to the first file if none is marked as primary
+    pub fn primary_file(&self) -> Option<&ModrinthFile> {
+        self.files.iter()
+            .find(|file| file.primary)
+            .or_else(|| self.files.first())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ModrinthDependency {
+    /// Specific version the dependency is pinned to
+    pub version_id: Option<String>,
+    /// Any version compatible with the game is fine if only the project is known
+    pub project_id: Option<String>,
+    pub dependency_type: String,
+}
+
+impl ModrinthDependency {
+    pub fn is_required(&self) -> bool {
+        self.dependency_type == "required"
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ModrinthFile {
+    pub url: String,
+    pub filename: String,
+    pub primary: bool,
+    pub size: u64,
+    pub hashes: ModrinthHashes
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ModrinthHashes {
+    pub sha1: String,
+    pub sha512: String
+}
+
+/// Placeholder struct for CurseForge API implementation
+pub struct CurseForgeApi;
+
+///
+/// CurseForge Core API v1
+/// https://docs.curseforge.com/
+///
+impl CurseForgeApi {
+
+    /// Request a specific file of a project
+    pub async fn file(api_key: &str, project_id: u64, file_id: u64) -> Result<CurseForgeFile> {
+        Ok(HTTP_CLIENT.get(format!("{}/mods/{}/files/{}", CURSEFORGE_API, project_id, file_id))
+            .header("x-api-key", api_key)
+            .send().await?
+            .error_for_status()?
+            .json::<CurseForgeResponse<CurseForgeFile>>()
+            .await?
+            .data
+        )
+    }
+
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CurseForgeResponse<T> {
+    pub data: T
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CurseForgeFile {
+    pub id: u64,
+    #[serde(rename = "fileName")]
+    pub file_name: String,
+    /// Missing if the author has disabled third-party downloads
+    #[serde(rename = "downloadUrl")]
+    pub download_url: Option<String>,
+    #[serde(rename = "fileLength")]
+    pub file_length: u64,
+    #[serde(default)]
+    pub hashes: Vec<CurseForgeHash>
+}
+
+impl CurseForgeFile {
+    pub fn sha1(&self) -> Option<&String> {
+        self.hashes.iter()
+            .find(|hash| hash.algo == CurseForgeHash::SHA1)
+            .map(|hash| &hash.value)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CurseForgeHash {
+    pub value: String,
+    pub algo: u32
+}
+
+impl CurseForgeHash {
+    pub const SHA1: u32 = 1;
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Branches {
     #[serde(rename = "defaultBranch")]
@@ -173,23 +376,23 @@ pub struct Changelog {
 ///
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Build {
-    #[serde(rename(serialize = "buildId"))]
+    #[serde(rename(serialize = "buildId"), alias = "buildId")]
     pub build_id: u32,
-    #[serde(rename(serialize = "commitId"))]
+    #[serde(rename(serialize = "commitId"), alias = "commitId")]
     pub commit_id: String,
     pub branch: String,
     pub subsystem: String,
-    #[serde(rename(serialize = "lbVersion"))]
+    #[serde(rename(serialize = "lbVersion"), alias = "lbVersion")]
     pub lb_version: String,
-    #[serde(rename(serialize = "mcVersion"))]
+    #[serde(rename(serialize = "mcVersion"), alias = "mcVersion")]
     pub mc_version: String,
     pub release: bool,
     pub date: DateTime<Utc>,
     pub message: String,
     pub url: String,
-    #[serde(rename(serialize = "jreDistribution"), default)]
+    #[serde(rename(serialize = "jreDistribution"), alias = "jreDistribution", default)]
     pub jre_distribution: JavaDistribution,
-    #[serde(rename(serialize = "jreVersion"))]
+    #[serde(rename(serialize = "jreVersion"), alias = "jreVersion")]
     pub jre_version: u32,
     #[serde(flatten)]
     pub subsystem_specific_data: SubsystemSpecificData
@@ -203,13 +406,17 @@ pub struct Build {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SubsystemSpecificData {
     // Additional data
-    #[serde(rename(serialize = "fabricApiVersion"))]
+    #[serde(rename(serialize = "fabricApiVersion"), alias = "fabricApiVersion")]
     pub fabric_api_version: String,
-    #[serde(rename(serialize = "fabricLoaderVersion"))]
+    #[serde(rename(serialize = "fabricLoaderVersion"), alias = "fabricLoaderVersion")]
     pub fabric_loader_version: String,
-    #[serde(rename(serialize = "kotlinVersion"))]
+    #[serde(rename(serialize = "quiltLoaderVersion"), alias = "quiltLoaderVersion", default)]
+    pub quilt_loader_version: String,
+    #[serde(rename(serialize = "neoForgeVersion"), alias = "neoForgeVersion", default)]
+    pub neoforge_version: String,
+    #[serde(rename(serialize = "kotlinVersion"), alias = "kotlinVersion")]
     pub kotlin_version: String,
-    #[serde(rename(serialize = "kotlinModVersion"))]
+    #[serde(rename(serialize = "kotlinModVersion"), alias = "kotlinModVersion")]
     pub kotlin_mod_version: String
 }
 
@@ -217,18 +424,74 @@ pub struct SubsystemSpecificData {
 ///
 /// JSON struct of Launch Manifest
 ///
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct LaunchManifest {
+    /// Manifests from before the schema was versioned are version 1
+    #[serde(rename = "schemaVersion", default = "default_schema_version")]
+    pub schema_version: u32,
     pub build: Build,
     pub subsystem: LoaderSubsystem,
     pub mods: Vec<LoaderMod>,
-    pub repositories: BTreeMap<String, String>,
+    pub repositories: BTreeMap<String, RepositoryUrls>,
+    /// Hosts SkipAd downloads may end up on after following all redirects, including their subdomains.
+    /// Without any hosts, SkipAd downloads are not restricted.
+    #[serde(rename = "allowedDownloadHosts", default)]
+    pub allowed_download_hosts: Vec<String>,
+}
+
+/// Range of launch manifest schema versions this launcher is able to read
+pub const SUPPORTED_MANIFEST_VERSIONS: RangeInclusive<u32> = 1..=1;
+
+fn default_schema_version() -> u32 {
+    1
+}
+
+impl LaunchManifest {
+
+    /// Deserializes the manifest, checking the schema version first as newer manifests might not deserialize at all
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        let schema_version = match value.get("schemaVersion") {
+            Some(version) => version.as_u64()
+                .and_then(|x| u32::try_from(x).ok())
+                .ok_or_else(|| anyhow!("Invalid launch manifest schema version {}", version))?,
+            None => default_schema_version(),
+        };
+
+        if !SUPPORTED_MANIFEST_VERSIONS.contains(&schema_version) {
+            return Err(LauncherError::UnsupportedManifestVersion {
+                found: schema_version,
+                supported: SUPPORTED_MANIFEST_VERSIONS,
+            }.into());
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+
 }
 
 ///
-/// JSON struct of mod
+/// URL of a repository, or a list of mirrors which are tried in order
 ///
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum RepositoryUrls {
+    Single(String),
+    Mirrors(Vec<String>),
+}
+
+impl RepositoryUrls {
+    pub fn urls(&self) -> &[String] {
+        match self {
+            RepositoryUrls::Single(url) => std::slice::from_ref(url),
+            RepositoryUrls::Mirrors(urls) => urls,
+        }
+    }
+}
+
+///
+/// JSON struct of mod
+///
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LoaderMod {
     #[serde(default)]
     pub required: bool,
@@ -237,23 +500,83 @@ pub struct LoaderMod {
     pub enabled: bool,
     pub name: String,
     pub source: ModSource,
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+    /// Expected size in bytes, used to weight the download progress
+    pub size: Option<u64>,
 }
 
 ///
 /// JSON struct of ModSource (the method to be used for downloading the mod)
 ///
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(tag = "type")]
 pub enum ModSource {
     #[serde(rename = "skip")]
     #[serde(rename_all = "camelCase")]
-    SkipAd { artifact_name: String, url: String, #[serde(default)] extract: bool },
+    SkipAd {
+        artifact_name: String,
+        url: String,
+        #[serde(default)]
+        extract: ArchiveExtraction,
+        /// Name of the jar to extract, required if the archive contains multiple mods
+        #[serde(default)]
+        extract_file: Option<String>,
+    },
     #[serde(rename = "repository")]
     #[serde(rename_all = "camelCase")]
     Repository { repository: String, artifact: String },
     #[serde(rename = "local")]
     #[serde(rename_all = "camelCase")]
-    Local { file_name: String }
+    Local { file_name: String },
+    #[serde(rename = "modrinth")]
+    #[serde(rename_all = "camelCase")]
+    Modrinth { project_id: String, version_id: String },
+    #[serde(rename = "curseforge")]
+    #[serde(rename_all = "camelCase")]
+    CurseForge { project_id: u64, file_id: u64 },
+    #[serde(rename = "file")]
+    #[serde(rename_all = "camelCase")]
+    LocalFile { path: String }
+}
+
+/// Maximum number of nested archives which are extracted to find the jar
+pub const MAX_ARCHIVE_DEPTH: u32 = 4;
+
+///
+/// How the downloaded file of a SkipAd mod is extracted: `false`, `true` for a single archive,
+/// the number of nested archives, or `"auto"` to descend until the jar is found
+///
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(untagged)]
+pub enum ArchiveExtraction {
+    Enabled(bool),
+    Depth(u32),
+    Mode(ArchiveExtractionMode),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ArchiveExtractionMode {
+    #[serde(rename = "auto")]
+    Auto,
+}
+
+impl Default for ArchiveExtraction {
+    fn default() -> Self {
+        ArchiveExtraction::Enabled(false)
+    }
+}
+
+impl ArchiveExtraction {
+    /// Number of nested archives which might be extracted, 0 if the download is the jar itself
+    pub fn max_depth(&self) -> u32 {
+        match self {
+            ArchiveExtraction::Enabled(false) => 0,
+            ArchiveExtraction::Enabled(true) => 1,
+            ArchiveExtraction::Depth(depth) => *depth,
+            ArchiveExtraction::Mode(ArchiveExtractionMode::Auto) => MAX_ARCHIVE_DEPTH,
+        }
+    }
 }
 
 impl ModSource {
@@ -263,6 +586,9 @@ impl ModSource {
                 ModSource::SkipAd { artifact_name, .. } => format!("{}.jar", artifact_name),
                 ModSource::Repository { repository: _repository, artifact } => get_maven_artifact_path(artifact)?,
                 ModSource::Local { file_name } => file_name.clone(),
+                ModSource::Modrinth { project_id, version_id } => format!("modrinth/{}/{}.jar", project_id, version_id),
+                ModSource::CurseForge { project_id, file_id } => format!("curseforge/{}/{}.jar", project_id, file_id),
+                ModSource::LocalFile { path } => path.clone(),
             }
         )
     }
@@ -271,13 +597,54 @@ impl ModSource {
 ///
 /// JSON struct of subsystem
 ///
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "name")]
 pub enum LoaderSubsystem {
     #[serde(rename = "fabric")]
     Fabric { manifest: String, mod_directory: String },
     #[serde(rename = "forge")]
-    Forge { manifest: String, mod_directory: String  },
+    Forge {
+        manifest: String,
+        mod_directory: String,
+        /// Installer of Forge 1.13+, its processors have to run before launching
+        #[serde(default)]
+        installer: Option<String>,
+    },
+    #[serde(rename = "quilt")]
+    Quilt { manifest: String, mod_directory: String },
+    #[serde(rename = "neoforge")]
+    NeoForge {
+        /// Version profile, if empty the profile of the installer is used
+        #[serde(default)]
+        manifest: String,
+        mod_directory: String,
+        /// Installer of NeoForge, its processors have to run before launching
+        #[serde(default)]
+        installer: Option<String>,
+    },
+}
+
+impl LoaderSubsystem {
+    /// Name of the loader as used by mod platforms
+    pub fn loader_name(&self) -> &'static str {
+        match self {
+            LoaderSubsystem::Fabric { .. } => "fabric",
+            LoaderSubsystem::Forge { .. } => "forge",
+            LoaderSubsystem::Quilt { .. } => "quilt",
+            LoaderSubsystem::NeoForge { .. } => "neoforge",
+        }
+    }
+
+    /// Files describing a mod which can be loaded by the loader
+    pub fn mod_descriptors(&self) -> &'static [&'static str] {
+        match self {
+            LoaderSubsystem::Fabric { .. } => &["fabric.mod.json"],
+            LoaderSubsystem::Forge { .. } => &["META-INF/mods.toml", "mcmod.info"],
+            // Quilt is able to load Fabric mods as well
+            LoaderSubsystem::Quilt { .. } => &["quilt.mod.json", "fabric.mod.json"],
+            LoaderSubsystem::NeoForge { .. } => &["META-INF/neoforge.mods.toml", "META-INF/mods.toml"],
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
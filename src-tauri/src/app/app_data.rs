@@ -17,27 +17,96 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
  
-use std::{path::Path, collections::HashMap};
+use std::{path::{Path, PathBuf}, collections::HashMap, sync::{Arc, OnceLock}, time::Duration};
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
-use crate::{auth::ClientAccount, minecraft::auth::MinecraftAccount};
+use tokio_util::sync::CancellationToken;
+use crate::{app::api::LoaderMod, auth::ClientAccount, minecraft::{auth::MinecraftAccount, launcher::{GameDirLayout, ModLinkMode}}, utils::{DownloadOptions, DownloadThrottle, HostConnectionLimit}};
 
 fn default_concurrent_downloads() -> i32 {
     10
 }
 
+fn default_concurrent_mod_downloads() -> i32 {
+    4
+}
+
+fn default_concurrent_asset_downloads() -> i32 {
+    24
+}
+
+fn default_max_connections_per_host() -> u32 {
+    6
+}
+
+fn default_download_attempts() -> u32 {
+    3
+}
+
+fn default_download_retry_delay() -> u64 {
+    500
+}
+
+fn default_download_timeout_secs() -> u64 {
+    30
+}
+
+fn default_prelaunch_timeout_secs() -> u64 {
+    600
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct LauncherOptions {
     #[serde(rename = "keepLauncherOpen")]
     pub keep_launcher_open: bool,
+    #[serde(rename = "offline", default)]
+    pub offline: bool,
     #[serde(rename = "customDataPath", default)]
     pub custom_data_path: String,
+    #[serde(rename = "bundledModsPath", default)]
+    pub bundled_mods_path: String,
+    #[serde(rename = "customModCachePath", default)]
+    pub custom_mod_cache_path: String,
+    /// Size limit of the mod cache in megabytes, 0 for no limit
+    #[serde(rename = "modCacheLimit", default)]
+    pub mod_cache_limit_mb: u64,
+    #[serde(rename = "modLinkMode", default)]
+    pub mod_link_mode: ModLinkMode,
+    #[serde(rename = "resolveDependencies", default)]
+    pub resolve_dependencies: bool,
+    #[serde(rename = "repairModCache", default)]
+    pub repair_mod_cache: bool,
+    #[serde(rename = "customGameDirPath", default)]
+    pub custom_game_dir_path: String,
+    #[serde(rename = "gameDirLayout", default)]
+    pub game_dir_layout: GameDirLayout,
+    /// Current directory of the game, empty uses the game directory
+    #[serde(rename = "workingDir", default)]
+    pub working_dir: String,
     #[serde(rename = "showNightlyBuilds")]
     pub show_nightly_builds: bool,
     #[serde(rename = "memoryPercentage")]
     pub memory_percentage: i32,
+    /// Initial heap size in megabytes, 0 lets the JVM decide
+    #[serde(rename = "minMemory", default)]
+    pub min_memory_mb: u64,
+    /// Maximum heap size in megabytes, 0 uses the memory percentage
+    #[serde(rename = "maxMemory", default)]
+    pub max_memory_mb: u64,
+    #[serde(rename = "customJvmArgs", default)]
+    pub custom_jvm_args: Vec<String>,
+    #[serde(rename = "modDenylist", default)]
+    pub mod_denylist: Vec<String>,
+    #[serde(rename = "mainClassOverride", default)]
+    pub main_class_override: String,
+    /// Environment variables of the game, values may refer to variables of the launcher with `${VAR}`
+    #[serde(rename = "gameEnv", default)]
+    pub game_env: HashMap<String, String>,
+    /// Keep the libraries of modded profiles even if the inherited profile has a higher version
+    #[serde(rename = "preferModdedLibraries", default)]
+    pub prefer_modded_libraries: bool,
     #[serde(rename = "customJavaPath", default)]
     pub custom_java_path: String,
     #[serde(rename = "selectedBranch")]
@@ -52,11 +121,147 @@ pub(crate) struct LauncherOptions {
     pub current_account: Option<MinecraftAccount>,
     #[serde(rename = "branchOptions", default)]
     pub branch_options: HashMap<String, BranchOptions>,
+    /// Settings of the launcher itself, stored next to the other options
+    #[serde(flatten)]
+    pub config: LauncherConfig,
+    #[serde(rename = "curseForgeApiKey", default)]
+    pub curseforge_api_key: String,
+    #[serde(rename = "quickPlayServer", default)]
+    pub quick_play_server: String,
+    /// Size of the game window in pixels, 0 keeps the size of the game
+    #[serde(rename = "windowWidth", default)]
+    pub window_width: u32,
+    #[serde(rename = "windowHeight", default)]
+    pub window_height: u32,
+    #[serde(rename = "fullscreen", default)]
+    pub fullscreen: bool,
+    /// Launches the demo of the game, which does not require owning it
+    #[serde(rename = "demo", default)]
+    pub demo: bool,
+    #[serde(rename = "preLaunchCommand", default)]
+    pub pre_launch_command: String,
+    #[serde(rename = "postExitCommand", default)]
+    pub post_exit_command: String,
+    /// Level of the launcher log, e.g. `info` or `trace`, applied on the next start and overridden by `RUST_LOG`
+    #[serde(rename = "logLevel", default)]
+    pub log_level: String,
+}
+
+///
+/// How the launcher behaves independent of the launched game, e.g. how it downloads and connects.
+///
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LauncherConfig {
     #[serde(rename = "concurrentDownloads", default = "default_concurrent_downloads")]
-    pub concurrent_downloads: i32
+    pub concurrent_downloads: i32,
+    #[serde(rename = "concurrentModDownloads", default = "default_concurrent_mod_downloads")]
+    pub concurrent_mod_downloads: i32,
+    /// Asset objects are tiny, so many more of them are downloaded at once
+    #[serde(rename = "concurrentAssetDownloads", default = "default_concurrent_asset_downloads")]
+    pub concurrent_asset_downloads: i32,
+    #[serde(rename = "downloadAttempts", default = "default_download_attempts")]
+    pub download_attempts: u32,
+    /// Delay before the first retry in milliseconds
+    #[serde(rename = "downloadRetryDelay", default = "default_download_retry_delay")]
+    pub download_retry_delay: u64,
+    #[serde(rename = "downloadTimeout", default = "default_download_timeout_secs")]
+    pub download_timeout_secs: u64,
+    /// Wall-clock limit in seconds for everything before the game is started, 0 disables it
+    #[serde(rename = "prelaunchTimeout", default = "default_prelaunch_timeout_secs")]
+    pub prelaunch_timeout_secs: u64,
+    /// Maximum download speed in KiB/s, 0 is unlimited
+    #[serde(rename = "maxDownloadSpeed", default)]
+    pub max_download_speed_kb: u64,
+    /// Simultaneous connections to a single host, 0 does not limit them
+    #[serde(rename = "maxConnectionsPerHost", default = "default_max_connections_per_host")]
+    pub max_connections_per_host: u32,
+    /// Allows downloads and download pages over plain HTTP
+    #[serde(rename = "allowInsecureDownloads", default)]
+    pub allow_insecure_downloads: bool,
+    /// HTTP or SOCKS5 proxy used for all requests, applied on the next start of the launcher
+    #[serde(rename = "proxyUrl", default)]
+    pub proxy_url: String,
+    /// Replaces the default user agent of the launcher, empty uses the default
+    #[serde(rename = "userAgent", default)]
+    pub user_agent: String,
+    /// PEM bundle of certificates trusted in addition to the system certificates, e.g. of a corporate proxy
+    #[serde(rename = "customCaCert", default)]
+    pub custom_ca_cert: String,
+    /// Disables certificate validation entirely, only meant as a last resort
+    #[serde(rename = "dangerAcceptInvalidCerts", default)]
+    pub danger_accept_invalid_certs: bool,
+    /// Shared by all downloads using this config, so the limits apply to a launch as a whole
+    #[serde(skip)]
+    limits: OnceLock<DownloadLimits>,
+}
+
+#[derive(Debug)]
+struct DownloadLimits {
+    throttle: Option<Arc<DownloadThrottle>>,
+    connection_limit: Option<Arc<HostConnectionLimit>>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl LauncherConfig {
+
+    pub fn download_options(&self, cancellation: &CancellationToken) -> DownloadOptions {
+        let limits = self.limits.get_or_init(|| DownloadLimits {
+            throttle: (self.max_download_speed_kb > 0)
+                .then(|| Arc::new(DownloadThrottle::new(self.max_download_speed_kb * 1024))),
+            connection_limit: (self.max_connections_per_host > 0)
+                .then(|| Arc::new(HostConnectionLimit::new(self.max_connections_per_host as usize))),
+        });
+
+        DownloadOptions {
+            max_attempts: self.download_attempts,
+            retry_delay: Duration::from_millis(self.download_retry_delay),
+            cancellation: cancellation.clone(),
+            idle_timeout: Duration::from_secs(self.download_timeout_secs.max(1)),
+            allow_insecure: self.allow_insecure_downloads,
+            user_agent: Some(self.user_agent.trim().to_string()).filter(|x| !x.is_empty()),
+            throttle: limits.throttle.clone(),
+            connection_limit: limits.connection_limit.clone(),
+            allowed_hosts: None,
+        }
+    }
+
+    pub fn prelaunch_timeout(&self) -> Option<Duration> {
+        (self.prelaunch_timeout_secs > 0).then(|| Duration::from_secs(self.prelaunch_timeout_secs))
+    }
+
+    /// Settings for the launcher log, the proxy is left out since it might contain credentials
+    pub fn log_summary(&self) -> String {
+        format!(
+            "Downloads: {} concurrent, {} mods, {} assets, {} attempt(s), {} KiB/s, {} per host, insecure: {}",
+            self.concurrent_downloads, self.concurrent_mod_downloads, self.concurrent_asset_downloads,
+            self.download_attempts, self.max_download_speed_kb, self.max_connections_per_host, self.allow_insecure_downloads
+        )
+    }
+
+}
+
+impl Default for LauncherConfig {
+    fn default() -> Self {
+        Self {
+            concurrent_downloads: default_concurrent_downloads(),
+            concurrent_mod_downloads: default_concurrent_mod_downloads(),
+            concurrent_asset_downloads: default_concurrent_asset_downloads(),
+            download_attempts: default_download_attempts(),
+            download_retry_delay: default_download_retry_delay(),
+            download_timeout_secs: default_download_timeout_secs(),
+            prelaunch_timeout_secs: default_prelaunch_timeout_secs(),
+            max_download_speed_kb: 0,
+            max_connections_per_host: default_max_connections_per_host(),
+            allow_insecure_downloads: false,
+            proxy_url: String::new(),
+            user_agent: String::new(),
+            custom_ca_cert: String::new(),
+            danger_accept_invalid_certs: false,
+            limits: OnceLock::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub(crate) struct BranchOptions {
     #[serde(rename = "modStates", default)]
     pub mod_states: HashMap<String, bool>,
@@ -64,6 +269,26 @@ pub(crate) struct BranchOptions {
     pub custom_mod_states: HashMap<String, bool>,
 }
 
+impl BranchOptions {
+
+    /// Whether the mod is enabled, required mods can not be disabled by the user
+    pub fn is_mod_enabled(&self, loader_mod: &LoaderMod) -> bool {
+        loader_mod.required || self.mod_states.get(&loader_mod.name).copied().unwrap_or(loader_mod.enabled)
+    }
+
+    pub fn set_mod_enabled(&mut self, name: &str, enabled: bool) {
+        self.mod_states.insert(name.to_string(), enabled);
+    }
+
+    /// Applies the saved choices of the user to the mods of the launch manifest
+    pub fn apply_mod_states(&self, mods: &mut [LoaderMod]) {
+        for loader_mod in mods.iter_mut() {
+            loader_mod.enabled = self.is_mod_enabled(loader_mod);
+        }
+    }
+
+}
+
 impl LauncherOptions {
 
     pub async fn load(app_data: &Path) -> Result<Self> {
@@ -77,6 +302,40 @@ impl LauncherOptions {
         Ok(())
     }
 
+    /// Proxy of the stored options, read synchronously since the HTTP client is created on first use
+    pub fn configured_proxy(app_data: &Path) -> Option<String> {
+        let options = Self::load_sync(app_data)?;
+        Some(options.config.proxy_url.trim().to_string()).filter(|x| !x.is_empty())
+    }
+
+    /// User agent override of the stored options, read synchronously like the proxy
+    pub fn configured_user_agent(app_data: &Path) -> Option<String> {
+        let options = Self::load_sync(app_data)?;
+        Some(options.config.user_agent.trim().to_string()).filter(|x| !x.is_empty())
+    }
+
+    /// Certificate bundle of the stored options, read synchronously like the proxy
+    pub fn configured_ca_cert(app_data: &Path) -> Option<PathBuf> {
+        let options = Self::load_sync(app_data)?;
+        Some(options.config.custom_ca_cert.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// Log level of the stored options, read synchronously since logging is set up before anything else
+    pub fn configured_log_level(app_data: &Path) -> Option<String> {
+        let options = Self::load_sync(app_data)?;
+        Some(options.log_level.trim().to_string()).filter(|x| !x.is_empty())
+    }
+
+    pub fn accepts_invalid_certs(app_data: &Path) -> bool {
+        Self::load_sync(app_data).is_some_and(|options| options.config.danger_accept_invalid_certs)
+    }
+
+    fn load_sync(app_data: &Path) -> Option<Self> {
+        let content = std::fs::read(app_data.join("options.json")).ok()?;
+        serde_json::from_slice::<Self>(&content).ok()
+    }
 
 }
 
@@ -84,9 +343,26 @@ impl Default for LauncherOptions {
     fn default() -> Self {
         Self {
             keep_launcher_open: false,
+            offline: false,
             custom_data_path: String::new(),
+            bundled_mods_path: String::new(),
+            custom_mod_cache_path: String::new(),
+            mod_cache_limit_mb: 0,
+            mod_link_mode: ModLinkMode::Copy,
+            resolve_dependencies: false,
+            repair_mod_cache: false,
+            custom_game_dir_path: String::new(),
+            working_dir: String::new(),
+            game_dir_layout: GameDirLayout::Branch,
             show_nightly_builds: false,
             memory_percentage: 80, // 80% memory of computer allocated to game
+            min_memory_mb: 0,
+            max_memory_mb: 0,
+            custom_jvm_args: Vec::new(),
+            mod_denylist: Vec::new(),
+            main_class_override: String::new(),
+            game_env: HashMap::new(),
+            prefer_modded_libraries: false,
             custom_java_path: String::new(),
             selected_branch: None,
             selected_build: None,
@@ -94,7 +370,16 @@ impl Default for LauncherOptions {
             current_account: None,
             branch_options: HashMap::new(),
             skip_advertisement: false,
-            concurrent_downloads: 10
+            config: LauncherConfig::default(),
+            curseforge_api_key: String::new(),
+            quick_play_server: String::new(),
+            window_width: 0,
+            window_height: 0,
+            fullscreen: false,
+            demo: false,
+            pre_launch_command: String::new(),
+            post_exit_command: String::new(),
+            log_level: String::new(),
         }
     }
 }
\ No newline at end of file
@@ -17,30 +17,38 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
  
-use std::{sync::{Arc, Mutex}, thread, path::PathBuf};
+use std::{collections::HashMap, sync::{Arc, Mutex}, thread, path::PathBuf};
 
 use anyhow::anyhow;
 use tokio::fs;
-use tracing::{error, info, debug};
+use tracing::{error, info, debug, warn};
 use tauri::{Manager, Window};
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-use crate::{auth::{ClientAccountAuthenticator, ClientAccount}, minecraft::{auth::{self, MinecraftAccount}, launcher::{LauncherData, LaunchingParameter}, prelauncher, progress::ProgressUpdate}, HTTP_CLIENT, LAUNCHER_DIRECTORY, LAUNCHER_VERSION};
+use crate::{auth::{ClientAccountAuthenticator, ClientAccount}, minecraft::{auth::{self, MinecraftAccount}, launcher::{LauncherData, LaunchingParameter}, prelauncher, progress::{JsonProgressWriter, ProgressReceiver, ProgressTracker, ProgressUpdate}, version::LibraryConflictResolution}, HTTP_CLIENT, LAUNCHER_DIRECTORY, LAUNCHER_VERSION};
+use crate::error::LauncherError;
 use crate::app::api::{Branches, Changelog, ContentDelivery, News};
 use crate::utils::percentage_of_total_memory;
 
-use super::{api::{ApiEndpoints, Build, LoaderMod, ModSource}, app_data::LauncherOptions};
+use crate::minecraft::dependencies::DependencyGraph;
+use crate::minecraft::launch_cache::{LaunchCache, LAUNCH_MANIFEST, RESOLVED_DEPENDENCIES};
+use crate::minecraft::mod_metadata::{self, ResolvedMod};
+use super::{api::{ApiEndpoints, Build, LaunchManifest, LoaderMod, ModSource}, app_data::{BranchOptions, LauncherOptions}, profiles::{Profile, Profiles}};
 
 pub type ShareableWindow = Arc<Mutex<Window>>;
 
 struct RunnerInstance {
-    terminator: tokio::sync::oneshot::Sender<()>,
+    cancellation: CancellationToken,
 }
 
 struct AppState {
     runner_instance: Arc<Mutex<Option<RunnerInstance>>>
 }
 
+/// File the progress of launches is streamed to as JSON lines, an opt-in for tools embedding the launcher
+const PROGRESS_JSON_ENV: &str = "LIQUIDLAUNCHER_PROGRESS_JSON";
+
 const ERROR_MSG: &str = "Try restarting the LiquidLauncher with administrator rights.\nIf this error persists, upload your log with the button below and report it to GitHub.";
 
 #[tauri::command]
@@ -83,8 +91,21 @@ async fn get_options() -> Result<LauncherOptions, String> {
 }
 
 #[tauri::command]
-async fn store_options(options: LauncherOptions) -> Result<(), String> {
+async fn store_options(mut options: LauncherOptions) -> Result<(), String> {
     let config_dir = LAUNCHER_DIRECTORY.config_dir();
+
+    // Mod states are only changed through set_mod_state, the options of the frontend do not overwrite them
+    let mut stored_branch_options = LauncherOptions::load(config_dir)
+        .await
+        .map(|stored| stored.branch_options)
+        .unwrap_or_default();
+    for (branch, branch_options) in options.branch_options.iter_mut() {
+        branch_options.mod_states = stored_branch_options.remove(branch).map(|x| x.mod_states).unwrap_or_default();
+    }
+    for (branch, stored) in stored_branch_options {
+        options.branch_options.entry(branch).or_default().mod_states = stored.mod_states;
+    }
+
     options.store(config_dir)
         .await
         .map_err(|e| format!("unable to store config data: {:?}", e))?;
@@ -92,6 +113,76 @@ async fn store_options(options: LauncherOptions) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+async fn get_mod_states(branch: String) -> Result<HashMap<String, bool>, String> {
+    Ok(stored_branch_options(&branch).await.mod_states)
+}
+
+/// Mod states are saved by set_mod_state, the options passed by the frontend do not carry them
+async fn stored_branch_options(branch: &str) -> BranchOptions {
+    LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir())
+        .await
+        .ok()
+        .and_then(|mut options| options.branch_options.remove(branch))
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+async fn set_mod_state(branch: String, mod_name: String, enabled: bool) -> Result<(), String> {
+    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    let mut options = LauncherOptions::load(config_dir)
+        .await
+        .map_err(|e| format!("unable to load config data: {:?}", e))?;
+
+    options.branch_options.entry(branch)
+        .or_default()
+        .set_mod_enabled(&mod_name, enabled);
+    options.store(config_dir)
+        .await
+        .map_err(|e| format!("unable to store config data: {:?}", e))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_profiles() -> Result<Vec<Profile>, String> {
+    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    let profiles = Profiles::load(config_dir)
+        .await
+        .map_err(|e| format!("unable to load profiles: {:?}", e))?;
+
+    Ok(profiles.profiles)
+}
+
+#[tauri::command]
+async fn add_profile(profile: Profile) -> Result<(), String> {
+    modify_profiles(|profiles| profiles.add(profile)).await
+}
+
+#[tauri::command]
+async fn update_profile(name: &str, profile: Profile) -> Result<(), String> {
+    modify_profiles(|profiles| profiles.update(name, profile)).await
+}
+
+#[tauri::command]
+async fn delete_profile(name: &str) -> Result<(), String> {
+    modify_profiles(|profiles| profiles.delete(name)).await
+}
+
+async fn modify_profiles<F>(modify: F) -> Result<(), String> where F: FnOnce(&mut Profiles) -> anyhow::Result<()> {
+    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    let mut profiles = Profiles::load(config_dir)
+        .await
+        .map_err(|e| format!("unable to load profiles: {:?}", e))?;
+
+    modify(&mut profiles).map_err(|e| format!("{}", e))?;
+    profiles.store(config_dir)
+        .await
+        .map_err(|e| format!("unable to store profiles: {:?}", e))?;
+
+    Ok(())
+}
+
 #[tauri::command]
 async fn request_branches() -> Result<Branches, String> {
     let branches = ApiEndpoints::branches()
@@ -119,8 +210,28 @@ async fn request_mods(mc_version: &str, subsystem: &str) -> Result<Vec<LoaderMod
     Ok(mods)
 }
 
+/// Recommended and custom mods of the build, like the frontend requests them for the selected build.
+/// Recommended mods can not be requested offline, only the custom mods are used then.
+async fn build_mods(build: &Build, offline: bool) -> Result<Vec<LoaderMod>, String> {
+    let mut mods = if offline {
+        warn!("Recommended mods of build {} are not available offline", build.build_id);
+        vec![]
+    } else {
+        request_mods(&build.mc_version, &build.subsystem).await?
+    };
+    mods.extend(get_custom_mods(&build.branch, &build.mc_version).await?);
+    Ok(mods)
+}
+
 #[tauri::command]
 async fn login_offline(username: &str) -> Result<MinecraftAccount, String> {
+    // Servers reject names which could not belong to a premium account
+    let valid_name = (1..=16).contains(&username.len())
+        && username.chars().all(|x| x.is_ascii_alphanumeric() || x == '_');
+    if !valid_name {
+        return Err("username must be 1 to 16 characters long and only contain letters, digits and underscores".to_string());
+    }
+
     let account = MinecraftAccount::auth_offline(username.to_string())
         .await;
 
@@ -185,7 +296,7 @@ async fn get_custom_mods(branch: &str, mc_version: &str) -> Result<Vec<LoaderMod
             // todo: pull name from JAR manifest
             let file_name_without_extension = file_name.replace(".jar", "");
             
-            mods.push(LoaderMod { required: false, enabled: true, name: file_name_without_extension, source: ModSource::Local { file_name } });
+            mods.push(LoaderMod { required: false, enabled: true, name: file_name_without_extension, source: ModSource::Local { file_name }, sha1: None, sha256: None, size: None });
         }
     }
 
@@ -253,6 +364,17 @@ fn handle_stderr(window: &ShareableWindow, data: &[u8]) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn open_progress_stream() -> Option<Box<dyn ProgressReceiver + Send + Sync>> {
+    let path = std::env::var_os(PROGRESS_JSON_ENV)?;
+    match std::fs::OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(file) => Some(Box::new(JsonProgressWriter::new(file))),
+        Err(e) => {
+            warn!("Unable to open progress stream {:?}: {:?}", path, e);
+            None
+        }
+    }
+}
+
 fn handle_progress(window: &ShareableWindow, progress_update: ProgressUpdate) -> anyhow::Result<()> {
     window.lock().map_err(|_| anyhow!("Window lock is poisoned"))?.emit("progress-update", &progress_update)?;
 
@@ -273,10 +395,14 @@ fn handle_log(window: &ShareableWindow, msg: &str) -> anyhow::Result<()> {
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn run_client(
     build_id: u32,
     options: LauncherOptions,
-    mods: Vec<LoaderMod>,
+    mut mods: Vec<LoaderMod>,
+    profile: Option<String>,
+    commit_id: Option<String>,
+    dry_run: Option<bool>,
     window: Window,
     app_state: tauri::State<'_, AppState>
 ) -> Result<(), String> {
@@ -284,6 +410,21 @@ async fn run_client(
     let shareable_window: ShareableWindow = Arc::new(Mutex::new(window));
 
     let minecraft_account = options.current_account.ok_or("no account selected")?;
+
+    // The token might have expired since the account has been refreshed by the frontend, the demo does not need a valid one
+    let minecraft_account = if options.offline || options.demo {
+        minecraft_account
+    } else {
+        let minecraft_account = minecraft_account.refresh().await
+            .map_err(|e| format!("unable to refresh account: {:?}", e))?;
+
+        // The frontend stores the refreshed account with the options, otherwise the outdated tokens are used again
+        if let Err(e) = shareable_window.lock().map_err(|_| anyhow!("Window lock is poisoned"))
+            .and_then(|window| Ok(window.emit("account-refreshed", &minecraft_account)?)) {
+            warn!("Unable to store the refreshed account: {:?}", e);
+        }
+        minecraft_account
+    };
     let (account_name, uuid, token, user_type) = match minecraft_account {
         MinecraftAccount::MsaAccount { msa: _, xbl: _, mca, profile, .. } => (profile.name, profile.id.to_string(), mca.data.access_token, "msa".to_string()),
         MinecraftAccount::LegacyMsaAccount { name, uuid, token, .. } => (name, uuid.to_string(), token, "msa".to_string()),
@@ -298,9 +439,26 @@ async fn run_client(
     // Random XUID
     let xuid = Uuid::new_v4().to_string();
 
-    let parameters = LaunchingParameter {
+    let mut parameters = LaunchingParameter {
         memory: percentage_of_total_memory(options.memory_percentage),
+        min_memory_mb: if options.min_memory_mb > 0 { Some(options.min_memory_mb) } else { None },
+        max_memory_mb: if options.max_memory_mb > 0 { Some(options.max_memory_mb) } else { None },
+        custom_jvm_args: options.custom_jvm_args.into_iter().filter(|x| !x.trim().is_empty()).collect(),
+        main_class_override: if !options.main_class_override.trim().is_empty() { Some(options.main_class_override.trim().to_string()) } else { None },
+        game_env: options.game_env.into_iter().filter(|(name, _)| !name.trim().is_empty()).collect(),
+        mod_denylist: options.mod_denylist.into_iter().map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).collect(),
+        library_conflicts: if options.prefer_modded_libraries { LibraryConflictResolution::Child } else { LibraryConflictResolution::Highest },
         custom_data_path: if !options.custom_data_path.is_empty() { Some(options.custom_data_path) } else { None },
+        bundled_mods_path: if !options.bundled_mods_path.is_empty() { Some(options.bundled_mods_path) } else { None },
+        custom_mod_cache_path: if !options.custom_mod_cache_path.is_empty() { Some(options.custom_mod_cache_path) } else { None },
+        mod_cache_max_bytes: if options.mod_cache_limit_mb > 0 { Some(options.mod_cache_limit_mb * 1024 * 1024) } else { None },
+        mod_link_mode: options.mod_link_mode,
+        resolve_dependencies: options.resolve_dependencies,
+        repair_mod_cache: options.repair_mod_cache,
+        dry_run: dry_run.unwrap_or_default(),
+        custom_game_dir_path: if !options.custom_game_dir_path.is_empty() { Some(options.custom_game_dir_path) } else { None },
+        game_dir_layout: options.game_dir_layout,
+        working_dir: if !options.working_dir.is_empty() { Some(options.working_dir) } else { None },
         custom_java_path: if !options.custom_java_path.is_empty() { Some(options.custom_java_path) } else { None },
         auth_player_name: account_name,
         auth_uuid: uuid,
@@ -309,9 +467,44 @@ async fn run_client(
         clientid: auth::AZURE_CLIENT_ID.to_string(),
         user_type,
         keep_launcher_open: options.keep_launcher_open,
-        concurrent_downloads: options.concurrent_downloads,
+        offline: options.offline,
         client_account,
-        skip_advertisement: skip_advertisement
+        skip_advertisement: skip_advertisement,
+        curseforge_api_key: if !options.curseforge_api_key.is_empty() { Some(options.curseforge_api_key) } else { None },
+        quick_play_server: if !options.quick_play_server.trim().is_empty() { Some(options.quick_play_server.trim().to_string()) } else { None },
+        width: if options.window_width > 0 { Some(options.window_width) } else { None },
+        height: if options.window_height > 0 { Some(options.window_height) } else { None },
+        fullscreen: options.fullscreen,
+        demo: options.demo,
+        pre_launch_command: if !options.pre_launch_command.trim().is_empty() { Some(options.pre_launch_command) } else { None },
+        post_exit_command: if !options.post_exit_command.trim().is_empty() { Some(options.post_exit_command) } else { None },
+    };
+
+    // A profile selects its own build and replaces some of the options
+    let selected_build_id = build_id;
+    let build_id = match profile {
+        Some(name) => {
+            let profiles = Profiles::load(LAUNCHER_DIRECTORY.config_dir())
+                .await
+                .map_err(|e| format!("unable to load profiles: {:?}", e))?;
+            let profile = profiles.get(&name).ok_or_else(|| format!("profile {} does not exist", name))?;
+
+            profile.apply(&mut parameters);
+            profile.build_id
+        }
+        None => build_id,
+    };
+
+    // A commit id pins the exact build, e.g. to reproduce a bug report
+    let build_id = match commit_id {
+        Some(commit_id) if !commit_id.trim().is_empty() => {
+            let build = ApiEndpoints::resolve_build_by_commit(&commit_id)
+                .await
+                .map_err(|e| format!("unable to resolve build of commit {}: {:?}", commit_id, e))?;
+            info!("Commit {} resolved to build {}", commit_id, build.build_id);
+            build.build_id
+        }
+        _ => build_id,
     };
 
     let runner_instance = &app_state.runner_instance;
@@ -321,14 +514,32 @@ async fn run_client(
     }
     
     info!("Loading launch manifest...");
-    let launch_manifest = ApiEndpoints::launch_manifest(build_id)
-        .await
-        .map_err(|e| format!("failed to fetch launch manifest of build {}: {:?}", build_id, e))?;
+    let mut launch_manifest = if parameters.offline {
+        LaunchCache::new(&parameters.data_directory(), build_id).read::<LaunchManifest>(LAUNCH_MANIFEST)
+            .await
+            .map_err(|e| format!("failed to load cached launch manifest of build {}: {:?}", build_id, e))?
+    } else {
+        ApiEndpoints::launch_manifest(build_id, parameters.dry_run)
+            .await
+            .map_err(|e| format!("failed to fetch launch manifest of build {}: {:?}", build_id, e))?
+    };
+
+    // The mods of the frontend belong to the selected build, a profile or commit might launch a different one
+    if build_id != selected_build_id {
+        info!("Loading mods of build {}...", build_id);
+        mods = build_mods(&launch_manifest.build, parameters.offline).await?;
+    }
+
+    // Optional mods of the manifest follow the choice of the user, which is saved per branch
+    let branch_options = stored_branch_options(&launch_manifest.build.branch).await;
+    branch_options.apply_mod_states(&mut launch_manifest.mods);
+    branch_options.apply_mod_states(&mut mods);
 
-    let (terminator_tx, terminator_rx) = tokio::sync::oneshot::channel();
+    let config = options.config;
+    let cancellation = CancellationToken::new();
 
     *runner_instance.lock().map_err(|e| format!("unable to lock runner instance: {:?}", e))?
-        = Some(RunnerInstance { terminator: terminator_tx });
+        = Some(RunnerInstance { cancellation: cancellation.clone() });
 
     let copy_of_runner_instance = runner_instance.clone();
 
@@ -347,22 +558,31 @@ async fn run_client(
                     on_log: handle_log,
                     hide_window: |w| w.lock().unwrap().hide().unwrap(),
                     data: Box::new(shareable_window.clone()),
-                    terminator: terminator_rx
+                    cancellation,
+                    progress: ProgressTracker::default(),
+                    progress_stream: open_progress_stream(),
                 };
 
-                if let Err(e) = prelauncher::launch(
-                    launch_manifest,
-                    parameters,
-                    mods,
-                    launcher_data
-                ).await {
-                    if !keep_launcher_open {
-                        shareable_window.lock().unwrap().show().unwrap();
+                let result = async {
+                    if let Some(game) = prelauncher::launch(launch_manifest, parameters, &config, mods, launcher_data).await? {
+                        let _ = shareable_window.lock().unwrap().emit("client-started", game.pid);
+                        game.exit.await?;
+                    }
+                    Ok::<_, anyhow::Error>(())
+                }.await;
+
+                if let Err(e) = result {
+                    if matches!(e.downcast_ref::<LauncherError>(), Some(LauncherError::Cancelled)) {
+                        info!("Launch has been cancelled");
+                    } else {
+                        if !keep_launcher_open {
+                            shareable_window.lock().unwrap().show().unwrap();
+                        }
+
+                        let message = format!("An error occourd:\n\n{:?}", e);
+                        shareable_window.lock().unwrap().emit("client-error", format!("{}\n\n{}", message, ERROR_MSG)).unwrap();
+                        handle_stderr(&shareable_window, message.as_bytes()).unwrap();
                     }
-
-                    let message = format!("An error occourd:\n\n{:?}", e);
-                    shareable_window.lock().unwrap().emit("client-error", format!("{}\n\n{}", message, ERROR_MSG)).unwrap();
-                    handle_stderr(&shareable_window, message.as_bytes()).unwrap();
                 };
 
                 *copy_of_runner_instance.lock().map_err(|e| format!("unable to lock runner instance: {:?}", e)).unwrap()
@@ -383,7 +603,7 @@ async fn terminate(app_state: tauri::State<'_, AppState>) -> Result<(), String>
 
     if let Some(inst) = lck.take() {
         info!("Sending sigterm");
-        inst.terminator.send(()).unwrap();
+        inst.cancellation.cancel();
     }
     Ok(())
 }
@@ -431,6 +651,68 @@ async fn default_data_folder_path() -> Result<String, String> {
     }
 }
 
+/// Removes mods of the game directory of the build which are neither mods of the build, one of the given mods nor on the allowlist
+#[tauri::command]
+async fn prune_mods(build_id: u32, options: LauncherOptions, mods: Vec<LoaderMod>, allowlist: Vec<String>, dry_run: bool) -> Result<Vec<String>, String> {
+    let manifest = ApiEndpoints::launch_manifest(build_id, dry_run).await
+        .map_err(|e| format!("unable to request launch manifest: {:?}", e))?;
+
+    let data_directory = if !options.custom_data_path.is_empty() {
+        PathBuf::from(&options.custom_data_path)
+    } else {
+        LAUNCHER_DIRECTORY.data_dir().to_path_buf()
+    };
+    let game_directories = if !options.custom_game_dir_path.is_empty() {
+        PathBuf::from(&options.custom_game_dir_path)
+    } else {
+        data_directory.join("gameDir")
+    };
+    let mods_path = game_directories.join(options.game_dir_layout.directory_name(&manifest.build)).join("mods");
+
+    let allowlist = allowlist.into_iter()
+        .chain(mods.iter().flat_map(prelauncher::mod_file_name_variants))
+        .collect::<Vec<_>>();
+    let pruned = prelauncher::prune_unmanaged_mods(&manifest, &mods_path, &allowlist, dry_run).await
+        .map_err(|e| format!("unable to prune mods: {:?}", e))?;
+    Ok(pruned.iter().map(|path| path.display().to_string()).collect())
+}
+
+/// Lists the mods of the build with the metadata of their jars in the game directory, nothing is downloaded
+#[tauri::command]
+async fn get_installed_mods(build_id: u32, options: LauncherOptions, mut mods: Vec<LoaderMod>) -> Result<Vec<ResolvedMod>, String> {
+    let mut manifest = ApiEndpoints::launch_manifest(build_id, false).await
+        .map_err(|e| format!("unable to request launch manifest: {:?}", e))?;
+
+    // The same mods as for a launch, so the listed file names match the placed ones
+    let branch_options = stored_branch_options(&manifest.build.branch).await;
+    branch_options.apply_mod_states(&mut manifest.mods);
+    branch_options.apply_mod_states(&mut mods);
+
+    let data_directory = if !options.custom_data_path.is_empty() {
+        PathBuf::from(&options.custom_data_path)
+    } else {
+        LAUNCHER_DIRECTORY.data_dir().to_path_buf()
+    };
+    let game_directories = if !options.custom_game_dir_path.is_empty() {
+        PathBuf::from(&options.custom_game_dir_path)
+    } else {
+        data_directory.join("gameDir")
+    };
+    let mods_path = game_directories.join(options.game_dir_layout.directory_name(&manifest.build)).join("mods");
+
+    // Dependencies are part of the launch as well, the ones of the last launch are cached
+    let launch_cache = LaunchCache::new(&data_directory, build_id);
+    if options.resolve_dependencies && launch_cache.contains(RESOLVED_DEPENDENCIES) {
+        let dependencies = launch_cache.read::<DependencyGraph>(RESOLVED_DEPENDENCIES).await
+            .map_err(|e| format!("unable to read resolved dependencies: {:?}", e))?;
+        mods.extend(dependencies.mods);
+    }
+
+    let mod_denylist = options.mod_denylist.iter().map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).collect::<Vec<_>>();
+    mod_metadata::resolve_mods(&manifest, &mods, &mod_denylist, &mods_path).await
+        .map_err(|e| format!("unable to read installed mods: {:?}", e))
+}
+
 #[tauri::command]
 async fn clear_data(options: LauncherOptions) -> Result<(), String> {
     let data_directory = if !options.custom_data_path.is_empty() {
@@ -446,6 +728,12 @@ async fn clear_data(options: LauncherOptions) -> Result<(), String> {
         .map(std::fs::remove_dir_all)
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| format!("unable to clear data: {:?}", e))?;
+
+    // A custom mod cache only contains downloads, unlike a custom game directory
+    let mod_cache = std::path::Path::new(&options.custom_mod_cache_path);
+    if !options.custom_mod_cache_path.is_empty() && mod_cache.exists() {
+        std::fs::remove_dir_all(mod_cache).map_err(|e| format!("unable to clear mod cache: {:?}", e))?;
+    }
     Ok(())
 }
 
@@ -492,6 +780,12 @@ pub fn gui_main() {
             check_health,
             get_options,
             store_options,
+            get_mod_states,
+            set_mod_state,
+            get_profiles,
+            add_profile,
+            update_profile,
+            delete_profile,
             request_branches,
             request_builds,
             request_mods,
@@ -505,6 +799,8 @@ pub fn gui_main() {
             fetch_news,
             fetch_changelog,
             clear_data,
+            prune_mods,
+            get_installed_mods,
             mem_percentage,
             default_data_folder_path,
             terminate,
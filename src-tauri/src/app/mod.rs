@@ -22,3 +22,4 @@ pub mod gui;
 pub mod api;
 pub mod webview;
 pub mod app_data;
+pub mod profiles;
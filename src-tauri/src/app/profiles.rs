@@ -0,0 +1,146 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::minecraft::launcher::LaunchingParameter;
+
+///
+/// A named launch configuration, which overrides the launcher options for a specific build
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Profile {
+    pub name: String,
+    #[serde(rename = "buildId")]
+    pub build_id: u32,
+    #[serde(rename = "gameDirPath", default)]
+    pub game_dir_path: Option<String>,
+    #[serde(default)]
+    pub overrides: ProfileOverrides,
+}
+
+/// Launcher options which are replaced if specified, everything else is taken from the launcher options
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ProfileOverrides {
+    #[serde(rename = "minMemory", default)]
+    pub min_memory_mb: Option<u64>,
+    #[serde(rename = "maxMemory", default)]
+    pub max_memory_mb: Option<u64>,
+    #[serde(rename = "customJvmArgs", default)]
+    pub custom_jvm_args: Option<Vec<String>>,
+    #[serde(rename = "customJavaPath", default)]
+    pub custom_java_path: Option<String>,
+    #[serde(rename = "keepLauncherOpen", default)]
+    pub keep_launcher_open: Option<bool>,
+}
+
+impl Profile {
+
+    /// Applies the profile to the parameters created from the launcher options
+    pub fn apply(&self, parameter: &mut LaunchingParameter) {
+        if let Some(game_dir_path) = &self.game_dir_path {
+            parameter.custom_game_dir_path = Some(game_dir_path.clone());
+        }
+
+        let overrides = &self.overrides;
+        if let Some(min_memory_mb) = overrides.min_memory_mb {
+            parameter.min_memory_mb = Some(min_memory_mb);
+        }
+        if let Some(max_memory_mb) = overrides.max_memory_mb {
+            parameter.max_memory_mb = Some(max_memory_mb);
+        }
+        if let Some(custom_jvm_args) = &overrides.custom_jvm_args {
+            parameter.custom_jvm_args = custom_jvm_args.clone();
+        }
+        if let Some(custom_java_path) = &overrides.custom_java_path {
+            parameter.custom_java_path = Some(custom_java_path.clone());
+        }
+        if let Some(keep_launcher_open) = overrides.keep_launcher_open {
+            parameter.keep_launcher_open = keep_launcher_open;
+        }
+    }
+
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Profiles {
+    pub profiles: Vec<Profile>,
+}
+
+impl Profiles {
+
+    pub async fn load(app_data: &Path) -> Result<Self> {
+        let path = app_data.join("profiles.json");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        // load the profiles from the file
+        Ok(serde_json::from_slice::<Self>(&fs::read(path).await?)?)
+    }
+
+    pub async fn store(&self, app_data: &Path) -> Result<()> {
+        // store the profiles in the file
+        fs::write(app_data.join("profiles.json"), serde_json::to_string(&self)?).await?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|x| x.name == name)
+    }
+
+    pub fn add(&mut self, profile: Profile) -> Result<()> {
+        if self.get(&profile.name).is_some() {
+            bail!("profile {} already exists", profile.name);
+        }
+
+        self.profiles.push(profile);
+        Ok(())
+    }
+
+    /// Replaces the profile with the given name, the profile might be renamed
+    pub fn update(&mut self, name: &str, profile: Profile) -> Result<()> {
+        if profile.name != name && self.get(&profile.name).is_some() {
+            bail!("profile {} already exists", profile.name);
+        }
+
+        match self.profiles.iter_mut().find(|x| x.name == name) {
+            Some(existing) => *existing = profile,
+            None => bail!("profile {} does not exist", name),
+        }
+
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<()> {
+        let count = self.profiles.len();
+        self.profiles.retain(|x| x.name != name);
+
+        if self.profiles.len() == count {
+            bail!("profile {} does not exist", name);
+        }
+
+        Ok(())
+    }
+
+}
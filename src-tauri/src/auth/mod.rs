@@ -157,7 +157,7 @@ impl ClientAccountAuthenticator {
             .set_auth_uri(auth_url)
             .set_token_uri(token_url);
         
-        let http_client = reqwest::ClientBuilder::new()
+        let http_client = crate::with_network_options(reqwest::ClientBuilder::new())
             .redirect(reqwest::redirect::Policy::none())
             .build()
             .context("Client should build")?;
@@ -25,5 +25,98 @@ pub enum LauncherError {
     InvalidVersionProfile(String),
     #[error("Unknown template parameter: {0}")]
     UnknownTemplateParameter(String),
+    #[error("Checksum mismatch of {name}: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String, name: String },
+    #[error("{name} can not be downloaded by the launcher, please download it manually from {url}")]
+    ManualDownloadRequired { name: String, url: String },
+    #[error("Local mod file of {name} is invalid: {reason}")]
+    InvalidLocalFile { name: String, reason: String },
+    #[error("Failed to download {url} after {attempts} attempt(s)")]
+    DownloadFailed { url: String, attempts: u32 },
+    #[error("Launch has been cancelled")]
+    Cancelled,
+    #[error("{0} is not available offline, launch this build once while online")]
+    NotAvailableOffline(String),
+    #[error("Not enough disk space: {} MiB required, but only {} MiB available", .needed / 1024 / 1024, .available / 1024 / 1024)]
+    InsufficientDiskSpace { needed: u64, available: u64 },
+    #[error("{name} is not a valid jar file")]
+    InvalidJar { name: String },
+    #[error("No compatible Java installation found, Java {required} is required")]
+    NoCompatibleJava { required: u32 },
+    #[error("Failed to download from any of the mirrors: {}", .urls.join(", "))]
+    AllMirrorsFailed { urls: Vec<String> },
+    #[error("Download timed out, no data has been received for {secs} seconds")]
+    DownloadTimeout { secs: u64 },
+    #[error("Download ended after {received} of {expected} bytes")]
+    IncompleteDownload { expected: u64, received: u64 },
+    #[error("Refusing to download from {url}, only HTTPS is allowed")]
+    InsecureUrl { url: String },
+    #[error("This build requires launch manifest version {found}, but only versions {} to {} are supported, please update your launcher", .supported.start(), .supported.end())]
+    UnsupportedManifestVersion { found: u32, supported: std::ops::RangeInclusive<u32> },
+    #[error("{} in the downloaded archive, it contains: {}", .expected.as_ref().map_or("There is no single mod jar".to_string(), |x| format!("{} is missing", x)), .contents.join(", "))]
+    JarNotFoundInArchive { expected: Option<String>, contents: Vec<String> },
+    #[error("The downloaded archive is nested deeper than {max_depth} archive(s)")]
+    ArchiveTooDeep { max_depth: u32 },
+    #[error("The downloaded archive extracts to more than {} MiB", .limit / 1024 / 1024)]
+    ArchiveTooLarge { limit: u64 },
+    #[error("The {hook} hook failed with exit code {}", .exit_code.map_or("unknown".to_string(), |x| x.to_string()))]
+    HookFailed { hook: String, exit_code: Option<i32> },
+    #[error("The {hook} hook did not finish within {secs} seconds")]
+    HookTimeout { hook: String, secs: u64 },
+    #[error("Installer processor {processor} failed with exit code {}", .exit_code.map_or("unknown".to_string(), |x| x.to_string()))]
+    ProcessorFailed { processor: String, exit_code: Option<i32> },
+    #[error("Minecraft version {id} does not exist, did you mean {}?", .suggestions.join(", "))]
+    UnknownVersion { id: String, suggestions: Vec<String> },
+    #[error("Mod {artifact} references repository {name}, but the manifest only defines [{}]", .available.join(", "))]
+    UnknownRepository { name: String, available: Vec<String>, artifact: String },
+    #[error("{name} is on the mod denylist, but it is required by this build")]
+    RequiredModDenied { name: String },
+    #[error("There is no build of commit {commit_id}")]
+    UnknownCommit { commit_id: String },
+    #[error("Commit id {commit_id} is ambiguous, it matches the builds {}", .builds.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", "))]
+    AmbiguousCommit { commit_id: String, builds: Vec<u32> },
+    #[error("Refusing to download from {url}, {host} is not one of the expected hosts [{}]", .allowed.join(", "))]
+    UnexpectedDownloadHost { url: String, host: String, allowed: Vec<String> },
+    #[error("Preparing the launch took longer than {secs} seconds")]
+    PrelaunchTimeout { secs: u64 },
+    #[error("{path} does not exist on the local filesystem")]
+    LocalSourceNotFound { path: String },
+    #[error("The game crashed with exit code {exit_code}:\n\n{report_excerpt}")]
+    GameCrashed { exit_code: i32, report_excerpt: String },
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Validation(String),
+}
+
+impl LauncherError {
+    /// Connection problems, timeouts and server errors are worth another try, client errors (4xx) are not
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LauncherError::DownloadTimeout { .. } | LauncherError::IncompleteDownload { .. } => true,
+            LauncherError::Network(error) => is_retryable_network_error(error),
+            LauncherError::Io(error) => is_retryable_io_error(error),
+            _ => false,
+        }
+    }
+}
+
+pub fn is_retryable_network_error(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => status.is_server_error(),
+        None => error.is_connect() || error.is_timeout() || error.is_request() || error.is_body(),
+    }
+}
+
+pub fn is_retryable_io_error(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::TimedOut
+            | std::io::ErrorKind::UnexpectedEof
+    )
 }
 
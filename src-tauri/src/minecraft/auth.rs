@@ -17,7 +17,7 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use azalea_auth::{
     cache::ExpiringValue, get_minecraft_token, get_ms_auth_token, get_ms_link_code, get_profile, refresh_ms_auth_token, AccessTokenResponse, AuthError, MinecraftAuthResponse, ProfileResponse, XboxLiveAuth
@@ -150,7 +150,7 @@ impl MinecraftAccount {
                         Err(e) => {
                             // can't refresh, re-authenticate required
                             error!("Error refreshing Microsoft auth token: {}", e);
-                            msa
+                            bail!("Microsoft session of {} has expired, please login again", profile.name);
                         }
                     }
                 } else {
@@ -0,0 +1,173 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Result;
+use async_zip::read::seek::ZipFileReader;
+use tokio::fs;
+use tracing::{debug, info, warn};
+
+use crate::utils::{sha1sum_async, sha256sum_async};
+
+struct CachedFile {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// Deletes the least recently used files of the cache until it is smaller than the limit.
+/// Files which are kept are never deleted, but count towards the size of the cache.
+///
+/// Returns the number of bytes freed.
+pub async fn gc(directory: &Path, max_bytes: u64, keep: &HashSet<PathBuf>) -> Result<u64> {
+    if !directory.exists() {
+        return Ok(0);
+    }
+
+    let mut files = Vec::new();
+    collect_files(directory, &mut files).await?;
+
+    let mut total = files.iter().map(|x| x.size).sum::<u64>();
+    debug!("Cache {} takes up {} of {} bytes", directory.display(), total, max_bytes);
+    if total <= max_bytes {
+        return Ok(0);
+    }
+
+    // Oldest first
+    files.sort_by_key(|x| x.last_used);
+
+    let mut freed = 0;
+    for file in files.into_iter().filter(|x| !keep.contains(&x.path) && !is_sidecar(&x.path)) {
+        if total <= max_bytes {
+            break;
+        }
+
+        info!("Removing {} from cache", file.path.display());
+        fs::remove_file(&file.path).await?;
+        total -= file.size;
+        freed += file.size;
+    }
+
+    Ok(freed)
+}
+
+/// Checksums a cached file is expected to have
+#[derive(Debug, Default)]
+pub struct ExpectedChecksum {
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+}
+
+/// Files ending with these are downloads in progress, which are resumed instead of being cached entries
+const IN_PROGRESS_EXTENSIONS: [&str; 3] = [".part", ".download", ".archive"];
+/// Files ending with these describe a cached entry, e.g. the validators of its download, and are no entries themselves
+const SIDECAR_EXTENSIONS: [&str; 1] = [".meta"];
+
+fn is_sidecar(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    SIDECAR_EXTENSIONS.iter().any(|extension| name.ends_with(extension))
+}
+
+/// Deletes cached files which are empty, not valid zip archives or do not match their known checksum,
+/// e.g. left behind by a crash while writing them.
+///
+/// Returns the number of files removed.
+pub async fn verify_and_repair(directory: &Path, checksums: &HashMap<PathBuf, ExpectedChecksum>) -> Result<usize> {
+    if !directory.exists() {
+        return Ok(0);
+    }
+
+    let mut files = Vec::new();
+    collect_files(directory, &mut files).await?;
+
+    let mut removed = 0;
+    for file in files {
+        let name = file.path.to_string_lossy();
+        if IN_PROGRESS_EXTENSIONS.iter().any(|extension| name.ends_with(extension)) || is_sidecar(&file.path) {
+            continue;
+        }
+
+        if let Err(reason) = verify_file(&file, checksums.get(&file.path)).await {
+            warn!("Removing corrupt file {} from cache: {}", file.path.display(), reason);
+            fs::remove_file(&file.path).await?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+async fn verify_file(file: &CachedFile, checksum: Option<&ExpectedChecksum>) -> Result<(), String> {
+    if file.size == 0 {
+        return Err("file is empty".to_string());
+    }
+
+    let archive = fs::File::open(&file.path).await.map_err(|e| e.to_string())?;
+    if ZipFileReader::new(archive).await.is_err() {
+        return Err("file is not a valid zip archive".to_string());
+    }
+
+    let Some(checksum) = checksum else {
+        return Ok(());
+    };
+    if let Some(expected) = &checksum.sha256 {
+        let actual = sha256sum_async(&file.path).await.map_err(|e| e.to_string())?;
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(format!("SHA-256 {} does not match {}", actual, expected));
+        }
+    }
+    if let Some(expected) = &checksum.sha1 {
+        let actual = sha1sum_async(&file.path).await.map_err(|e| e.to_string())?;
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(format!("SHA-1 {} does not match {}", actual, expected));
+        }
+    }
+
+    Ok(())
+}
+
+async fn collect_files(directory: &Path, files: &mut Vec<CachedFile>) -> Result<()> {
+    let mut pending = vec![directory.to_path_buf()];
+
+    while let Some(directory) = pending.pop() {
+        let mut entries = fs::read_dir(&directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+                continue;
+            }
+
+            // Access times are not tracked by every file system
+            let last_used = metadata.accessed()
+                .or_else(|_| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push(CachedFile {
+                path: entry.path(),
+                size: metadata.len(),
+                last_used,
+            });
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,227 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::app::api::{LoaderMod, ModSource, ModrinthApi, ModrinthVersion};
+use crate::minecraft::prelauncher::mod_file_name;
+use crate::utils::get_maven_artifact_path;
+
+///
+/// Required dependencies of the mods which are missing from the launch, together with the mods requiring them
+///
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    /// Pairs of the requiring mod and the name of its dependency
+    pub edges: Vec<(String, String)>,
+    /// Dependencies which have been added to the launch
+    pub mods: Vec<LoaderMod>,
+}
+
+impl fmt::Display for DependencyGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.edges.is_empty() {
+            return write!(f, "no dependencies");
+        }
+
+        let edges = self.edges.iter()
+            .map(|(dependent, dependency)| format!("{} -> {}", dependent, dependency))
+            .collect::<Vec<_>>();
+        write!(f, "{} added, {}", self.mods.len(), edges.join(", "))
+    }
+}
+
+/// Mods which are part of the launch, identified by their Modrinth project, mod id or file name
+#[derive(Default)]
+struct KnownMods {
+    projects: HashSet<String>,
+    mod_ids: HashSet<String>,
+    file_names: HashSet<String>,
+}
+
+impl KnownMods {
+
+    /// Mod ids and file names are compared ignoring their case
+    fn insert(&mut self, current_mod: &LoaderMod) {
+        self.mod_ids.insert(current_mod.name.to_lowercase());
+        self.file_names.insert(mod_file_name(current_mod).to_lowercase());
+
+        match &current_mod.source {
+            ModSource::Modrinth { project_id, .. } => {
+                self.projects.insert(project_id.clone());
+            }
+            ModSource::Repository { artifact, .. } => {
+                if let Some(artifact_id) = artifact.split(':').nth(1) {
+                    self.mod_ids.insert(artifact_id.to_lowercase());
+                }
+                if let Some(file_name) = get_maven_artifact_path(artifact).ok().as_deref().and_then(|path| path.rsplit('/').next()) {
+                    self.file_names.insert(file_name.to_lowercase());
+                }
+            }
+            ModSource::SkipAd { artifact_name, .. } => {
+                self.file_names.insert(artifact_name.to_lowercase());
+            }
+            ModSource::LocalFile { path } => {
+                if let Some(file_name) = path.rsplit(['/', '\\']).next() {
+                    self.file_names.insert(file_name.to_lowercase());
+                }
+            }
+            ModSource::Local { .. } | ModSource::CurseForge { .. } => {}
+        }
+    }
+
+    fn contains_mod_id(&self, mod_id: &str) -> bool {
+        self.mod_ids.contains(&mod_id.to_lowercase())
+    }
+
+    fn contains_file_name(&self, file_name: &str) -> bool {
+        self.file_names.contains(&file_name.to_lowercase())
+    }
+
+}
+
+/// Resolves the required dependencies of Modrinth mods, including the dependencies of dependencies.
+/// Mods which are already part of the launch from any source are not added again, they are matched
+/// by their Modrinth project, their mod id or their file name.
+pub async fn resolve_dependencies(mods: &[&LoaderMod], mc_version: &str, loader: &str) -> Result<DependencyGraph> {
+    let mut graph = DependencyGraph::default();
+    let mut known = KnownMods::default();
+    let mut pending = VecDeque::new();
+
+    for current_mod in mods {
+        known.insert(current_mod);
+        if let ModSource::Modrinth { version_id, .. } = &current_mod.source {
+            pending.push_back((current_mod.name.clone(), version_id.clone()));
+        }
+    }
+
+    while let Some((dependent, version_id)) = pending.pop_front() {
+        let version = ModrinthApi::version(&version_id).await
+            .with_context(|| format!("Failed to request dependencies of {}", dependent))?;
+
+        for dependency in version.dependencies.iter().filter(|x| x.is_required()) {
+            let (project_id, pinned_version) = match (&dependency.version_id, &dependency.project_id) {
+                (Some(version_id), _) => {
+                    let pinned_version = ModrinthApi::version(version_id).await?;
+                    (pinned_version.project_id.clone(), Some(pinned_version))
+                }
+                (None, Some(project_id)) => (project_id.clone(), None),
+                (None, None) => continue,
+            };
+
+            graph.edges.push((dependent.clone(), project_id.clone()));
+            if known.projects.contains(&project_id) {
+                continue;
+            }
+
+            let project = ModrinthApi::project(&project_id).await
+                .with_context(|| format!("Failed to request dependency {} of {}", project_id, dependent))?;
+            if known.contains_mod_id(&project.slug) {
+                debug!("{} requires {}, which is already part of the launch", dependent, project.slug);
+                known.projects.insert(project_id);
+                continue;
+            }
+
+            let dependency_version = match pinned_version {
+                Some(pinned_version) => pinned_version,
+                None => newest_compatible_version(&project_id, mc_version, loader).await?,
+            };
+            let file = dependency_version.primary_file();
+            if file.is_some_and(|file| known.contains_file_name(&file.filename)) {
+                debug!("{} requires {}, which is already part of the launch", dependent, project.slug);
+                known.projects.insert(project_id);
+                continue;
+            }
+
+            debug!("{} requires {} {}", dependent, project_id, dependency_version.version_number);
+            // The slug is the readable name of the project, which Modrinth mods usually use as their mod id
+            let dependency_mod = LoaderMod {
+                required: true,
+                enabled: true,
+                name: project.slug.clone(),
+                source: ModSource::Modrinth {
+                    project_id: project_id.clone(),
+                    version_id: dependency_version.id.clone(),
+                },
+                sha1: file.map(|file| file.hashes.sha1.clone()),
+                sha256: None,
+                size: file.map(|file| file.size),
+            };
+            known.insert(&dependency_mod);
+            graph.mods.push(dependency_mod);
+            pending.push_back((project.slug, dependency_version.id));
+        }
+    }
+
+    Ok(graph)
+}
+
+async fn newest_compatible_version(project_id: &str, mc_version: &str, loader: &str) -> Result<ModrinthVersion> {
+    ModrinthApi::project_versions(project_id, mc_version, loader).await?
+        .into_iter()
+        .next()
+        .with_context(|| format!("No version of {} is compatible with Minecraft {} and {}", project_id, mc_version, loader))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loader_mod(name: &str, source: ModSource) -> LoaderMod {
+        LoaderMod {
+            required: true,
+            enabled: true,
+            name: name.to_string(),
+            source,
+            sha1: None,
+            sha256: None,
+            size: None,
+        }
+    }
+
+    #[test]
+    fn repository_mod_is_known_by_mod_id_and_file_name() {
+        let mut known = KnownMods::default();
+        known.insert(&loader_mod("Fabric API", ModSource::Repository {
+            repository: "fabric".to_string(),
+            artifact: "net.fabricmc.fabric-api:fabric-api:0.92.0+1.20.1".to_string(),
+        }));
+
+        assert!(known.contains_mod_id("fabric-api"));
+        assert!(known.contains_file_name("fabric-api-0.92.0+1.20.1.jar"));
+        assert!(!known.contains_mod_id("sodium"));
+        assert!(known.projects.is_empty());
+    }
+
+    #[test]
+    fn mods_of_other_sources_are_known_by_file_name() {
+        let mut known = KnownMods::default();
+        known.insert(&loader_mod("modmenu", ModSource::Local { file_name: "ModMenu-7.2.2.jar".to_string() }));
+        known.insert(&loader_mod("sodium", ModSource::LocalFile { path: "/home/user/mods/sodium-fabric-0.5.3.jar".to_string() }));
+
+        assert!(known.contains_mod_id("ModMenu"));
+        assert!(known.contains_file_name("modmenu-7.2.2.jar"));
+        assert!(known.contains_file_name("sodium-fabric-0.5.3.jar"));
+    }
+}
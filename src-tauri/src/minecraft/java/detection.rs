@@ -0,0 +1,141 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+ 
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::Result;
+use path_absolutize::Absolutize;
+use tokio::fs;
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::error::LauncherError;
+use crate::utils::{OperatingSystem, OS};
+
+/// Java binary used to query the version of an installation
+const JAVA_BINARY: &str = if cfg!(windows) { "java.exe" } else { "java" };
+
+/// Find an installed JRE on the system which matches the required major version.
+/// Looks at `JAVA_HOME`, the `PATH` and the usual install locations of the operating system.
+pub async fn find_system_java(required: u32) -> Result<PathBuf> {
+    for candidate in java_candidates().await {
+        let Some(major_version) = java_major_version(&candidate).await else {
+            continue;
+        };
+
+        debug!("Found Java {} at {}", major_version, candidate.display());
+        if major_version == required {
+            return Ok(launch_binary(&candidate).absolutize()?.to_path_buf());
+        }
+    }
+
+    Err(LauncherError::NoCompatibleJava { required }.into())
+}
+
+/// On Windows the game is launched with `javaw.exe` to not open a console window
+fn launch_binary(java_binary: &Path) -> PathBuf {
+    if OS == OperatingSystem::WINDOWS {
+        let javaw = java_binary.with_file_name("javaw.exe");
+        if javaw.exists() {
+            return javaw;
+        }
+    }
+    java_binary.to_path_buf()
+}
+
+async fn java_candidates() -> Vec<PathBuf> {
+    let mut java_homes = Vec::new();
+
+    if let Some(java_home) = env::var_os("JAVA_HOME") {
+        java_homes.push(PathBuf::from(java_home));
+    }
+
+    let mut candidates = java_homes.iter()
+        .map(|home| home.join("bin").join(JAVA_BINARY))
+        .collect::<Vec<_>>();
+
+    if let Some(path) = env::var_os("PATH") {
+        candidates.extend(env::split_paths(&path).map(|dir| dir.join(JAVA_BINARY)));
+    }
+
+    let install_locations: &[&str] = match OS {
+        OperatingSystem::WINDOWS => &[
+            "C:\\Program Files\\Java",
+            "C:\\Program Files\\Eclipse Adoptium",
+            "C:\\Program Files\\Zulu",
+            "C:\\Program Files\\Microsoft",
+            "C:\\Program Files (x86)\\Java",
+        ],
+        OperatingSystem::OSX => &["/Library/Java/JavaVirtualMachines"],
+        _ => &["/usr/lib/jvm", "/usr/java", "/opt/java"],
+    };
+
+    for location in install_locations {
+        let Ok(mut entries) = fs::read_dir(location).await else {
+            continue;
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let home = match OS {
+                OperatingSystem::OSX => entry.path().join("Contents").join("Home"),
+                _ => entry.path(),
+            };
+            candidates.push(home.join("bin").join(JAVA_BINARY));
+        }
+    }
+
+    // The same installation is often found multiple times, e.g. through JAVA_HOME and PATH or a symlink,
+    // only its first occurrence is kept to preserve the order of preference
+    let mut seen = HashSet::new();
+    candidates.retain(|candidate| {
+        candidate.is_file() && seen.insert(std::fs::canonicalize(candidate).unwrap_or_else(|_| candidate.clone()))
+    });
+    candidates
+}
+
+/// Runs `java -version` and parses the major version of its output
+async fn java_major_version(java_binary: &Path) -> Option<u32> {
+    let output = Command::new(java_binary)
+        .arg("-version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .ok()?;
+
+    // The version is printed to stderr, some distributions use stdout
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_major_version(&stderr).or_else(|| parse_major_version(&stdout))
+}
+
+/// Parses the major version from outputs like `openjdk version "17.0.2"` or `java version "1.8.0_292"`
+fn parse_major_version(output: &str) -> Option<u32> {
+    let version = output.split('"').nth(1)?;
+    let mut parts = version.split(['.', '_', '-', '+']);
+
+    match parts.next()?.parse::<u32>().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
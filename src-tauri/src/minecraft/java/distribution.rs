@@ -7,7 +7,10 @@ pub enum JavaDistribution {
     #[serde(rename = "graalvm")]
     GraalVM,
     #[serde(rename = "openjdk")]
-    OpenJDK
+    OpenJDK,
+    /// Runtimes of the official launcher, listed by Mojang's java-runtime manifest
+    #[serde(rename = "mojang")]
+    Mojang
 }
 
 impl Default for JavaDistribution {
@@ -47,6 +50,8 @@ impl JavaDistribution {
                     jre_version, os_name, os_arch, archive_type
                 )
             }
+            // Consists of single files instead of an archive, see [super::mojang_runtime]
+            JavaDistribution::Mojang => super::mojang_runtime::JAVA_RUNTIME_MANIFEST_URL.to_string(),
         }
     }
 
@@ -54,7 +59,8 @@ impl JavaDistribution {
         match self {
             JavaDistribution::Temurin => "temurin",
             JavaDistribution::GraalVM => "graalvm",
-            JavaDistribution::OpenJDK => "openjdk"
+            JavaDistribution::OpenJDK => "openjdk",
+            JavaDistribution::Mojang => "mojang"
         }
     }
 
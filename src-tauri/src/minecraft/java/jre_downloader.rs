@@ -17,16 +17,16 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::io::Cursor;
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Result};
 use path_absolutize::Absolutize;
 use tokio::fs;
 
-use crate::utils::{download_file, tar_gz_extract, zip_extract, OperatingSystem, ARCHITECTURE, OS};
+use crate::utils::{download_file_resumable, tar_gz_extract, zip_extract, DownloadOptions, OperatingSystem, ARCHITECTURE, OS};
 
-use super::JavaDistribution;
+use crate::minecraft::progress::ProgressReceiver;
+use super::{mojang_runtime, JavaDistribution};
 
 /// Find java binary in JRE folder
 pub async fn find_java_binary(
@@ -34,6 +34,10 @@ pub async fn find_java_binary(
     jre_distribution: &JavaDistribution,
     jre_version: &u32,
 ) -> Result<PathBuf> {
+    if let JavaDistribution::Mojang = jre_distribution {
+        return mojang_runtime::find_runtime_binary(runtimes_folder, jre_version).await;
+    }
+
     let runtime_path =
         runtimes_folder.join(format!("{}_{}", jre_distribution.get_name(), jre_version));
 
@@ -54,38 +58,54 @@ pub async fn find_java_binary(
         };
 
         if java_binary.exists() {
-            // Check if the binary has execution permissions on linux and macOS
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
+            make_executable(&java_binary).await?;
+            return Ok(java_binary.absolutize()?.to_path_buf());
+        }
+    }
+
+    return Err(anyhow::anyhow!("Failed to find JRE"));
+}
 
-                let metadata = fs::metadata(&java_binary).await?;
+/// Check if the binary has execution permissions on linux and macOS, a no-op on windows
+pub(crate) async fn make_executable(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
 
-                if !metadata.permissions().mode() & 0o111 != 0 {
-                    // try to change permissions
-                    let mut permissions = metadata.permissions();
-                    permissions.set_mode(0o111);
-                    fs::set_permissions(&java_binary, permissions).await?;
-                }
-            }
+        let metadata = fs::metadata(path).await?;
+        let mode = metadata.permissions().mode();
 
-            return Ok(java_binary.absolutize()?.to_path_buf());
+        if mode & 0o111 != 0o111 {
+            // chmod +x, while keeping the existing permissions
+            let mut permissions = metadata.permissions();
+            permissions.set_mode(mode | 0o111);
+            fs::set_permissions(path, permissions).await?;
         }
     }
+    #[cfg(not(unix))]
+    let _ = path;
 
-    return Err(anyhow::anyhow!("Failed to find JRE"));
+    Ok(())
 }
 
-/// Download specific JRE to runtimes
+/// Download specific JRE to runtimes.
+/// The component of the version profile selects the runtime of Mojang, other distributions ignore it.
 pub async fn jre_download<F>(
     runtimes_folder: &Path,
     jre_distribution: &JavaDistribution,
     jre_version: &u32,
+    component: Option<&str>,
+    download_options: &DownloadOptions,
+    progress: &impl ProgressReceiver,
     on_progress: F,
 ) -> Result<PathBuf>
 where
     F: Fn(u64, u64),
 {
+    if let JavaDistribution::Mojang = jre_distribution {
+        return mojang_runtime::runtime_download(runtimes_folder, component, jre_version, download_options, progress, on_progress).await;
+    }
+
     let runtime_path =
         runtimes_folder.join(format!("{}_{}", jre_distribution.get_name(), jre_version));
 
@@ -105,16 +125,24 @@ where
     // Download from JRE source and extract runtime files
     fs::create_dir_all(&runtime_path).await?;
 
-    let retrieved_bytes = download_file(&url, on_progress).await?;
-    let cursor = Cursor::new(&retrieved_bytes[..]);
+    // The archive is kept next to the runtime folder, so an interrupted download can be resumed
+    let archive_path = runtimes_folder.join(format!(
+        "{}_{}.{}",
+        jre_distribution.get_name(),
+        jre_version,
+        archive_type
+    ));
+    download_file_resumable(&url, &archive_path, download_options, progress, on_progress).await?;
+    let archive = fs::File::open(&archive_path).await?;
 
     match OS {
-        OperatingSystem::WINDOWS => zip_extract(cursor, runtime_path.as_path()).await?,
+        OperatingSystem::WINDOWS => zip_extract(archive, runtime_path.as_path()).await?,
         OperatingSystem::LINUX | OperatingSystem::OSX => {
-            tar_gz_extract(cursor, runtime_path.as_path()).await?
+            tar_gz_extract(archive, runtime_path.as_path()).await?
         }
         _ => bail!("Unsupported OS"),
     }
+    fs::remove_file(&archive_path).await?;
 
     // Find JRE afterwards
     find_java_binary(runtimes_folder, jre_distribution, jre_version).await
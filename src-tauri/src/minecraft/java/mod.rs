@@ -18,11 +18,14 @@
  */
  
 pub(crate) mod jre_downloader;
+pub(crate) mod mojang_runtime;
 mod runtime;
 mod distribution;
+mod detection;
 
 pub use {
     jre_downloader::*,
     runtime::*,
     distribution::*,
+    detection::*,
 };
\ No newline at end of file
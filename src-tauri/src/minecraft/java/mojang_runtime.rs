@@ -0,0 +1,284 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use futures::{stream, StreamExt, TryStreamExt};
+use path_absolutize::Absolutize;
+use serde::Deserialize;
+use tokio::fs;
+use tracing::debug;
+
+use crate::error::LauncherError;
+use crate::minecraft::progress::ProgressReceiver;
+use crate::utils::{download_file_hashed, get_cached, sha1sum_async, Architecture, DownloadOptions, OperatingSystem, ARCHITECTURE, OS};
+use crate::HTTP_CLIENT;
+
+use super::jre_downloader::make_executable;
+
+/// Lists the Java runtimes Mojang provides for every platform
+pub(crate) const JAVA_RUNTIME_MANIFEST_URL: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+/// Runtimes are added rarely, the manifest is only requested again after this time
+const JAVA_RUNTIME_MANIFEST_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A runtime consists of hundreds of small files, which are downloaded in parallel
+const CONCURRENT_RUNTIME_DOWNLOADS: usize = 16;
+
+/// Runtimes of every component for a single platform
+type PlatformRuntimes = HashMap<String, Vec<RuntimeVersion>>;
+
+#[derive(Deserialize)]
+struct RuntimeVersion {
+    manifest: RuntimeDownload,
+    version: RuntimeVersionName,
+}
+
+#[derive(Deserialize)]
+struct RuntimeVersionName {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct RuntimeDownload {
+    sha1: String,
+    size: u64,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct RuntimeManifest {
+    files: HashMap<String, RuntimeFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum RuntimeFile {
+    Directory,
+    File {
+        #[serde(default)]
+        executable: bool,
+        downloads: RuntimeFileDownloads,
+    },
+    Link {
+        target: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct RuntimeFileDownloads {
+    raw: RuntimeDownload,
+}
+
+/// Name of the platform in the runtime manifest
+fn platform_name() -> Result<&'static str> {
+    Ok(match (OS, ARCHITECTURE) {
+        (OperatingSystem::WINDOWS, Architecture::X64) => "windows-x64",
+        (OperatingSystem::WINDOWS, Architecture::X86) => "windows-x86",
+        (OperatingSystem::WINDOWS, Architecture::AARCH64) => "windows-arm64",
+        (OperatingSystem::LINUX, Architecture::X64) => "linux",
+        (OperatingSystem::LINUX, Architecture::X86) => "linux-i386",
+        (OperatingSystem::OSX, Architecture::X64) => "mac-os",
+        (OperatingSystem::OSX, Architecture::AARCH64) => "mac-os-arm64",
+        _ => bail!("Mojang does not provide Java runtimes for {:?} {}", OS, ARCHITECTURE),
+    })
+}
+
+/// Major version of a runtime version name, e.g. `17.0.8` or `1.8.0_51`
+fn major_version(name: &str) -> Option<u32> {
+    let name = name.strip_prefix("1.").unwrap_or(name);
+    name.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+/// Written once every file and link of a runtime is in place, an interrupted download is never used
+const RUNTIME_COMPLETE_MARKER: &str = ".complete";
+
+/// Folder of the runtime of the major version, `runtimes/{version}`
+pub fn runtime_path(runtimes_folder: &Path, jre_version: &u32) -> PathBuf {
+    runtimes_folder.join(jre_version.to_string())
+}
+
+/// Java binary of an extracted runtime, macOS runtimes are shipped as a bundle
+fn java_binary(runtime_path: &Path) -> PathBuf {
+    match OS {
+        OperatingSystem::WINDOWS => runtime_path.join("bin").join("javaw.exe"),
+        OperatingSystem::OSX => runtime_path.join("jre.bundle").join("Contents").join("Home").join("bin").join("java"),
+        _ => runtime_path.join("bin").join("java"),
+    }
+}
+
+/// Find java binary of a runtime downloaded from Mojang, the download has to be complete
+pub async fn find_runtime_binary(runtimes_folder: &Path, jre_version: &u32) -> Result<PathBuf> {
+    let runtime_path = runtime_path(runtimes_folder, jre_version);
+    let java_binary = java_binary(&runtime_path);
+    if !runtime_path.join(RUNTIME_COMPLETE_MARKER).exists() || !java_binary.exists() {
+        bail!("Failed to find JRE");
+    }
+
+    make_executable(&java_binary).await?;
+    Ok(java_binary.absolutize()?.to_path_buf())
+}
+
+/// Picks the runtime of the component, or the first one of the required major version
+fn select_runtime<'a>(runtimes: &'a PlatformRuntimes, component: Option<&str>, jre_version: &u32) -> Option<&'a RuntimeVersion> {
+    if let Some(runtime) = component.and_then(|component| runtimes.get(component)).and_then(|versions| versions.first()) {
+        if major_version(&runtime.version.name) == Some(*jre_version) {
+            return Some(runtime);
+        }
+    }
+
+    let mut components = runtimes.keys().collect::<Vec<_>>();
+    components.sort();
+    components.into_iter()
+        .filter_map(|component| runtimes[component].first())
+        .find(|runtime| major_version(&runtime.version.name) == Some(*jre_version))
+}
+
+/// Download the runtime of the required major version from Mojang's java-runtime manifest into `runtimes/{version}`.
+/// Reports the bytes downloaded so far out of the size of the whole runtime.
+pub async fn runtime_download<F>(
+    runtimes_folder: &Path,
+    component: Option<&str>,
+    jre_version: &u32,
+    download_options: &DownloadOptions,
+    progress: &impl ProgressReceiver,
+    on_progress: F,
+) -> Result<PathBuf>
+where
+    F: Fn(u64, u64),
+{
+    let content = get_cached(JAVA_RUNTIME_MANIFEST_URL, "java_runtimes.json", JAVA_RUNTIME_MANIFEST_TTL).await
+        .context("Failed to fetch the Java runtimes of Mojang")?;
+    let mut platforms = serde_json::from_slice::<HashMap<String, PlatformRuntimes>>(&content)
+        .context("Failed to parse the Java runtimes of Mojang")?;
+    let runtimes = platforms.remove(platform_name()?).unwrap_or_default();
+    let runtime = select_runtime(&runtimes, component, jre_version)
+        .ok_or(LauncherError::NoCompatibleJava { required: *jre_version })?;
+
+    progress.log(&format!("Downloading Java {} from Mojang", runtime.version.name));
+    let manifest = HTTP_CLIENT.get(&runtime.manifest.url)
+        .send().await?
+        .error_for_status()?
+        .json::<RuntimeManifest>()
+        .await
+        .context("Failed to fetch the runtime manifest")?;
+
+    let runtime_path = runtime_path(runtimes_folder, jre_version);
+    fs::create_dir_all(&runtime_path).await?;
+    let marker = runtime_path.join(RUNTIME_COMPLETE_MARKER);
+    if fs::metadata(&marker).await.is_ok() {
+        fs::remove_file(&marker).await?;
+    }
+
+    // Directories first, so every file has its parent
+    let mut files = manifest.files.into_iter().collect::<Vec<_>>();
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (name, file) in &files {
+        if let RuntimeFile::Directory = file {
+            fs::create_dir_all(runtime_path.join(name)).await?;
+        }
+    }
+
+    let total = files.iter()
+        .map(|(_, file)| match file {
+            RuntimeFile::File { downloads, .. } => downloads.raw.size,
+            _ => 0,
+        })
+        .sum::<u64>();
+    let done = AtomicU64::new(0);
+    on_progress(0, total);
+
+    stream::iter(files.iter().filter_map(|(name, file)| match file {
+        RuntimeFile::File { executable, downloads } => Some((name, *executable, &downloads.raw)),
+        _ => None,
+    }))
+        .map(|(name, executable, download)| {
+            let path = runtime_path.join(name);
+            let (done, on_progress) = (&done, &on_progress);
+            async move {
+                download_runtime_file(&path, executable, download, download_options, progress).await
+                    .with_context(|| format!("Failed to download {} of the Java runtime", name))?;
+                on_progress(done.fetch_add(download.size, Ordering::SeqCst) + download.size, total);
+                anyhow::Ok(())
+            }
+        })
+        .buffer_unordered(CONCURRENT_RUNTIME_DOWNLOADS)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    for (name, file) in &files {
+        if let RuntimeFile::Link { target } = file {
+            create_link(&runtime_path.join(name), target).await?;
+        }
+    }
+    fs::write(&marker, &runtime.version.name).await
+        .context("Failed to mark the Java runtime as complete")?;
+
+    find_runtime_binary(runtimes_folder, jre_version).await
+}
+
+/// Files of a previous download are kept if their checksum still matches
+async fn download_runtime_file(
+    path: &Path,
+    executable: bool,
+    download: &RuntimeDownload,
+    download_options: &DownloadOptions,
+    progress: &impl ProgressReceiver,
+) -> Result<()> {
+    if path.exists() && sha1sum_async(path).await? == download.sha1 {
+        debug!("{} is up to date", path.display());
+    } else {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let actual = download_file_hashed(&download.url, path, download_options, progress, |_, _| {}).await?;
+        if actual != download.sha1 {
+            fs::remove_file(path).await?;
+            return Err(LauncherError::ChecksumMismatch {
+                expected: download.sha1.clone(),
+                actual,
+                name: path.display().to_string(),
+            }.into());
+        }
+    }
+
+    if executable {
+        make_executable(path).await?;
+    }
+    Ok(())
+}
+
+/// Links only occur in the runtimes of Linux and macOS
+async fn create_link(path: &Path, target: &str) -> Result<()> {
+    if fs::symlink_metadata(path).await.is_ok() {
+        fs::remove_file(path).await?;
+    }
+
+    #[cfg(unix)]
+    fs::symlink(target, path).await?;
+    #[cfg(not(unix))]
+    debug!("Skipping link {} to {}", path.display(), target);
+
+    Ok(())
+}
@@ -17,13 +17,18 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
  
+use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use tokio::sync::oneshot::Receiver;
+use tokio::fs::{self, File};
 use tokio::process::{Child, Command};
 use anyhow::{Result, bail};
-use tokio::io::AsyncReadExt;
-use tracing::debug;
+use path_absolutize::Absolutize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::error::LauncherError;
 pub struct JavaRuntime(PathBuf);
 
 impl JavaRuntime {
@@ -32,43 +37,62 @@ impl JavaRuntime {
         JavaRuntime(path)
     }
 
-    pub async fn execute(&self, arguments: Vec<String>, game_dir: &Path) -> Result<Child> {
+    pub async fn execute(&self, arguments: Vec<String>, working_dir: &Path, env: &HashMap<String, String>) -> Result<Child> {
         if !self.0.exists() {
             bail!("Java runtime not found at: {}", self.0.display());
         }
 
         debug!("Executing Java runtime: {}", self.0.display());
         
-        let mut command = Command::new(&self.0);
-        command.current_dir(game_dir);
+        // A relative path would be resolved against the working directory of the game on some platforms
+        let mut command = std::process::Command::new(&*self.0.absolutize()?);
+        command.current_dir(working_dir);
         command.args(arguments);
+        command.envs(env);
 
         command
             .stderr(Stdio::piped())
             .stdout(Stdio::piped());
 
+        // The processes started by the game, e.g. the game relaunched by Forge, can be killed along with it
+        set_process_group(&mut command);
+
+        let mut command = Command::from(command);
         let child = command.spawn()?;
         Ok(child)
     }
 
-    pub async fn handle_io<D: Send + Sync>(&self, running_task: &mut Child, on_stdout: fn(&D, &[u8]) -> Result<()>, on_stderr: fn(&D, &[u8]) -> Result<()>, terminator: Receiver<()>, data: &D) -> Result<()> {
+    pub async fn handle_io<D: Send + Sync>(&self, running_task: &mut Child, on_stdout: fn(&D, &[u8]) -> Result<()>, on_stderr: fn(&D, &[u8]) -> Result<()>, terminator: CancellationToken, game_log: &mut GameLog, data: &D) -> Result<()> {
         let mut stdout = running_task.stdout.take().unwrap();
         let mut stderr = running_task.stderr.take().unwrap();
     
         let mut stdout_buf = vec![0; 1024];
         let mut stderr_buf = vec![0; 1024];
-    
-        tokio::pin!(terminator);
+
+        // A closed stream would be read again and again otherwise
+        let mut stdout_open = true;
+        let mut stderr_open = true;
     
         loop {
             tokio::select! {
-                read_len = stdout.read(&mut stdout_buf) => {
-                    let _ = (on_stdout)(&data, &stdout_buf[..read_len?]);
+                read_len = stdout.read(&mut stdout_buf), if stdout_open => {
+                    let read_len = read_len?;
+                    stdout_open = read_len > 0;
+                    let _ = (on_stdout)(&data, &stdout_buf[..read_len]);
+                    game_log.record(OutputStream::Stdout, &stdout_buf[..read_len]).await;
                 },
-                read_len = stderr.read(&mut stderr_buf) => {
-                    let _ = (on_stderr)(&data, &stderr_buf[..read_len?]);
+                read_len = stderr.read(&mut stderr_buf), if stderr_open => {
+                    let read_len = read_len?;
+                    stderr_open = read_len > 0;
+                    let _ = (on_stderr)(&data, &stderr_buf[..read_len]);
+                    game_log.record(OutputStream::Stderr, &stderr_buf[..read_len]).await;
                 },
-                _ = &mut terminator => {
+                _ = terminator.cancelled() => {
+                    if let Some(pid) = running_task.id() {
+                        if let Err(e) = kill_process_tree(pid).await {
+                            warn!("Failed to kill process tree of {}: {:?}", pid, e);
+                        }
+                    }
                     running_task.kill().await?;
                     break;
                 },
@@ -77,7 +101,8 @@ impl JavaRuntime {
 
                     debug!("Process exited with code: {}", code);
                     if code != 0 && code != -1073740791 { // -1073740791 = happens when the process is killed forcefully, we don't want to bail in this case
-                        bail!("Process exited with non-zero exit code: {}.", code);
+                        game_log.sync().await;
+                        return Err(LauncherError::GameCrashed { exit_code: code, report_excerpt: game_log.tail() }.into());
                     }
                     break;
                 },
@@ -86,4 +111,180 @@ impl JavaRuntime {
         Ok(())
     }
 
-}
\ No newline at end of file
+}
+
+/// Kills the process and all processes it has started, e.g. the game relaunched by Forge
+#[cfg(windows)]
+fn kill_process_tree_command(pid: u32) -> std::process::Command {
+    let mut command = std::process::Command::new("taskkill");
+    command.args(["/T", "/F", "/PID", &pid.to_string()]);
+    command
+}
+
+/// Kills the process group of the process, which has been started as its leader by [JavaRuntime::execute]
+/// or [set_process_group]
+#[cfg(not(windows))]
+fn kill_process_tree_command(pid: u32) -> std::process::Command {
+    let mut command = std::process::Command::new("kill");
+    command.args(["-KILL", "--", &format!("-{}", pid)]);
+    command
+}
+
+pub async fn kill_process_tree(pid: u32) -> Result<()> {
+    let status = Command::from(kill_process_tree_command(pid))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await?;
+
+    if !status.success() {
+        bail!("Killing process tree of {} exited with {}", pid, status);
+    }
+    Ok(())
+}
+
+/// Starts the command as leader of its own process group, so the processes it starts can be killed along with it
+pub fn set_process_group(command: &mut std::process::Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+///
+/// Kills the process tree of a child once dropped, unless the child has exited before.
+/// Futures running a child are dropped when the prelaunch times out, which only kills the direct child otherwise.
+///
+pub struct ProcessTreeGuard {
+    pid: Option<u32>,
+}
+
+impl ProcessTreeGuard {
+
+    pub fn new(child: &Child) -> Self {
+        Self { pid: child.id() }
+    }
+
+    /// The child has exited, processes it left running in the background are not killed
+    pub fn disarm(&mut self) {
+        self.pid = None;
+    }
+
+}
+
+impl Drop for ProcessTreeGuard {
+    fn drop(&mut self) {
+        let Some(pid) = self.pid.take() else {
+            return;
+        };
+
+        debug!("Killing process tree of {}", pid);
+        let status = kill_process_tree_command(pid)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if !status.is_ok_and(|status| status.success()) {
+            warn!("Failed to kill process tree of {}", pid);
+        }
+    }
+}
+
+/// Number of lines of the game output which are kept to be attached to errors
+const TAIL_LINES: usize = 50;
+
+/// Number of logs of previous launches kept next to the current one
+const KEPT_LOGS: usize = 5;
+
+#[derive(Clone, Copy)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+///
+/// Output of the game, written to a log file while keeping the last lines in memory
+///
+#[derive(Default)]
+pub struct GameLog {
+    file: Option<File>,
+    pending: [Vec<u8>; 2],
+    tail: VecDeque<String>,
+}
+
+impl GameLog {
+
+    /// Opens `latest.log` in the directory, the logs of previous launches are rotated to `latest.1.log` and so on
+    pub async fn open(directory: &Path) -> Result<Self> {
+        fs::create_dir_all(directory).await?;
+
+        for index in (0..KEPT_LOGS).rev() {
+            let from = Self::log_path(directory, index);
+            if from.exists() {
+                fs::rename(&from, Self::log_path(directory, index + 1)).await?;
+            }
+        }
+
+        Ok(Self {
+            file: Some(File::create(Self::log_path(directory, 0)).await?),
+            ..Default::default()
+        })
+    }
+
+    fn log_path(directory: &Path, index: usize) -> PathBuf {
+        match index {
+            0 => directory.join("latest.log"),
+            _ => directory.join(format!("latest.{}.log", index)),
+        }
+    }
+
+    pub async fn record(&mut self, stream: OutputStream, data: &[u8]) {
+        if let Some(file) = &mut self.file {
+            if let Err(e) = file.write_all(data).await {
+                warn!("Failed to write game log: {:?}", e);
+                self.file = None;
+            }
+        }
+
+        // Streams are split into lines separately, they might be interleaved in the middle of a line
+        let pending = &mut self.pending[stream as usize];
+        pending.extend_from_slice(data);
+        while let Some(index) = pending.iter().position(|x| *x == b'\n') {
+            let line = pending.drain(..=index).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+
+            if self.tail.len() == TAIL_LINES {
+                self.tail.pop_front();
+            }
+            self.tail.push_back(line);
+        }
+    }
+
+    /// Writes the log to disk, e.g. before it is read or the launcher exits without dropping it
+    pub async fn sync(&mut self) {
+        if let Some(file) = &mut self.file {
+            let synced = async {
+                file.flush().await?;
+                file.sync_all().await
+            }.await;
+
+            if let Err(e) = synced {
+                warn!("Failed to write game log: {:?}", e);
+                self.file = None;
+            }
+        }
+    }
+
+    /// Last lines of the game output
+    pub fn tail(&self) -> String {
+        let mut lines = self.tail.iter().cloned().collect::<Vec<_>>();
+        lines.extend(self.pending.iter()
+            .filter(|x| !x.is_empty())
+            .map(|x| String::from_utf8_lossy(x).trim_end().to_string()));
+
+        lines.join("\n")
+    }
+
+}
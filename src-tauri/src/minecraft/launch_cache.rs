@@ -0,0 +1,71 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+ 
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::fs;
+
+use crate::error::LauncherError;
+
+///
+/// Manifests of previous launches, written to disk for every build that has been launched successfully.
+/// This allows launching the same build again without any network access.
+///
+pub struct LaunchCache {
+    directory: PathBuf,
+}
+
+pub const LAUNCH_MANIFEST: &str = "launch_manifest.json";
+pub const VERSION_PROFILE: &str = "version_profile.json";
+pub const INHERITED_VERSION_PROFILES: &str = "inherited_version_profiles.json";
+pub const RESOLVED_DEPENDENCIES: &str = "resolved_dependencies.json";
+
+impl LaunchCache {
+
+    pub fn new(data_directory: &Path, build_id: u32) -> Self {
+        Self {
+            directory: data_directory.join("launch_cache").join(build_id.to_string()),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.directory.join(name).exists()
+    }
+
+    pub async fn read<T: DeserializeOwned>(&self, name: &str) -> Result<T> {
+        let path = self.directory.join(name);
+        let content = fs::read(&path).await
+            .map_err(|_| LauncherError::NotAvailableOffline(name.to_string()))?;
+
+        serde_json::from_slice::<T>(&content)
+            .with_context(|| format!("Failed to parse cached {}", path.display()))
+    }
+
+    pub async fn write<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        fs::create_dir_all(&self.directory).await?;
+
+        let path = self.directory.join(name);
+        fs::write(&path, serde_json::to_vec(value)?).await
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+}
@@ -1,23 +1,34 @@
-use std::{path::{Path, PathBuf}, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+use std::{collections::HashSet, path::{Component, Path, PathBuf}, sync::{atomic::{AtomicU64, Ordering}, Arc}};
 
 use anyhow::Result;
 use futures::{stream, StreamExt};
-use tracing::error;
+use tokio::fs;
+use tracing::{error, warn};
 
 use crate::{
     error::LauncherError,
     join_and_mkdir,
-    minecraft::{progress::{ProgressReceiver, ProgressUpdate, ProgressUpdateSteps}, version::{AssetIndexLocation, VersionProfile}},
+    minecraft::{progress::{ProgressReceiver, ProgressUpdate, ProgressUpdateSteps}, version::{AssetIndex, VersionProfile}},
 };
 
-use super::{LauncherData, LaunchingParameter};
+use crate::app::app_data::LauncherConfig;
 
-pub async fn setup_assets<'a, D: Send + Sync>(
-    assets_folder: &'a Path,
-    version_profile: &'a VersionProfile,
-    launching_parameter: &'a LaunchingParameter,
-    launcher_data: &'a LauncherData<D>,
-) -> Result<&'a AssetIndexLocation> {
+use super::LauncherData;
+
+/// Assets ready to be used by the game
+pub struct PreparedAssets {
+    pub index_name: String,
+    /// Directory passed to versions with a legacy asset layout, which read the assets by their name
+    pub game_assets: PathBuf,
+}
+
+pub async fn setup_assets<D: Send + Sync>(
+    assets_folder: &Path,
+    game_dir: &Path,
+    version_profile: &VersionProfile,
+    config: &LauncherConfig,
+    launcher_data: &LauncherData<D>,
+) -> Result<PreparedAssets> {
     let indexes_folder: PathBuf = join_and_mkdir!(assets_folder, "indexes");
     let objects_folder: PathBuf = join_and_mkdir!(assets_folder, "objects");
 
@@ -27,12 +38,16 @@ pub async fn setup_assets<'a, D: Send + Sync>(
         .ok_or_else(|| {
             LauncherError::InvalidVersionProfile("Asset index unspecified".to_string())
         })?;
+    let download_options = config.download_options(&launcher_data.cancellation);
     let asset_index = asset_index_location
-        .load_asset_index(&indexes_folder)
+        .load_asset_index(&indexes_folder, &download_options)
         .await?;
+    // Several asset names can share the same object, each object only has to be downloaded once
+    let mut seen_hashes = HashSet::new();
     let asset_objects_to_download = asset_index
         .objects
         .values()
+        .filter(|x| seen_hashes.insert(x.hash.as_str()))
         .map(|x| x.to_owned())
         .collect::<Vec<_>>();
     let assets_downloaded = Arc::new(AtomicU64::new(0));
@@ -49,15 +64,16 @@ pub async fn setup_assets<'a, D: Send + Sync>(
         stream::iter(asset_objects_to_download.into_iter().map(|asset_object| {
             let download_count = assets_downloaded.clone();
             let folder_clone = objects_folder.clone();
+            let download_options = &download_options;
 
             async move {
                 let hash = asset_object.hash.clone();
                 match asset_object
-                    .download_destructing(folder_clone, launcher_data)
+                    .download_destructing(folder_clone, download_options, launcher_data)
                     .await
                 {
                     Ok(downloaded) => {
-                        let curr = download_count.fetch_add(1, Ordering::Relaxed);
+                        let curr = download_count.fetch_add(1, Ordering::Relaxed) + 1;
 
                         if downloaded {
                             // the progress bar is only being updated when a asset has been downloaded to improve speeds
@@ -74,7 +90,7 @@ pub async fn setup_assets<'a, D: Send + Sync>(
                 Ok(())
             }
         }))
-        .buffer_unordered(launching_parameter.concurrent_downloads as usize)
+        .buffer_unordered(config.concurrent_asset_downloads.max(1) as usize)
         .collect()
         .await;
 
@@ -84,5 +100,111 @@ pub async fn setup_assets<'a, D: Send + Sync>(
         asset_max,
     ));
 
-    Ok(asset_index_location)
+    let game_assets = game_assets_folder(&asset_index, &asset_index_location.id, assets_folder, game_dir);
+    if asset_index.is_virtual || asset_index.map_to_resources {
+        launcher_data.progress_update(ProgressUpdate::set_label("Preparing legacy assets..."));
+        materialize_legacy_assets(&asset_index, &objects_folder, &game_assets).await?;
+    }
+
+    Ok(PreparedAssets {
+        index_name: asset_index_location.id.clone(),
+        game_assets,
+    })
+}
+
+/// Directory the game reads the assets from, legacy layouts are read by the asset names instead of the object store
+fn game_assets_folder(asset_index: &AssetIndex, index_name: &str, assets_folder: &Path, game_dir: &Path) -> PathBuf {
+    if asset_index.map_to_resources {
+        game_dir.join("resources")
+    } else if asset_index.is_virtual {
+        assets_folder.join("virtual").join(index_name)
+    } else {
+        assets_folder.to_path_buf()
+    }
+}
+
+/// Copies the objects to their asset names, as old versions do not know about the object store
+async fn materialize_legacy_assets(asset_index: &AssetIndex, objects_folder: &Path, target_folder: &Path) -> Result<()> {
+    for (name, asset_object) in &asset_index.objects {
+        let relative_path = Path::new(name);
+        if asset_object.hash.len() < 2 || !relative_path.components().all(|x| matches!(x, Component::Normal(_))) {
+            warn!("Skipping invalid legacy asset {}", name);
+            continue;
+        }
+
+        let target = target_folder.join(relative_path);
+        if fs::metadata(&target).await.is_ok_and(|x| x.len() == asset_object.size as u64) {
+            continue;
+        }
+
+        let object = objects_folder.join(&asset_object.hash[0..2]).join(&asset_object.hash);
+        if !object.exists() {
+            // Failed downloads have been logged already
+            continue;
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::copy(&object, &target).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sha1_of;
+    use crate::utils::test_support::TempDir;
+
+    /// Excerpt of the `legacy` asset index used by 1.6.4
+    fn legacy_asset_index(objects: &[(&str, &[u8])]) -> AssetIndex {
+        let objects = objects.iter()
+            .map(|(name, content)| (name.to_string(), serde_json::json!({ "hash": sha1_of(content), "size": content.len() })))
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::from_value(serde_json::json!({ "virtual": true, "objects": objects })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn lays_out_legacy_assets_of_1_6_4() {
+        let sound: &[u8] = b"OggS cave1";
+        let lang: &[u8] = b"language.name=English";
+        let asset_index = legacy_asset_index(&[
+            ("sounds/ambient/cave/cave1.ogg", sound),
+            ("lang/en_US.lang", lang),
+            ("../outside.txt", b"invalid"),
+        ]);
+        assert!(asset_index.is_virtual);
+        assert!(!asset_index.map_to_resources);
+
+        let root = TempDir::new("legacy-assets");
+        let (assets_folder, game_dir) = (root.join("assets"), root.join("game"));
+        let objects_folder = assets_folder.join("objects");
+        for content in [sound, lang, b"invalid"] {
+            let hash = sha1_of(content);
+            let object = objects_folder.join(&hash[0..2]).join(&hash);
+            fs::create_dir_all(object.parent().unwrap()).await.unwrap();
+            fs::write(object, content).await.unwrap();
+        }
+
+        let game_assets = game_assets_folder(&asset_index, "legacy", &assets_folder, &game_dir);
+        assert_eq!(game_assets, assets_folder.join("virtual").join("legacy"));
+
+        materialize_legacy_assets(&asset_index, &objects_folder, &game_assets).await.unwrap();
+        assert_eq!(fs::read(game_assets.join("sounds/ambient/cave/cave1.ogg")).await.unwrap(), sound);
+        assert_eq!(fs::read(game_assets.join("lang/en_US.lang")).await.unwrap(), lang);
+        assert!(!root.join("assets").join("virtual").join("outside.txt").exists());
+    }
+
+    #[test]
+    fn pre_1_6_assets_are_mapped_to_resources() {
+        let asset_index = serde_json::from_value::<AssetIndex>(serde_json::json!({
+            "map_to_resources": true,
+            "objects": {},
+        })).unwrap();
+
+        let game_assets = game_assets_folder(&asset_index, "pre-1.6", Path::new("assets"), Path::new("game"));
+        assert_eq!(game_assets, Path::new("game").join("resources"));
+    }
 }
@@ -1,19 +1,23 @@
 use std::path::Path;
 use std::fmt::Write;
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use path_absolutize::Absolutize;
 use tokio::fs;
 
 use crate::{
-    error::LauncherError, minecraft::{progress::{get_max, get_progress, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps}, version::VersionProfile}, utils::{download_file, sha1sum, OS}
+    error::LauncherError, minecraft::{progress::{get_max, get_progress, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps}, version::VersionProfile}, utils::{download_file_hashed, sha1sum_async, OS}
 };
 
+use crate::app::app_data::LauncherConfig;
+
 use super::LauncherData;
 
 pub async fn setup_client_jar<D: Send + Sync>(
+    versions_folder: &Path,
     client_folder: &Path,
     natives_folder: &Path,
     version_profile: &VersionProfile,
+    config: &LauncherConfig,
     launcher_data: &LauncherData<D>,
     class_path: &mut String,
 ) -> Result<()> {
@@ -22,7 +26,17 @@ pub async fn setup_client_jar<D: Send + Sync>(
         .as_ref()
         .and_then(|x| x.client.as_ref())
     {
-        let client_jar = client_folder.join(format!("{}.jar", &version_profile.id));
+        let client_jar = version_profile.client_jar_path(versions_folder);
+        // The jar is stored under its vanilla version, which differs from the folder of loader profiles
+        fs::create_dir_all(client_jar.parent().context("Failed to get parent of client JAR")?).await
+            .context("Failed to create folder of client JAR")?;
+
+        // Previously the client jar has been stored next to the natives of the profile
+        let profile_client_jar = client_folder.join(format!("{}.jar", &version_profile.id));
+        if !client_jar.exists() && profile_client_jar.exists() && profile_client_jar != client_jar {
+            fs::rename(&profile_client_jar, &client_jar).await
+                .context("Failed to move client JAR")?;
+        }
 
         // Add client jar to class path
         write!(
@@ -35,8 +49,12 @@ pub async fn setup_client_jar<D: Send + Sync>(
         // Download client jar
         let requires_download = if !client_jar.exists() {
             true
+        } else if fs::metadata(&client_jar).await?.len() != client_download.size as u64 {
+            // Cheaper than the checksum, e.g. for interrupted copies
+            launcher_data.log("Client JAR size does not match, downloading again");
+            true
         } else {
-            let hash = sha1sum(&client_jar)?;
+            let hash = sha1sum_async(&client_jar).await?;
             launcher_data.log(&*format!(
                 "Client JAR local hash: {}, remote: {}",
                 hash, client_download.sha1
@@ -48,27 +66,30 @@ pub async fn setup_client_jar<D: Send + Sync>(
             launcher_data.log("Downloading client...");
             launcher_data.progress_update(ProgressUpdate::set_label("Downloading client..."));
 
-            let retrieved_bytes = download_file(&client_download.url, |a, b| {
+            // The hash is computed while downloading
+            let hash = download_file_hashed(&client_download.url, &client_jar, &config.download_options(&launcher_data.cancellation), launcher_data, |a, b| {
                 launcher_data.progress_update(ProgressUpdate::set_for_step(
                     ProgressUpdateSteps::DownloadClientJar,
                     get_progress(0, a, b),
                     get_max(1),
                 ));
             })
-            .await?;
-
-            fs::write(&client_jar, retrieved_bytes)
-                .await
-                .context("Failed to write client JAR")?;
+            .await
+            .context("Failed to download client JAR")?;
 
             // After downloading, check sha1
-            let hash = sha1sum(&client_jar)?;
             launcher_data.log(&*format!(
                 "Client JAR local hash: {}, remote: {}",
                 hash, client_download.sha1
             ));
             if hash != client_download.sha1 {
-                bail!("Client JAR download failed. SHA1 mismatch.");
+                // Otherwise the corrupted jar would be checked again on every launch
+                let _ = fs::remove_file(&client_jar).await;
+                return Err(LauncherError::ChecksumMismatch {
+                    expected: client_download.sha1.clone(),
+                    actual: hash,
+                    name: client_jar.display().to_string(),
+                }.into());
             }
         }
 
@@ -0,0 +1,180 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use crate::minecraft::version::ArgumentDeclaration;
+
+use super::LaunchingParameter;
+
+/// Feature of version profiles which support joining a server directly
+pub(crate) const QUICK_PLAY_MULTIPLAYER_FEATURE: &str = "is_quick_play_multiplayer";
+
+/// Versions declaring this feature log the quick play session to the file of `--quickPlayPath`
+pub(crate) const QUICK_PLAYS_SUPPORT_FEATURE: &str = "has_quick_plays_support";
+
+pub(crate) const DEMO_USER_FEATURE: &str = "is_demo_user";
+
+/// Versions declaring this feature pass the window size through templates instead of our arguments
+pub(crate) const CUSTOM_RESOLUTION_FEATURE: &str = "has_custom_resolution";
+
+///
+/// Features the rules of the arguments are evaluated against.
+/// A feature is only enabled if the version profile declares it, otherwise the launcher passes plain arguments instead.
+///
+pub struct FeatureSet {
+    supported: HashSet<&'static str>,
+    enabled: HashSet<String>,
+}
+
+impl FeatureSet {
+
+    pub fn new(launching_parameter: &LaunchingParameter, arguments: &ArgumentDeclaration) -> Result<Self> {
+        let quick_play = launching_parameter.quick_play_server.is_some();
+        Ok(Self::from_requested(&[
+            (QUICK_PLAY_MULTIPLAYER_FEATURE, quick_play),
+            (QUICK_PLAYS_SUPPORT_FEATURE, quick_play),
+            (DEMO_USER_FEATURE, launching_parameter.demo),
+            (CUSTOM_RESOLUTION_FEATURE, launching_parameter.window_size()?.is_some()),
+        ], arguments))
+    }
+
+    /// Pairs of every feature and whether the launch asks for it
+    fn from_requested(requested: &[(&'static str, bool)], arguments: &ArgumentDeclaration) -> Self {
+        let supported = requested.iter()
+            .map(|(feature, _)| *feature)
+            .filter(|feature| arguments.has_feature(feature))
+            .collect::<HashSet<_>>();
+        let enabled = requested.iter()
+            .filter(|(feature, active)| *active && supported.contains(feature))
+            .map(|(feature, _)| feature.to_string())
+            .collect();
+
+        Self { supported, enabled }
+    }
+
+    /// Whether the version profile declares the feature
+    pub fn is_supported(&self, feature: &str) -> bool {
+        self.supported.contains(feature)
+    }
+
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        self.enabled.contains(feature)
+    }
+
+    /// Names of the enabled features, as matched by the rules
+    pub fn names(&self) -> &HashSet<String> {
+        &self.enabled
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEATURES: [&str; 4] = [QUICK_PLAY_MULTIPLAYER_FEATURE, QUICK_PLAYS_SUPPORT_FEATURE, DEMO_USER_FEATURE, CUSTOM_RESOLUTION_FEATURE];
+
+    /// Conditional game arguments of 1.20.4
+    fn arguments() -> ArgumentDeclaration {
+        let gated = |feature: &str, value: serde_json::Value| serde_json::json!({
+            "rules": [{ "action": "allow", "features": { feature: true } }],
+            "value": value,
+        });
+        serde_json::from_value(serde_json::json!({
+            "arguments": {
+                "game": [
+                    "--username", "${auth_player_name}",
+                    gated(DEMO_USER_FEATURE, serde_json::json!("--demo")),
+                    gated(CUSTOM_RESOLUTION_FEATURE, serde_json::json!(["--width", "${resolution_width}", "--height", "${resolution_height}"])),
+                    gated(QUICK_PLAYS_SUPPORT_FEATURE, serde_json::json!(["--quickPlayPath", "${quickPlayPath}"])),
+                    gated(QUICK_PLAY_MULTIPLAYER_FEATURE, serde_json::json!(["--quickPlayMultiplayer", "${quickPlayMultiplayer}"])),
+                ],
+                "jvm": [],
+            },
+        })).unwrap()
+    }
+
+    /// Game arguments with only the feature requested
+    fn game_arguments(feature: Option<&'static str>) -> (FeatureSet, Vec<String>) {
+        let arguments = arguments();
+        let requested = FEATURES.map(|x| (x, Some(x) == feature));
+        let feature_set = FeatureSet::from_requested(&requested, &arguments);
+
+        let mut command_arguments = Vec::new();
+        arguments.add_game_args_to_vec(&mut command_arguments, feature_set.names()).unwrap();
+        (feature_set, command_arguments)
+    }
+
+    #[test]
+    fn no_feature_adds_no_conditional_arguments() {
+        let (feature_set, command_arguments) = game_arguments(None);
+
+        assert!(feature_set.names().is_empty());
+        assert!(FEATURES.iter().all(|feature| feature_set.is_supported(feature)));
+        assert_eq!(command_arguments, ["--username", "${auth_player_name}"]);
+    }
+
+    #[test]
+    fn demo_user_adds_demo() {
+        let (feature_set, command_arguments) = game_arguments(Some(DEMO_USER_FEATURE));
+
+        assert!(feature_set.is_enabled(DEMO_USER_FEATURE));
+        assert_eq!(command_arguments, ["--username", "${auth_player_name}", "--demo"]);
+    }
+
+    #[test]
+    fn custom_resolution_adds_window_size() {
+        let (feature_set, command_arguments) = game_arguments(Some(CUSTOM_RESOLUTION_FEATURE));
+
+        assert!(feature_set.is_enabled(CUSTOM_RESOLUTION_FEATURE));
+        assert_eq!(command_arguments[2..], ["--width", "${resolution_width}", "--height", "${resolution_height}"]);
+    }
+
+    #[test]
+    fn quick_plays_support_adds_log_path() {
+        let (feature_set, command_arguments) = game_arguments(Some(QUICK_PLAYS_SUPPORT_FEATURE));
+
+        assert!(feature_set.is_enabled(QUICK_PLAYS_SUPPORT_FEATURE));
+        assert_eq!(command_arguments[2..], ["--quickPlayPath", "${quickPlayPath}"]);
+    }
+
+    #[test]
+    fn quick_play_multiplayer_adds_server() {
+        let (feature_set, command_arguments) = game_arguments(Some(QUICK_PLAY_MULTIPLAYER_FEATURE));
+
+        assert!(feature_set.is_enabled(QUICK_PLAY_MULTIPLAYER_FEATURE));
+        assert_eq!(command_arguments[2..], ["--quickPlayMultiplayer", "${quickPlayMultiplayer}"]);
+    }
+
+    #[test]
+    fn undeclared_feature_is_not_enabled() {
+        let arguments = serde_json::from_value::<ArgumentDeclaration>(serde_json::json!({
+            "minecraftArguments": "--username ${auth_player_name}",
+        })).unwrap();
+
+        let feature_set = FeatureSet::from_requested(&[(DEMO_USER_FEATURE, true)], &arguments);
+
+        // The launcher passes --demo itself to versions which do not know the feature
+        assert!(!feature_set.is_supported(DEMO_USER_FEATURE));
+        assert!(!feature_set.is_enabled(DEMO_USER_FEATURE));
+    }
+}
@@ -0,0 +1,388 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::{Context, Result};
+use async_zip::read::seek::ZipFileReader as SeekZipFileReader;
+use serde::Deserialize;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use crate::app::api::{LaunchManifest, LoaderSubsystem};
+use crate::error::LauncherError;
+use crate::minecraft::java::{set_process_group, ProcessTreeGuard};
+use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
+use crate::minecraft::version::Library;
+use crate::utils::{download_file_resumable, get_maven_artifact_path, sha1sum_async, OS};
+
+use crate::app::app_data::LauncherConfig;
+
+use super::LauncherData;
+
+///
+/// Install profile of the Forge and NeoForge installers
+///
+#[derive(Deserialize)]
+struct InstallProfile {
+    /// Path of the version profile inside the installer
+    #[serde(default = "default_version_json")]
+    json: String,
+    #[serde(default)]
+    data: HashMap<String, SidedData>,
+    #[serde(default)]
+    processors: Vec<Processor>,
+    #[serde(default)]
+    libraries: Vec<Library>,
+}
+
+fn default_version_json() -> String {
+    "/version.json".to_string()
+}
+
+#[derive(Deserialize)]
+struct SidedData {
+    client: String,
+}
+
+#[derive(Deserialize)]
+struct Processor {
+    /// Processors without sides run on the client and the server
+    sides: Option<Vec<String>>,
+    jar: String,
+    #[serde(default)]
+    classpath: Vec<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Files created by the processor and their SHA1
+    #[serde(default)]
+    outputs: HashMap<String, String>,
+}
+
+/// Installer of the launch manifest, only Forge and NeoForge builds might specify one
+fn installer_url(manifest: &LaunchManifest) -> Option<String> {
+    let build = &manifest.build;
+    match &manifest.subsystem {
+        LoaderSubsystem::Forge { installer, .. } => installer.clone(),
+        LoaderSubsystem::NeoForge { installer, .. } => installer.as_ref().map(|installer| installer
+            .replace("{MINECRAFT_VERSION}", &build.mc_version)
+            .replace("{NEOFORGE_VERSION}", &build.subsystem_specific_data.neoforge_version)),
+        _ => None,
+    }
+}
+
+///
+/// Runs the processors of the installer, which patch the client jar and create the libraries
+/// of Forge 1.13+ that are not downloadable. This is only done once per installer.
+///
+pub async fn run_installer_processors<D: Send + Sync>(
+    data: &Path,
+    libraries_folder: &Path,
+    client_jar: &Path,
+    java_bin: &Path,
+    manifest: &LaunchManifest,
+    config: &LauncherConfig,
+    launcher_data: &LauncherData<D>,
+) -> Result<()> {
+    let Some(installer_url) = installer_url(manifest) else {
+        return Ok(());
+    };
+
+    let installers_folder = data.join("installers");
+    let installer_name = installer_name(&installer_url)?;
+    let installed_marker = installers_folder.join(format!("{}.installed", installer_name));
+
+    if installed_marker.exists() {
+        return Ok(());
+    }
+
+    let download_options = config.download_options(&launcher_data.cancellation);
+    let installer = fetch_installer(&installers_folder, &installer_url, config, launcher_data).await?;
+
+    let mut reader = SeekZipFileReader::new(fs::File::open(&installer).await?).await?;
+    let install_profile = read_install_profile(&mut reader).await?;
+
+    launcher_data.progress_update(ProgressUpdate::set_label("Downloading installer libraries..."));
+    for library in &install_profile.libraries {
+        let artifact = library.get_library_download()?;
+        let library_path = libraries_folder.join(&artifact.path);
+
+        // Libraries without URL are shipped with the installer
+        if artifact.url.is_empty() {
+            if !library_path.exists() {
+                extract_entry(&mut reader, &format!("maven/{}", artifact.path), &library_path).await?;
+            }
+            continue;
+        }
+
+        artifact.download(&library.name, libraries_folder.to_path_buf(), &download_options, launcher_data).await
+            .with_context(|| format!("Failed to download installer library {}", library.name))?;
+    }
+
+    // Data files of the installer are referenced by their path inside the installer
+    let data_folder = installers_folder.join(format!("{}.data", installer_name));
+    let mut variables = HashMap::from([
+        ("SIDE".to_string(), "client".to_string()),
+        ("MINECRAFT_JAR".to_string(), path_to_string(client_jar)),
+        ("MINECRAFT_VERSION".to_string(), manifest.build.mc_version.clone()),
+        ("ROOT".to_string(), path_to_string(data)),
+        ("INSTALLER".to_string(), path_to_string(&installer)),
+        ("LIBRARY_DIR".to_string(), path_to_string(libraries_folder)),
+    ]);
+    for (key, value) in &install_profile.data {
+        let value = match value.client.strip_prefix('/') {
+            Some(path) => {
+                let target = data_folder.join(path);
+                extract_entry(&mut reader, path, &target).await?;
+                path_to_string(&target)
+            }
+            None => resolve_value(&value.client, libraries_folder, &HashMap::new())?,
+        };
+        variables.insert(key.clone(), value);
+    }
+
+    let processors = install_profile.processors.iter()
+        .filter(|processor| processor.sides.as_ref().map_or(true, |sides| sides.iter().any(|side| side == "client")))
+        .collect::<Vec<_>>();
+    for (index, processor) in processors.iter().enumerate() {
+        launcher_data.check_cancelled()?;
+        launcher_data.progress_update(ProgressUpdate::set_label(format!(
+            "Running installer processor {} of {}...",
+            index + 1,
+            processors.len()
+        )));
+        run_processor(processor, java_bin, libraries_folder, &variables, launcher_data).await?;
+    }
+
+    fs::write(&installed_marker, []).await?;
+    info!("Installed {}", installer_name);
+    Ok(())
+}
+
+async fn run_processor<D: Send + Sync>(
+    processor: &Processor,
+    java_bin: &Path,
+    libraries_folder: &Path,
+    variables: &HashMap<String, String>,
+    launcher_data: &LauncherData<D>,
+) -> Result<()> {
+    let outputs = processor.outputs.iter()
+        .map(|(path, sha1)| Ok((resolve_value(path, libraries_folder, variables)?, resolve_value(sha1, libraries_folder, variables)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Output of a previous, interrupted installation might still be valid
+    let mut up_to_date = !outputs.is_empty();
+    for (path, sha1) in &outputs {
+        if !up_to_date {
+            break;
+        }
+        up_to_date = sha1sum_async(Path::new(path)).await.is_ok_and(|hash| hash == *sha1);
+    }
+    if up_to_date {
+        debug!("Outputs of processor {} are up to date", processor.jar);
+        return Ok(());
+    }
+
+    let jar = libraries_folder.join(get_maven_artifact_path(&processor.jar)?);
+    let main_class = read_main_class(&jar).await?;
+
+    let separator = OS.get_path_separator()?;
+    let mut class_path = path_to_string(&jar);
+    for library in &processor.classpath {
+        class_path.push_str(separator);
+        class_path.push_str(&path_to_string(&libraries_folder.join(get_maven_artifact_path(library)?)));
+    }
+
+    let args = processor.args.iter()
+        .map(|arg| resolve_value(arg, libraries_folder, variables))
+        .collect::<Result<Vec<_>>>()?;
+
+    launcher_data.log(&format!("Running processor {}", processor.jar));
+    let mut command = std::process::Command::new(java_bin);
+    command
+        .arg("-cp")
+        .arg(&class_path)
+        .arg(&main_class)
+        .args(&args);
+    set_process_group(&mut command);
+    let child = Command::from(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to run processor {}", processor.jar))?;
+    // Kills the processor with everything it has started when the prelaunch is aborted, times out or is cancelled
+    let mut tree_guard = ProcessTreeGuard::new(&child);
+
+    let output = tokio::select! {
+        output = child.wait_with_output() => output
+            .with_context(|| format!("Failed to run processor {}", processor.jar))?,
+        _ = launcher_data.cancellation.cancelled() => return Err(LauncherError::Cancelled.into()),
+    };
+    tree_guard.disarm();
+
+    for line in String::from_utf8_lossy(&output.stdout).lines().chain(String::from_utf8_lossy(&output.stderr).lines()) {
+        launcher_data.log(&format!("[{}] {}", processor.jar, line));
+    }
+
+    if !output.status.success() {
+        return Err(LauncherError::ProcessorFailed {
+            processor: processor.jar.clone(),
+            exit_code: output.status.code(),
+        }.into());
+    }
+
+    for (path, sha1) in outputs {
+        let actual = sha1sum_async(Path::new(&path)).await?;
+        if actual != sha1 {
+            return Err(LauncherError::ChecksumMismatch {
+                expected: sha1,
+                actual,
+                name: path,
+            }.into());
+        }
+    }
+
+    Ok(())
+}
+
+///
+/// Loads the version profile shipped with the installer, NeoForge only publishes its profiles as part of the installer.
+/// Returns none if the build has no installer.
+///
+pub async fn load_installer_version_profile<D: Send + Sync>(
+    data: &Path,
+    manifest: &LaunchManifest,
+    config: &LauncherConfig,
+    launcher_data: &LauncherData<D>,
+) -> Result<Option<serde_json::Value>> {
+    let Some(installer_url) = installer_url(manifest) else {
+        return Ok(None);
+    };
+
+    let installer = fetch_installer(&data.join("installers"), &installer_url, config, launcher_data).await?;
+    let mut reader = SeekZipFileReader::new(fs::File::open(&installer).await?).await?;
+    let install_profile = read_install_profile(&mut reader).await?;
+
+    let profile_path = install_profile.json.trim_start_matches('/');
+    let version_json = serde_json::from_slice(&read_entry(&mut reader, profile_path).await?)
+        .with_context(|| format!("Failed to parse {} of the installer", profile_path))?;
+    Ok(Some(version_json))
+}
+
+/// Whether the installer of the build has been downloaded before, a build without installer does not need one
+pub fn is_installer_downloaded(data: &Path, manifest: &LaunchManifest) -> bool {
+    match installer_url(manifest) {
+        Some(installer_url) => installer_name(&installer_url)
+            .is_ok_and(|name| data.join("installers").join(name).exists()),
+        None => true,
+    }
+}
+
+fn installer_name(installer_url: &str) -> Result<&str> {
+    installer_url.rsplit('/').next()
+        .filter(|name| !name.is_empty())
+        .with_context(|| format!("Invalid installer URL {}", installer_url))
+}
+
+/// Downloads the installer into the installers folder, unless it has been downloaded before
+async fn fetch_installer<D: Send + Sync>(
+    installers_folder: &Path,
+    installer_url: &str,
+    config: &LauncherConfig,
+    launcher_data: &LauncherData<D>,
+) -> Result<PathBuf> {
+    let installer = installers_folder.join(installer_name(installer_url)?);
+    if installer.exists() {
+        return Ok(installer);
+    }
+
+    fs::create_dir_all(installers_folder).await?;
+    launcher_data.progress_update(ProgressUpdate::set_label("Downloading installer..."));
+    let download_options = config.download_options(&launcher_data.cancellation);
+    download_file_resumable(installer_url, &installer, &download_options, launcher_data, |_, _| {}).await
+        .context("Failed to download installer")?;
+    Ok(installer)
+}
+
+async fn read_install_profile(reader: &mut SeekZipFileReader<fs::File>) -> Result<InstallProfile> {
+    serde_json::from_slice::<InstallProfile>(&read_entry(reader, "install_profile.json").await?)
+        .context("Failed to parse install profile")
+}
+
+/// Resolves `{VARIABLE}`, `[maven:artifact]` and `'literal'` values of the install profile
+fn resolve_value(value: &str, libraries_folder: &Path, variables: &HashMap<String, String>) -> Result<String> {
+    if let Some(key) = value.strip_prefix('{').and_then(|x| x.strip_suffix('}')) {
+        return variables.get(key)
+            .cloned()
+            .ok_or_else(|| LauncherError::UnknownTemplateParameter(key.to_string()).into());
+    }
+
+    if let Some(artifact) = value.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+        return Ok(path_to_string(&libraries_folder.join(get_maven_artifact_path(artifact)?)));
+    }
+
+    if let Some(literal) = value.strip_prefix('\'').and_then(|x| x.strip_suffix('\'')) {
+        return Ok(literal.to_string());
+    }
+
+    Ok(value.to_string())
+}
+
+async fn read_main_class(jar: &Path) -> Result<String> {
+    let mut reader = SeekZipFileReader::new(fs::File::open(jar).await
+        .with_context(|| format!("Processor {} is missing", jar.display()))?).await?;
+    let manifest = String::from_utf8(read_entry(&mut reader, "META-INF/MANIFEST.MF").await?)?;
+
+    manifest.lines()
+        .find_map(|line| line.strip_prefix("Main-Class:"))
+        .map(|main_class| main_class.trim().to_string())
+        .with_context(|| format!("Processor {} has no main class", jar.display()))
+}
+
+async fn read_entry(reader: &mut SeekZipFileReader<fs::File>, name: &str) -> Result<Vec<u8>> {
+    let index = reader.file().entries().iter()
+        .position(|x| x.entry().filename() == name)
+        .with_context(|| format!("{} is missing in the archive", name))?;
+
+    let mut content = Vec::new();
+    reader.entry(index).await?.read_to_end(&mut content).await?;
+    Ok(content)
+}
+
+async fn extract_entry(reader: &mut SeekZipFileReader<fs::File>, name: &str, target: &Path) -> Result<()> {
+    let content = read_entry(reader, name).await?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let mut file = fs::File::create(target).await?;
+    file.write_all(&content).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+fn path_to_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
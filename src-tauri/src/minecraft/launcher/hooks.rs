@@ -0,0 +1,214 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::app::api::Build;
+use crate::error::LauncherError;
+use crate::minecraft::java::{set_process_group, ProcessTreeGuard};
+
+/// Post-exit hooks are not bound by the prelaunch timeout, they are stopped after this time instead
+pub(crate) const POST_EXIT_HOOK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Output is read for this long after the hook has exited, processes it started in the background might keep it open
+const HOOK_OUTPUT_GRACE: Duration = Duration::from_millis(200);
+
+/// Runs a user command through the shell of the system, with the game directory as working directory.
+/// The output is passed to the log line by line and a non-zero exit code is returned as [LauncherError::HookFailed].
+/// The hook is done once the shell has exited, processes started in the background keep running.
+/// On timeout or cancellation the hook is killed along with all processes it has started.
+pub(crate) async fn run_hook(hook: &str, command: &str, game_dir: &Path, build: &Build, timeout: Option<Duration>, cancellation: Option<&CancellationToken>, log: &(dyn Fn(&str) + Sync)) -> Result<()> {
+    log(&format!("Running {} hook: {}", hook, command));
+    debug!("Running {} hook {} in {}", hook, command, game_dir.display());
+
+    let mut shell = shell_command(command);
+    set_process_group(&mut shell);
+    let mut child = Command::from(shell)
+        .current_dir(game_dir)
+        .env("MC_VERSION", &build.mc_version)
+        .env("BUILD_ID", build.build_id.to_string())
+        .env("BRANCH", &build.branch)
+        .env("GAME_DIR", game_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to run {} hook {}", hook, command))?;
+    // Kills the hook with everything it has started when the prelaunch is aborted, times out or is cancelled
+    let mut tree_guard = ProcessTreeGuard::new(&child);
+
+    let cancelled = async {
+        match cancellation {
+            Some(cancellation) => cancellation.cancelled().await,
+            None => std::future::pending().await,
+        }
+    };
+    let timed_out = async {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    let status = tokio::select! {
+        status = wait_logging_output(hook, &mut child, log) => status?,
+        _ = cancelled => return Err(LauncherError::Cancelled.into()),
+        _ = timed_out => {
+            return Err(LauncherError::HookTimeout { hook: hook.to_string(), secs: timeout.unwrap_or_default().as_secs() }.into());
+        },
+    };
+    tree_guard.disarm();
+
+    if !status.success() {
+        return Err(LauncherError::HookFailed {
+            hook: hook.to_string(),
+            exit_code: status.code(),
+        }.into());
+    }
+
+    Ok(())
+}
+
+/// Logs the output of the hook until it has exited and the output is closed, or the grace period after the exit is over
+async fn wait_logging_output(hook: &str, child: &mut Child, log: &(dyn Fn(&str) + Sync)) -> Result<ExitStatus> {
+    let mut stdout = BufReader::new(child.stdout.take().context("Output of the hook is not piped")?).lines();
+    let mut stderr = BufReader::new(child.stderr.take().context("Output of the hook is not piped")?).lines();
+    let (mut stdout_open, mut stderr_open) = (true, true);
+
+    let mut exit_status = None;
+    let grace = tokio::time::sleep(HOOK_OUTPUT_GRACE);
+    tokio::pin!(grace);
+
+    loop {
+        if let (Some(status), false, false) = (exit_status, stdout_open, stderr_open) {
+            return Ok(status);
+        }
+
+        tokio::select! {
+            line = stdout.next_line(), if stdout_open => match line {
+                Ok(Some(line)) => log(&format!("[{}] {}", hook, line)),
+                _ => stdout_open = false,
+            },
+            line = stderr.next_line(), if stderr_open => match line {
+                Ok(Some(line)) => log(&format!("[{}] {}", hook, line)),
+                _ => stderr_open = false,
+            },
+            status = child.wait(), if exit_status.is_none() => {
+                exit_status = Some(status?);
+                grace.as_mut().reset(Instant::now() + HOOK_OUTPUT_GRACE);
+            },
+            _ = &mut grace, if exit_status.is_some() => {
+                debug!("Output of the {} hook is still open, it is kept by a process running in the background", hook);
+                return Ok(exit_status.unwrap());
+            },
+        }
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> std::process::Command {
+    let mut shell = std::process::Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> std::process::Command {
+    let mut shell = std::process::Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::utils::test_support::TempDir;
+
+    use super::*;
+
+    fn build() -> Build {
+        serde_json::from_value(serde_json::json!({
+            "buildId": 1, "commitId": "0000000", "branch": "nextgen", "subsystem": "fabric", "lbVersion": "0.1.0",
+            "mcVersion": "1.20.4", "release": false, "date": "2024-01-01T00:00:00Z", "message": "", "url": "",
+            "jreVersion": 17, "fabricApiVersion": "", "fabricLoaderVersion": "", "kotlinVersion": "", "kotlinModVersion": "",
+        })).unwrap()
+    }
+
+    async fn run(command: &str, timeout: Option<Duration>) -> (Result<()>, Vec<String>) {
+        let lines = Mutex::new(Vec::new());
+        let game_dir = TempDir::new("hook");
+        let result = run_hook("test", command, game_dir.path(), &build(), timeout, None, &|line| lines.lock().unwrap().push(line.to_string())).await;
+        (result, lines.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn background_process_does_not_block_the_hook() {
+        let (result, lines) = tokio::time::timeout(Duration::from_secs(10), run("echo started; sleep 5 &", None)).await.unwrap();
+
+        result.unwrap();
+        assert!(lines.contains(&"[test] started".to_string()), "{:?}", lines);
+    }
+
+    #[tokio::test]
+    async fn failing_hook_reports_exit_code() {
+        let (result, lines) = run("echo failed >&2; exit 3", None).await;
+
+        let error = result.unwrap_err();
+        assert!(matches!(error.downcast_ref::<LauncherError>(), Some(LauncherError::HookFailed { exit_code: Some(3), .. })));
+        assert!(lines.contains(&"[test] failed".to_string()), "{:?}", lines);
+    }
+
+    #[tokio::test]
+    async fn hook_exceeding_its_timeout_is_stopped() {
+        let (result, _) = run("sleep 30", Some(Duration::from_millis(100))).await;
+
+        let error = result.unwrap_err();
+        assert!(matches!(error.downcast_ref::<LauncherError>(), Some(LauncherError::HookTimeout { .. })));
+    }
+
+    #[tokio::test]
+    async fn hook_exceeding_its_timeout_is_stopped_with_its_background_processes() {
+        let (result, lines) = run("sleep 30 & echo $!; wait", Some(Duration::from_millis(200))).await;
+        assert!(result.is_err());
+
+        let pid = lines.iter().find_map(|line| line.strip_prefix("[test] ")).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        // The killed process is reaped by init, until then it is still reported as existing
+        while is_running(pid) {
+            assert!(Instant::now() < deadline, "background process {} is still running", pid);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    fn is_running(pid: &str) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .is_ok_and(|stat| !stat.contains(") Z "))
+    }
+}
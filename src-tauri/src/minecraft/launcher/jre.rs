@@ -1,21 +1,27 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
+use tracing::debug;
 
 use crate::{
     app::api::LaunchManifest,
     minecraft::{
-        java::{find_java_binary, jre_downloader},
+        version::VersionProfile,
+        java::{find_java_binary, find_system_java, jre_downloader},
         progress::{get_max, get_progress, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps},
     },
 };
 
+use crate::app::app_data::LauncherConfig;
+
 use super::{LauncherData, LaunchingParameter};
 
 pub async fn load_jre<D: Send + Sync>(
     runtimes_folder: &Path,
     manifest: &LaunchManifest,
+    version_profile: &VersionProfile,
     launching_parameter: &LaunchingParameter,
+    config: &LauncherConfig,
     launcher_data: &LauncherData<D>,
 ) -> Result<PathBuf> {
     if let Some(jre) = &launching_parameter.custom_java_path {
@@ -24,26 +30,43 @@ pub async fn load_jre<D: Send + Sync>(
 
     launcher_data.progress_update(ProgressUpdate::set_label("Checking for JRE..."));
 
+    // The version profile knows best which Java the game requires, the build only provides the fallback
+    let required_version = version_profile.java_version.as_ref()
+        .map(|x| x.major_version)
+        .unwrap_or(manifest.build.jre_version);
+
     if let Ok(jre) = find_java_binary(
         runtimes_folder,
         &manifest.build.jre_distribution,
-        &manifest.build.jre_version,
+        &required_version,
     )
     .await
     {
         return Ok(jre);
     }
 
+    // Use an installed JRE of the required version instead of downloading one
+    match find_system_java(required_version).await {
+        Ok(jre) => {
+            launcher_data.log(&format!("Using installed Java {} at {:?}", required_version, jre));
+            return Ok(jre);
+        }
+        Err(e) => debug!("{}", e)
+    }
+
     launcher_data.log("Downloading JRE...");
     launcher_data.progress_update(ProgressUpdate::set_label("Download JRE..."));
 
     jre_downloader::jre_download(
         &runtimes_folder,
         &manifest.build.jre_distribution,
-        &manifest.build.jre_version,
+        &required_version,
+        version_profile.java_version.as_ref().and_then(|x| x.component.as_deref()),
+        &config.download_options(&launcher_data.cancellation),
+        launcher_data,
         |a, b| {
             launcher_data.progress_update(ProgressUpdate::set_for_step(
-                ProgressUpdateSteps::DownloadJRE,
+                ProgressUpdateSteps::DownloadJava,
                 get_progress(0, a, b),
                 get_max(1),
             ));
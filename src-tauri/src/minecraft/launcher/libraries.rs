@@ -1,4 +1,4 @@
-use std::{collections::HashSet, path::Path};
+use std::{collections::HashSet, path::Path, sync::atomic::{AtomicU64, Ordering}};
 use std::fmt::Write;
 use anyhow::{Context, Result};
 use futures::{stream, StreamExt};
@@ -7,26 +7,32 @@ use tokio::fs::OpenOptions;
 
 use crate::{
     error::LauncherError,
-    minecraft::{progress::{ProgressReceiver, ProgressUpdate, ProgressUpdateSteps}, rule_interpreter, version::{LibraryDownloadInfo, VersionProfile}}, utils::{zip_extract, OS},
+    minecraft::{progress::{ProgressReceiver, ProgressUpdate, ProgressUpdateSteps}, rule_interpreter, version::{LibraryDownloadInfo, VersionProfile}}, utils::{zip_extract_excluding, OS},
 };
 
-use super::{LauncherData, LaunchingParameter};
+use crate::app::app_data::LauncherConfig;
+
+use super::LauncherData;
 
 pub async fn setup_libraries<D: Send + Sync>(
     libraries_folder: &Path,
     natives_folder: &Path,
     version_profile: &VersionProfile,
-    launching_parameter: &LaunchingParameter,
+    config: &LauncherConfig,
     launcher_data: &LauncherData<D>,
     features: &HashSet<String>,
     class_path: &mut String,
 ) -> Result<()> {
+    // Libraries which are not meant for this system are left out, so they do not count towards the progress
     let libraries_to_download = version_profile
         .libraries
         .iter()
+        .filter(|library| rule_interpreter::check_condition(&library.rules, features).unwrap_or(false))
         .map(|x| x.to_owned())
         .collect::<Vec<_>>();
     let libraries_max = libraries_to_download.len() as u64;
+    let libraries_done = AtomicU64::new(0);
+    let download_options = config.download_options(&launcher_data.cancellation);
 
     launcher_data.progress_update(ProgressUpdate::set_label("Checking libraries..."));
     launcher_data.progress_update(ProgressUpdate::set_for_step(
@@ -36,70 +42,88 @@ pub async fn setup_libraries<D: Send + Sync>(
     ));
 
     let class_paths: Vec<Result<Option<String>>> =
-        stream::iter(libraries_to_download.into_iter().filter_map(|library| {
+        stream::iter(libraries_to_download.into_iter().map(|library| {
             // let download_count = libraries_downloaded.clone();
             let folder_clone = libraries_folder.to_path_buf();
             let native_clone = natives_folder.to_path_buf();
+            let download_options = &download_options;
+            let libraries_done = &libraries_done;
 
-            if !rule_interpreter::check_condition(&library.rules, &features).unwrap_or(false) {
-                return None;
-            }
-
-            Some(async move {
-                if let Some(natives) = &library.natives {
-                    if let Some(required_natives) = natives.get(OS.get_simple_name()?) {
-                        if let Some(classifiers) = library
-                            .downloads
-                            .as_ref()
-                            .and_then(|x| x.classifiers.as_ref())
-                        {
-                            if let Some(artifact) = classifiers
-                                .get(required_natives)
-                                .map(LibraryDownloadInfo::from)
+            async move {
+                let result = async {
+                    if library.natives.is_some() {
+                        if let Some(required_natives) = library.native_classifier()? {
+                            if let Some(classifiers) = library
+                                .downloads
+                                .as_ref()
+                                .and_then(|x| x.classifiers.as_ref())
                             {
-                                let path = artifact
-                                    .download(&library.name, folder_clone.clone(), launcher_data)
-                                    .await
-                                    .with_context(|| {
-                                        format!("Failed to download native library: {}", &library.name)
-                                    })?;
-
-                                launcher_data.progress_update(ProgressUpdate::set_label("Extracting natives..."));
-                                let file = OpenOptions::new()
-                                    .read(true)
-                                    .open(path)
-                                    .await
-                                    .context("Failed to open native library")?;
-                                zip_extract(file, &native_clone).await
-                                    .context("Failed to extract native library")?;
+                                if let Some(artifact) = classifiers
+                                    .get(&required_natives)
+                                    .map(LibraryDownloadInfo::from)
+                                {
+                                    let path = artifact
+                                        .download(&library.name, folder_clone.clone(), download_options, launcher_data)
+                                        .await
+                                        .with_context(|| {
+                                            format!("Failed to download native library: {}", &library.name)
+                                        })?;
+
+                                    launcher_data.progress_update(ProgressUpdate::set_label("Extracting natives..."));
+                                    let file = OpenOptions::new()
+                                        .read(true)
+                                        .open(path)
+                                        .await
+                                        .context("Failed to open native library")?;
+                                    let exclude = library.extract.as_ref()
+                                        .map(|x| x.exclude.as_slice())
+                                        .unwrap_or_default();
+                                    zip_extract_excluding(file, &native_clone, exclude, |file_name, index, count| {
+                                        launcher_data.progress_update(ProgressUpdate::set_label(format!(
+                                            "Extracting {} ({}/{})",
+                                            file_name, index + 1, count
+                                        )));
+                                    }).await
+                                        .context("Failed to extract native library")?;
+                                }
+                            } else {
+                                return Err(LauncherError::InvalidVersionProfile(
+                                    "missing classifiers, but natives required.".to_string(),
+                                )
+                                .into());
                             }
-                        } else {
-                            return Err(LauncherError::InvalidVersionProfile(
-                                "missing classifiers, but natives required.".to_string(),
-                            )
-                            .into());
                         }
+
+                        return Ok(None);
                     }
 
-                    return Ok(None);
-                }
-
-                // Download regular artifact
-                let artifact = library.get_library_download()?;
-                let path = artifact
-                    .download(&library.name, folder_clone.clone(), launcher_data)
-                    .await
-                    .with_context(|| format!("Failed to download library: {}", &library.name))?;
-
-                // Natives are not included in the classpath
-                return if library.natives.is_none() {
-                    return Ok(path.absolutize()?.to_str().map(|x| x.to_string()));
-                } else {
-                    Ok(None)
-                };
-            })
+                    // Download regular artifact
+                    let artifact = library.get_library_download()?;
+                    let path = artifact
+                        .download(&library.name, folder_clone.clone(), download_options, launcher_data)
+                        .await
+                        .with_context(|| format!("Failed to download library: {}", &library.name))?;
+
+                    // Natives are not included in the classpath
+                    return if library.natives.is_none() {
+                        return Ok(path.absolutize()?.to_str().map(|x| x.to_string()));
+                    } else {
+                        Ok(None)
+                    };
+                }.await;
+
+                // Aggregate progress of all in-flight downloads
+                let curr = libraries_done.fetch_add(1, Ordering::Relaxed) + 1;
+                launcher_data.progress_update(ProgressUpdate::set_for_step(
+                    ProgressUpdateSteps::DownloadLibraries,
+                    curr,
+                    libraries_max,
+                ));
+
+                result
+            }
         }))
-        .buffer_unordered(launching_parameter.concurrent_downloads as usize)
+        .buffer_unordered(config.concurrent_downloads.max(1) as usize)
         .collect()
         .await;
 
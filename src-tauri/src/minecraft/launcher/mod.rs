@@ -17,36 +17,49 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use std::process::exit;
+use std::time::SystemTime;
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future::BoxFuture;
+use futures::FutureExt;
 
 use path_absolutize::Absolutize;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
 use tracing::*;
 
-use crate::app::api::LaunchManifest;
+use crate::app::api::{Build, LaunchManifest};
+use crate::app::app_data::LauncherConfig;
 use crate::auth::ClientAccount;
 use crate::error::LauncherError;
-use crate::minecraft::java::JavaRuntime;
-use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
-use crate::{join_and_mkdir, join_and_mkdir_vec};
+use crate::minecraft::java::{kill_process_tree, GameLog, JavaRuntime};
+use crate::minecraft::progress::{ProgressPlan, ProgressReceiver, ProgressTracker, ProgressUpdate, ProgressUpdateSteps};
+use crate::{join_and_mkdir, join_and_mkdir_vec, mkdir};
 use crate::{
-    utils::{OS, OS_VERSION},
-    LAUNCHER_VERSION,
+    utils::{total_memory_mb, OS, OS_VERSION},
+    LAUNCHER_DIRECTORY, LAUNCHER_VERSION,
 };
 
 use self::assets::setup_assets;
 use self::client_jar::setup_client_jar;
+use self::features::{FeatureSet, CUSTOM_RESOLUTION_FEATURE, DEMO_USER_FEATURE, QUICK_PLAYS_SUPPORT_FEATURE, QUICK_PLAY_MULTIPLAYER_FEATURE};
+use self::forge::run_installer_processors;
+pub(crate) use self::forge::{is_installer_downloaded, load_installer_version_profile};
+use self::hooks::{run_hook, POST_EXIT_HOOK_TIMEOUT};
 use self::jre::load_jre;
 use self::libraries::setup_libraries;
 
-use super::version::VersionProfile;
+use super::version::{LibraryConflictResolution, VersionProfile};
 
 mod assets;
 mod client_jar;
+mod features;
+mod forge;
+mod hooks;
 mod jre;
 mod libraries;
 
@@ -57,35 +70,97 @@ pub struct LauncherData<D: Send + Sync> {
     pub(crate) on_log: fn(&D, &str) -> Result<()>,
     pub(crate) hide_window: fn(&D),
     pub(crate) data: Box<D>,
-    pub(crate) terminator: tokio::sync::oneshot::Receiver<()>,
+    pub(crate) cancellation: CancellationToken,
+    pub(crate) progress: ProgressTracker,
+    /// Additionally receives every update and log message, e.g. a [crate::minecraft::progress::JsonProgressWriter]
+    pub(crate) progress_stream: Option<Box<dyn ProgressReceiver + Send + Sync>>,
 }
 
 impl<D: Send + Sync> LauncherData<D> {
     fn hide_window(&self) {
         (self.hide_window)(&self.data);
     }
+
+    /// Fails with [LauncherError::Cancelled] once the launch has been cancelled
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.cancellation.is_cancelled() {
+            return Err(LauncherError::Cancelled.into());
+        }
+        Ok(())
+    }
+
+    /// Resets the progress, which is reported against the total of the plan from now on
+    pub fn set_progress_plan(&self, plan: ProgressPlan) {
+        for update in self.progress.set_plan(plan) {
+            self.emit_progress(update);
+        }
+    }
+
+    /// Sends the update to the frontend and the progress stream
+    fn emit_progress(&self, update: ProgressUpdate) {
+        if let Some(progress_stream) = &self.progress_stream {
+            progress_stream.progress_update(update.clone());
+        }
+        let _ = (self.on_progress)(&self.data, update);
+    }
 }
 
 impl<D: Send + Sync> ProgressReceiver for LauncherData<D> {
     fn progress_update(&self, progress_update: ProgressUpdate) {
-        let _ = (self.on_progress)(&self.data, progress_update);
+        // The stream additionally receives the progress within the step and the bytes of every transfer,
+        // which are only known before they are turned into the overall progress
+        if let Some(progress_stream) = &self.progress_stream {
+            if progress_update.is_translated() {
+                progress_stream.progress_update(progress_update.clone());
+            }
+        }
+
+        for update in self.progress.translate(progress_update) {
+            self.emit_progress(update);
+        }
     }
     fn log(&self, msg: &str) {
+        if let Some(progress_stream) = &self.progress_stream {
+            progress_stream.log(msg);
+        }
         let _ = (self.on_log)(&self.data, msg);
     }
 }
 
+///
+/// The started game
+///
+pub struct LaunchResult {
+    /// Process id of the game, if it is still running
+    pub pid: Option<u32>,
+    pub java_path: PathBuf,
+    pub class_path: String,
+    /// Resolves once the game has exited, fails if the game crashed
+    pub exit: BoxFuture<'static, Result<()>>,
+}
+
+impl LaunchResult {
+    /// Kills the game with all processes it has started, only call this while the game is running
+    pub async fn kill(&self) -> Result<()> {
+        let pid = self.pid.ok_or_else(|| anyhow!("Process id of the game is unknown"))?;
+        kill_process_tree(pid).await
+    }
+}
+
 ///
 /// Launches the game
 ///
-pub async fn launch<D: Send + Sync>(
+pub async fn launch<D: Send + Sync + 'static>(
     data: &Path,
     manifest: LaunchManifest,
     version_profile: VersionProfile,
     launching_parameter: LaunchingParameter,
+    config: &LauncherConfig,
     launcher_data: LauncherData<D>,
-) -> Result<()> {
-    let features: HashSet<String> = HashSet::new();
+) -> Result<LaunchResult> {
+    let feature_set = FeatureSet::new(&launching_parameter, &version_profile.arguments)?;
+    let features = feature_set.names();
+    let window_size = launching_parameter.window_size()?;
     let mut class_path = String::new();
 
     launcher_data.progress_update(ProgressUpdate::set_label("Setting up..."));
@@ -101,16 +176,22 @@ pub async fn launch<D: Send + Sync>(
     let natives_folder = join_and_mkdir!(client_folder, "natives");
     let libraries_folder = join_and_mkdir!(data, "libraries");
     let assets_folder = join_and_mkdir!(data, "assets");
-    let game_dir = join_and_mkdir_vec!(data, vec!["gameDir", &*manifest.build.branch]);
+    let game_dir = launching_parameter.game_directory(&manifest.build);
+    mkdir!(&game_dir);
 
+    launcher_data.progress_update(ProgressUpdate::StepStarted(ProgressUpdateSteps::DownloadJava));
     let java_bin = load_jre(
         &runtimes_folder,
         &manifest,
+        &version_profile,
         &launching_parameter,
+        config,
         &launcher_data,
     )
     .await
     .context("Failed to load JRE")?;
+    launcher_data.check_cancelled()?;
+    launcher_data.progress_update(ProgressUpdate::StepFinished(ProgressUpdateSteps::DownloadJava));
 
     launcher_data.log(&format!("Java Path: {:?}", java_bin));
     if !java_bin.exists() {
@@ -118,42 +199,68 @@ pub async fn launch<D: Send + Sync>(
     }
 
     // Check if json has client download (or doesn't require one)
+    launcher_data.progress_update(ProgressUpdate::StepStarted(ProgressUpdateSteps::DownloadClientJar));
     setup_client_jar(
+        &data.join("versions"),
         &client_folder,
         &natives_folder,
         &version_profile,
+        config,
         &launcher_data,
         &mut class_path,
     )
     .await
     .context("Failed to setup client JAR")?;
+    launcher_data.check_cancelled()?;
+    launcher_data.progress_update(ProgressUpdate::StepFinished(ProgressUpdateSteps::DownloadClientJar));
+
+    // Creates the libraries of Forge which are not downloadable
+    run_installer_processors(
+        data,
+        &libraries_folder,
+        &version_profile.client_jar_path(&data.join("versions")),
+        &java_bin,
+        &manifest,
+        config,
+        &launcher_data,
+    )
+    .await
+    .context("Failed to run installer processors")?;
+    launcher_data.check_cancelled()?;
 
     // Libraries
+    launcher_data.progress_update(ProgressUpdate::StepStarted(ProgressUpdateSteps::DownloadLibraries));
     setup_libraries(
         &libraries_folder,
         &natives_folder,
         &version_profile,
-        &launching_parameter,
+        config,
         &launcher_data,
-        &features,
+        features,
         &mut class_path,
     )
     .await
     .context("Failed to setup libraries")?;
+    launcher_data.check_cancelled()?;
+    launcher_data.progress_update(ProgressUpdate::StepFinished(ProgressUpdateSteps::DownloadLibraries));
 
     // Assets
-    let asset_index_location = setup_assets(
+    launcher_data.progress_update(ProgressUpdate::StepStarted(ProgressUpdateSteps::DownloadAssets));
+    let assets = setup_assets(
         &assets_folder,
+        &game_dir,
         &version_profile,
-        &launching_parameter,
+        config,
         &launcher_data,
     )
     .await
     .context("Failed to setup assets")?;
+    launcher_data.check_cancelled()?;
+    launcher_data.progress_update(ProgressUpdate::StepFinished(ProgressUpdateSteps::DownloadAssets));
 
     // Game
 
-    let java_runtime = JavaRuntime::new(java_bin);
+    let java_runtime = JavaRuntime::new(java_bin.clone());
 
     let mut command_arguments = Vec::new();
 
@@ -161,24 +268,53 @@ pub async fn launch<D: Send + Sync>(
     version_profile.arguments.add_jvm_args_to_vec(
         &mut command_arguments,
         &launching_parameter,
-        &features,
+        features,
     )?;
+    let custom_jvm_args_idx = command_arguments.len();
 
     // Main class
-    command_arguments.push(
-        version_profile
+    let main_class = match &launching_parameter.main_class_override {
+        Some(main_class) => {
+            warn!("Main class is overridden with {}", main_class);
+            launcher_data.log(&format!("WARNING: Main class of the version profile is overridden with {}", main_class));
+            main_class.clone()
+        }
+        None => version_profile
             .main_class
             .as_ref()
             .ok_or_else(|| {
                 LauncherError::InvalidVersionProfile("Main class unspecified".to_string())
             })?
             .to_owned(),
-    );
+    };
+    command_arguments.push(main_class);
 
     // Game args
     version_profile
         .arguments
-        .add_game_args_to_vec(&mut command_arguments, &features)?;
+        .add_game_args_to_vec(&mut command_arguments, features)?;
+
+    // Newer versions are able to join a server on their own, older versions are told through --server and --port
+    if let Some(server) = launching_parameter.quick_play_server.as_ref().filter(|_| !feature_set.is_supported(QUICK_PLAY_MULTIPLAYER_FEATURE)) {
+        let (host, port) = split_server_address(server);
+        command_arguments.extend(["--server".to_string(), host.to_string(), "--port".to_string(), port.to_string()]);
+    }
+
+    if let Some((width, height)) = window_size.filter(|_| !feature_set.is_supported(CUSTOM_RESOLUTION_FEATURE)) {
+        command_arguments.extend(["--width".to_string(), width.to_string(), "--height".to_string(), height.to_string()]);
+    }
+    if launching_parameter.fullscreen {
+        command_arguments.push("--fullscreen".to_string());
+    }
+    // Older versions are told through --demo
+    if launching_parameter.demo && !feature_set.is_supported(DEMO_USER_FEATURE) {
+        command_arguments.push("--demo".to_string());
+    }
+
+    let quick_play_log = game_dir.join("quickPlay").join("log.json");
+    if feature_set.is_enabled(QUICK_PLAYS_SUPPORT_FEATURE) {
+        mkdir!(quick_play_log.parent().unwrap());
+    }
 
     let mut mapped: Vec<String> = Vec::with_capacity(command_arguments.len());
 
@@ -193,9 +329,14 @@ pub async fn launch<D: Send + Sync>(
                 "assets_root" => {
                     output.push_str(assets_folder.absolutize().unwrap().to_str().unwrap())
                 }
-                "assets_index_name" => output.push_str(&asset_index_location.id),
+                "assets_index_name" => output.push_str(&assets.index_name),
+                "game_assets" => {
+                    output.push_str(assets.game_assets.absolutize().unwrap().to_str().unwrap())
+                }
                 "auth_uuid" => output.push_str(&launching_parameter.auth_uuid),
                 "auth_access_token" => output.push_str(&launching_parameter.auth_access_token),
+                // Session of versions before 1.7.2
+                "auth_session" => output.push_str(&format!("token:{}:{}", launching_parameter.auth_access_token, launching_parameter.auth_uuid)),
                 "user_type" => output.push_str(&launching_parameter.user_type),
                 "version_type" => output.push_str(&version_profile.version_type),
                 "natives_directory" => {
@@ -207,6 +348,10 @@ pub async fn launch<D: Send + Sync>(
                 "user_properties" => output.push_str("{}"),
                 "clientid" => output.push_str(&launching_parameter.clientid),
                 "auth_xuid" => output.push_str(&launching_parameter.auth_xuid),
+                "resolution_width" => output.push_str(&window_size.map(|(width, _)| width.to_string()).unwrap_or_default()),
+                "resolution_height" => output.push_str(&window_size.map(|(_, height)| height.to_string()).unwrap_or_default()),
+                "quickPlayMultiplayer" => output.push_str(launching_parameter.quick_play_server.as_deref().unwrap_or_default()),
+                "quickPlayPath" => output.push_str(quick_play_log.absolutize().unwrap().to_str().unwrap()),
                 _ => return Err(LauncherError::UnknownTemplateParameter(param.to_owned()).into()),
             };
 
@@ -214,10 +359,35 @@ pub async fn launch<D: Send + Sync>(
         })?);
     }
 
+    // Custom JVM args come after the generated ones to be able to override them.
+    // They are passed as they are, without any template processing or splitting.
+    mapped.splice(custom_jvm_args_idx..custom_jvm_args_idx, launching_parameter.custom_jvm_args.iter().cloned());
+
     launcher_data.progress_update(ProgressUpdate::set_label("Launching..."));
     launcher_data.progress_update(ProgressUpdate::set_to_max());
 
-    let mut running_task = java_runtime.execute(mapped, &game_dir).await?;
+    if let Some(pre_launch_command) = &launching_parameter.pre_launch_command {
+        // Bound by the prelaunch timeout
+        run_hook("pre-launch", pre_launch_command, &game_dir, &manifest.build, None, Some(&launcher_data.cancellation), &|msg| launcher_data.log(msg)).await?;
+    }
+
+    let game_env = launching_parameter.game_env.iter()
+        .map(|(name, value)| (name.clone(), expand_env_vars(value)))
+        .collect::<HashMap<_, _>>();
+    if !game_env.is_empty() {
+        launcher_data.log(&format!("Setting environment variables {}", game_env.keys().cloned().collect::<Vec<_>>().join(", ")));
+    }
+
+    // Arguments only contain absolute paths, so they are not affected by a different working directory
+    let working_dir = launching_parameter.working_dir.as_ref().map_or_else(|| game_dir.clone(), PathBuf::from);
+    mkdir!(&working_dir);
+    if working_dir != game_dir {
+        launcher_data.log(&format!("Starting the game in {}", working_dir.display()));
+    }
+
+    let mut running_task = java_runtime.execute(mapped, &working_dir, &game_env).await?;
+    let pid = running_task.id();
+    launcher_data.log(&format!("Game is running with process id {}", pid.map_or("unknown".to_string(), |x| x.to_string())));
 
     launcher_data.progress_update(ProgressUpdate::set_label("Running..."));
 
@@ -226,30 +396,192 @@ pub async fn launch<D: Send + Sync>(
         launcher_data.hide_window();
     }
 
-    let terminator = launcher_data.terminator;
-    let data = launcher_data.data;
+    let exit = async move {
+        let terminator = launcher_data.cancellation;
+        let data = launcher_data.data;
+
+        // The output of the game is kept to be able to diagnose crashes
+        let mut game_log = GameLog::open(&game_dir.join("launcher_logs")).await.unwrap_or_else(|e| {
+            warn!("Failed to open game log: {:?}", e);
+            GameLog::default()
+        });
+
+        let started = SystemTime::now();
+        let result = java_runtime
+            .handle_io(
+                &mut running_task,
+                launcher_data.on_stdout,
+                launcher_data.on_stderr,
+                terminator,
+                &mut game_log,
+                &data,
+            )
+            .await
+            .map_err(|e| match e.downcast::<LauncherError>() {
+                // The crash report explains the crash better than the output, if the game has written one
+                Ok(LauncherError::GameCrashed { exit_code, report_excerpt }) => LauncherError::GameCrashed {
+                    exit_code,
+                    report_excerpt: read_crash_report(&game_dir.join("crash-reports"), started).unwrap_or(report_excerpt),
+                }.into(),
+                Ok(e) => e.into(),
+                Err(e) => e,
+            });
+        // The launcher might exit right away, which does not flush the log
+        game_log.sync().await;
+
+        // Cleanup runs even if the game crashed or was killed, a failing hook does not hide the result of the game
+        if let Some(post_exit_command) = &launching_parameter.post_exit_command {
+            let on_log = launcher_data.on_log;
+            if let Err(e) = run_hook("post-exit", post_exit_command, &game_dir, &manifest.build, Some(POST_EXIT_HOOK_TIMEOUT), None, &|msg| {
+                let _ = on_log(&data, msg);
+            }).await {
+                warn!("Post-exit hook failed: {:?}", e);
+            }
+        }
+        result?;
+
+        if !launching_parameter.keep_launcher_open {
+            // Hide launcher window
+            exit(0);
+        }
 
-    java_runtime
-        .handle_io(
-            &mut running_task,
-            launcher_data.on_stdout,
-            launcher_data.on_stderr,
-            terminator,
-            &data,
-        )
-        .await?;
+        Ok(())
+    };
 
-    if !launching_parameter.keep_launcher_open {
-        // Hide launcher window
-        exit(0);
+    Ok(LaunchResult {
+        pid,
+        java_path: java_bin,
+        class_path,
+        exit: exit.boxed(),
+    })
+}
+
+/// Replaces `${VAR}` with the variable of the launcher's environment, unknown variables are replaced with nothing
+fn expand_env_vars(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start + 2..].find('}') else {
+            break;
+        };
+
+        output.push_str(&rest[..start]);
+        output.push_str(&std::env::var(&rest[start + 2..start + 2 + end]).unwrap_or_default());
+        rest = &rest[start + 2 + end + 1..];
     }
+    output.push_str(rest);
+    output
+}
+
+/// Number of lines of the crash report attached to the error
+const CRASH_REPORT_LINES: usize = 60;
+
+/// Reads the beginning of the newest crash report which has been written after the game started
+fn read_crash_report(crash_reports: &Path, started: SystemTime) -> Option<String> {
+    let (_, path) = std::fs::read_dir(crash_reports).ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .filter(|(modified, _)| *modified >= started)
+        .max_by_key(|(modified, _)| *modified)?;
 
-    Ok(())
+    let content = std::fs::read_to_string(&path).ok()?;
+    let excerpt = content.lines()
+        .take(CRASH_REPORT_LINES)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Some(format!("{}\n\n{}", path.display(), excerpt))
+}
+
+/// How mods are placed into the mods directory of the game
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModLinkMode {
+    #[default]
+    Copy,
+    /// Requires the mod cache to be on the same file system as the game directory
+    Hardlink,
+    Symlink,
 }
 
+/// How game directories are separated, every branch has at least its own game directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameDirLayout {
+    /// `gameDir/{branch}`
+    #[default]
+    Branch,
+    /// `gameDir/{branch}-{mc_version}`, like the custom mods
+    Version,
+    /// `gameDir/{branch}-{build_id}`
+    Build,
+}
+
+impl GameDirLayout {
+    /// Name of the game directory of the build below the game directories
+    pub fn directory_name(&self, build: &Build) -> String {
+        match self {
+            GameDirLayout::Branch => build.branch.clone(),
+            GameDirLayout::Version => format!("{}-{}", build.branch, build.mc_version),
+            GameDirLayout::Build => format!("{}-{}", build.branch, build.build_id),
+        }
+    }
+}
+
+/// Largest accepted window width or height, in pixels
+const MAX_WINDOW_DIMENSION: u32 = 16384;
+
+/// Splits `host[:port]` or `[ipv6][:port]` into host and port, the port defaults to 25565.
+/// An IPv6 address without brackets is taken as host without a port.
+fn split_server_address(address: &str) -> (&str, u16) {
+    const DEFAULT_PORT: u16 = 25565;
+
+    if let Some((host, rest)) = address.strip_prefix('[').and_then(|x| x.split_once(']')) {
+        let port = rest.strip_prefix(':').and_then(|port| port.parse().ok());
+        return (host, port.unwrap_or(DEFAULT_PORT));
+    }
+
+    match address.split_once(':') {
+        Some((host, port)) if !host.is_empty() && !port.contains(':') => match port.parse() {
+            Ok(port) => (host, port),
+            Err(_) => (address, DEFAULT_PORT),
+        },
+        _ => (address, DEFAULT_PORT),
+    }
+}
+
+/// Maximum heap size used if neither a maximum nor a memory percentage is specified
+pub const DEFAULT_MAX_MEMORY_MB: u64 = 2048;
+
 pub struct LaunchingParameter {
     pub memory: i64,
+    pub min_memory_mb: Option<u64>,
+    pub max_memory_mb: Option<u64>,
+    pub custom_jvm_args: Vec<String>,
+    /// Set for the game on top of the environment of the launcher, `${VAR}` is expanded from the launcher's environment
+    pub game_env: HashMap<String, String>,
+    /// Launched instead of the main class of the version profile, for testing builds of the client
+    pub main_class_override: Option<String>,
+    pub library_conflicts: LibraryConflictResolution,
     pub custom_data_path: Option<String>,
+    pub bundled_mods_path: Option<String>,
+    pub custom_mod_cache_path: Option<String>,
+    /// Size limit of the mod cache, the least recently used mods are removed once the game has started
+    pub mod_cache_max_bytes: Option<u64>,
+    pub mod_link_mode: ModLinkMode,
+    /// Names of mods which are not loaded, even if they are enabled by default
+    pub mod_denylist: Vec<String>,
+    /// Adds the required dependencies of Modrinth mods which are missing from the launch
+    pub resolve_dependencies: bool,
+    /// Removes corrupt files from the mod cache before the mods are retrieved
+    pub repair_mod_cache: bool,
+    /// Only report what would be downloaded, without downloading or launching anything
+    pub dry_run: bool,
+    pub custom_game_dir_path: Option<String>,
+    pub game_dir_layout: GameDirLayout,
+    /// Current directory of the game process, the game directory is used if unset
+    pub working_dir: Option<String>,
     pub custom_java_path: Option<String>,
     pub auth_player_name: String,
     pub auth_uuid: String,
@@ -258,9 +590,114 @@ pub struct LaunchingParameter {
     pub clientid: String,
     pub user_type: String,
     pub keep_launcher_open: bool,
-    pub concurrent_downloads: i32,
+    pub offline: bool,
     pub client_account: Option<ClientAccount>,
     pub skip_advertisement: bool,
+    pub curseforge_api_key: Option<String>,
+    /// Server to join once the game has started, `host[:port]`
+    pub quick_play_server: Option<String>,
+    /// Initial size of the game window in pixels, width and height have to be set together
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: bool,
+    pub demo: bool,
+    /// Shell command run before the game is started, a failure aborts the launch
+    pub pre_launch_command: Option<String>,
+    /// Shell command run after the game has exited
+    pub post_exit_command: Option<String>,
+}
+
+/// Mods are denied by their name, ignoring its case
+pub(crate) fn is_mod_denied(mod_denylist: &[String], name: &str) -> bool {
+    mod_denylist.iter().any(|denied| denied.eq_ignore_ascii_case(name))
+}
+
+impl LaunchingParameter {
+    pub fn data_directory(&self) -> PathBuf {
+        self.custom_data_path
+            .clone()
+            .map(|x| x.into())
+            .unwrap_or_else(|| LAUNCHER_DIRECTORY.data_dir().to_path_buf())
+    }
+
+    /// Validated size of the game window, if one has been set
+    pub fn window_size(&self) -> Result<Option<(u32, u32)>> {
+        match (self.width, self.height) {
+            (None, None) => Ok(None),
+            (Some(width), Some(height)) if (1..=MAX_WINDOW_DIMENSION).contains(&width) && (1..=MAX_WINDOW_DIMENSION).contains(&height) => {
+                Ok(Some((width, height)))
+            }
+            (Some(width), Some(height)) => Err(LauncherError::Validation(format!(
+                "Window size {}x{} is invalid, width and height have to be between 1 and {}",
+                width, height, MAX_WINDOW_DIMENSION
+            )).into()),
+            _ => Err(LauncherError::Validation("Window width and height have to be set together".to_string()).into()),
+        }
+    }
+
+    /// Settings of the launch for the launcher log, leaving out the credentials
+    pub fn log_summary(&self) -> Vec<String> {
+        let (min_memory, max_memory) = self.heap_size();
+        vec![
+            format!("Player: {} ({}), offline: {}, demo: {}", self.auth_player_name, self.user_type, self.offline, self.demo),
+            format!("Memory: {:?} - {} MiB, JVM arguments: {:?}", min_memory, max_memory, self.custom_jvm_args),
+            format!("Java: {:?}, main class override: {:?}", self.custom_java_path, self.main_class_override),
+            format!("Data directory: {}, game directory: {:?} ({:?}), working directory: {:?}", self.data_directory().display(), self.custom_game_dir_path, self.game_dir_layout, self.working_dir),
+            format!("Mod cache: {:?}, link mode: {:?}, denylist: {:?}", self.custom_mod_cache_path, self.mod_link_mode, self.mod_denylist),
+            format!("Window: {:?}x{:?}, fullscreen: {}, quick play: {:?}", self.width, self.height, self.fullscreen, self.quick_play_server),
+            format!("Skip advertisement: {}, dry run: {}", self.skip_advertisement, self.dry_run),
+        ]
+    }
+
+    /// Mod names of the denylist are matched ignoring their case
+    pub fn is_mod_denied(&self, name: &str) -> bool {
+        is_mod_denied(&self.mod_denylist, name)
+    }
+
+    /// Initial and maximum heap size in megabytes.
+    /// The maximum falls back to the memory percentage and [DEFAULT_MAX_MEMORY_MB] if both are unset.
+    pub fn heap_size(&self) -> (Option<u64>, u64) {
+        let max = self.max_memory_mb
+            .or_else(|| (self.memory > 0).then_some(self.memory as u64))
+            .unwrap_or(DEFAULT_MAX_MEMORY_MB);
+
+        let total = total_memory_mb();
+        if max > total {
+            warn!("Maximum heap size of {} MB exceeds the total system memory of {} MB", max, total);
+        }
+
+        let min = self.min_memory_mb.map(|min| {
+            if min > max {
+                warn!("Minimum heap size of {} MB exceeds the maximum of {} MB, using the maximum instead", min, max);
+                max
+            } else {
+                min
+            }
+        });
+
+        (min, max)
+    }
+
+    /// Cache of downloaded mods, shared by all branches
+    pub fn mod_cache_directory(&self) -> PathBuf {
+        self.custom_mod_cache_path
+            .clone()
+            .map(|x| x.into())
+            .unwrap_or_else(|| self.data_directory().join("mod_cache"))
+    }
+
+    /// Parent directory of the game directories
+    pub fn game_directories(&self) -> PathBuf {
+        self.custom_game_dir_path
+            .clone()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| self.data_directory().join("gameDir"))
+    }
+
+    /// Game directory of the build, containing its mods, options and worlds
+    pub fn game_directory(&self, build: &Build) -> PathBuf {
+        self.game_directories().join(self.game_dir_layout.directory_name(build))
+    }
 }
 
 fn process_templates<F: Fn(&mut String, &str) -> Result<()>>(
@@ -310,3 +747,18 @@ fn process_templates<F: Fn(&mut String, &str) -> Result<()>>(
 
     Ok(output)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_server_addresses() {
+        assert_eq!(split_server_address("play.example.net"), ("play.example.net", 25565));
+        assert_eq!(split_server_address("play.example.net:25566"), ("play.example.net", 25566));
+        assert_eq!(split_server_address("[2001:db8::1]:25566"), ("2001:db8::1", 25566));
+        assert_eq!(split_server_address("[2001:db8::1]"), ("2001:db8::1", 25565));
+        assert_eq!(split_server_address("2001:db8::1"), ("2001:db8::1", 25565));
+        assert_eq!(split_server_address("::1"), ("::1", 25565));
+    }
+}
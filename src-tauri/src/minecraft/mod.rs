@@ -23,4 +23,8 @@ mod rule_interpreter;
 pub mod auth;
 pub mod prelauncher;
 pub mod progress;
+pub mod launch_cache;
+pub mod cache;
+pub mod dependencies;
+pub mod mod_metadata;
 pub mod java;
\ No newline at end of file
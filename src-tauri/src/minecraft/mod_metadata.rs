@@ -0,0 +1,401 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use async_zip::read::seek::ZipFileReader as SeekZipFileReader;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+use crate::app::api::{LaunchManifest, LoaderMod, LoaderSubsystem};
+use crate::minecraft::prelauncher::{managed_mods, select_mods};
+
+///
+/// Mod of a launch together with what its descriptor says about it
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedMod {
+    /// Name of the mod in the launch manifest, or the file name for mods added as dependency
+    pub name: String,
+    #[serde(rename = "fileName")]
+    pub file_name: String,
+    /// Whether the jar is in the mods directory, the metadata is only known if it is
+    pub installed: bool,
+    pub id: Option<String>,
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
+    pub version: Option<String>,
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+}
+
+/// Metadata read from the descriptor of a mod jar
+#[derive(Debug, Default)]
+struct ModMetadata {
+    id: Option<String>,
+    display_name: Option<String>,
+    version: Option<String>,
+    authors: Vec<String>,
+    description: Option<String>,
+}
+
+/// Reads the descriptors of the mods placed in the mods directory by the last launch.
+/// The mods are selected and named like the launch does, mods which have not been placed yet are listed without metadata.
+pub async fn resolve_mods(manifest: &LaunchManifest, additional_mods: &[LoaderMod], mod_denylist: &[String], mods_path: &Path) -> Result<Vec<ResolvedMod>> {
+    let selection = select_mods(manifest.mods.iter().chain(additional_mods), mod_denylist)?;
+    let mut entries = selection.mods.iter()
+        .zip(selection.file_names)
+        .map(|(current_mod, file_name)| (current_mod.name.clone(), file_name))
+        .collect::<Vec<_>>();
+
+    // Dependencies resolved during the launch are only known by the files they were placed as
+    let known = entries.iter().map(|(_, file_name)| file_name.clone()).collect::<HashSet<_>>();
+    for file_name in managed_mods(mods_path).await?.unwrap_or_default() {
+        if !known.contains(&file_name) {
+            let name = file_name.strip_suffix(".jar").unwrap_or(&file_name).to_string();
+            entries.push((name, file_name));
+        }
+    }
+
+    let mut resolved = Vec::with_capacity(entries.len());
+    for (name, file_name) in entries {
+        let path = mods_path.join(&file_name);
+        let installed = path.is_file();
+        let metadata = if installed {
+            read_mod_metadata(&path, &manifest.subsystem).await.unwrap_or_else(|e| {
+                warn!("Failed to read the descriptor of {}: {:?}", path.display(), e);
+                None
+            }).unwrap_or_default()
+        } else {
+            ModMetadata::default()
+        };
+
+        resolved.push(ResolvedMod {
+            name,
+            file_name,
+            installed,
+            id: metadata.id,
+            display_name: metadata.display_name,
+            version: metadata.version,
+            authors: metadata.authors,
+            description: metadata.description,
+        });
+    }
+    Ok(resolved)
+}
+
+/// Reads the first descriptor of the jar which is known to the loader, none if the jar has no such descriptor
+async fn read_mod_metadata(path: &Path, subsystem: &LoaderSubsystem) -> Result<Option<ModMetadata>> {
+    let mut reader = SeekZipFileReader::new(fs::File::open(path).await?).await
+        .with_context(|| format!("{} is not a valid jar", path.display()))?;
+
+    for descriptor in subsystem.mod_descriptors() {
+        let Some(content) = read_entry(&mut reader, descriptor).await? else {
+            continue;
+        };
+
+        let mut metadata = match *descriptor {
+            "fabric.mod.json" => parse_fabric_descriptor(&content)?,
+            "quilt.mod.json" => parse_quilt_descriptor(&content)?,
+            "mcmod.info" => parse_mcmod_info(&content)?,
+            _ => parse_mods_toml(&content)?,
+        };
+
+        // Forge mods commonly take their version from the jar manifest
+        if metadata.version.as_deref().is_some_and(|version| version.starts_with("${")) {
+            metadata.version = match read_entry(&mut reader, "META-INF/MANIFEST.MF").await? {
+                Some(manifest) => String::from_utf8_lossy(&manifest).lines()
+                    .find_map(|line| line.strip_prefix("Implementation-Version:"))
+                    .map(|version| version.trim().to_string()),
+                None => None,
+            };
+        }
+        return Ok(Some(metadata));
+    }
+    Ok(None)
+}
+
+async fn read_entry(reader: &mut SeekZipFileReader<fs::File>, name: &str) -> Result<Option<Vec<u8>>> {
+    let Some(index) = reader.file().entries().iter().position(|x| x.entry().filename() == name) else {
+        return Ok(None);
+    };
+
+    let mut content = Vec::new();
+    reader.entry(index).await?.read_to_end(&mut content).await?;
+    Ok(Some(content))
+}
+
+/// Authors are either plain names or objects with a name and contact information
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FabricPerson {
+    Name(String),
+    Person { name: String },
+}
+
+#[derive(Deserialize)]
+struct FabricDescriptor {
+    id: Option<String>,
+    name: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    authors: Vec<FabricPerson>,
+}
+
+fn parse_fabric_descriptor(content: &[u8]) -> Result<ModMetadata> {
+    let descriptor = serde_json::from_slice::<FabricDescriptor>(content)
+        .context("Failed to parse fabric.mod.json")?;
+    Ok(ModMetadata {
+        id: descriptor.id,
+        display_name: descriptor.name,
+        version: descriptor.version,
+        authors: descriptor.authors.into_iter().map(|author| match author {
+            FabricPerson::Name(name) | FabricPerson::Person { name } => name,
+        }).collect(),
+        description: descriptor.description,
+    })
+}
+
+#[derive(Deserialize)]
+struct QuiltDescriptor {
+    quilt_loader: QuiltLoader,
+}
+
+#[derive(Deserialize)]
+struct QuiltLoader {
+    id: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    metadata: QuiltMetadata,
+}
+
+#[derive(Deserialize, Default)]
+struct QuiltMetadata {
+    name: Option<String>,
+    description: Option<String>,
+    /// Names of the contributors mapped to their roles
+    #[serde(default)]
+    contributors: HashMap<String, serde_json::Value>,
+}
+
+fn parse_quilt_descriptor(content: &[u8]) -> Result<ModMetadata> {
+    let descriptor = serde_json::from_slice::<QuiltDescriptor>(content)
+        .context("Failed to parse quilt.mod.json")?;
+    let loader = descriptor.quilt_loader;
+    let mut authors = loader.metadata.contributors.into_keys().collect::<Vec<_>>();
+    authors.sort();
+
+    Ok(ModMetadata {
+        id: loader.id,
+        display_name: loader.metadata.name,
+        version: loader.version,
+        authors,
+        description: loader.metadata.description,
+    })
+}
+
+#[derive(Deserialize)]
+struct ModsToml {
+    #[serde(default)]
+    mods: Vec<ModsTomlEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModsTomlEntry {
+    #[serde(rename = "modId")]
+    mod_id: Option<String>,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    /// A single string, usually a comma separated list
+    authors: Option<String>,
+}
+
+fn parse_mods_toml(content: &[u8]) -> Result<ModMetadata> {
+    let descriptor = toml::from_str::<ModsToml>(&String::from_utf8_lossy(content))
+        .context("Failed to parse mods.toml")?;
+    // Jars can contain several mods, the first one is the mod the jar is named after
+    let Some(entry) = descriptor.mods.into_iter().next() else {
+        return Ok(ModMetadata::default());
+    };
+
+    Ok(ModMetadata {
+        id: entry.mod_id,
+        display_name: entry.display_name,
+        version: entry.version,
+        authors: split_authors(entry.authors.as_deref()),
+        description: entry.description.map(|description| description.trim().to_string()),
+    })
+}
+
+/// Legacy descriptor, either a list of mods or an object containing the list
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum McModInfo {
+    List(Vec<McModInfoEntry>),
+    Object {
+        #[serde(rename = "modList")]
+        mod_list: Vec<McModInfoEntry>,
+    },
+}
+
+#[derive(Deserialize)]
+struct McModInfoEntry {
+    modid: Option<String>,
+    name: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    #[serde(rename = "authorList", alias = "authors", default)]
+    author_list: Vec<String>,
+}
+
+fn parse_mcmod_info(content: &[u8]) -> Result<ModMetadata> {
+    let descriptor = serde_json::from_slice::<McModInfo>(content)
+        .context("Failed to parse mcmod.info")?;
+    let entries = match descriptor {
+        McModInfo::List(entries) | McModInfo::Object { mod_list: entries } => entries,
+    };
+    let Some(entry) = entries.into_iter().next() else {
+        return Ok(ModMetadata::default());
+    };
+
+    Ok(ModMetadata {
+        id: entry.modid,
+        display_name: entry.name,
+        version: entry.version,
+        authors: entry.author_list,
+        description: entry.description,
+    })
+}
+
+fn split_authors(authors: Option<&str>) -> Vec<String> {
+    authors.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|author| !author.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fabric_descriptor() {
+        let metadata = parse_fabric_descriptor(br#"{
+            "schemaVersion": 1,
+            "id": "sodium",
+            "version": "0.5.8+mc1.20.4",
+            "name": "Sodium",
+            "description": "Sodium is a free and open-source optimization mod for Minecraft.",
+            "authors": ["JellySquid", { "name": "IMS", "contact": { "homepage": "https://github.com/IMS212" } }]
+        }"#).unwrap();
+
+        assert_eq!(metadata.id.as_deref(), Some("sodium"));
+        assert_eq!(metadata.display_name.as_deref(), Some("Sodium"));
+        assert_eq!(metadata.version.as_deref(), Some("0.5.8+mc1.20.4"));
+        assert_eq!(metadata.authors, ["JellySquid", "IMS"]);
+        assert!(metadata.description.is_some());
+    }
+
+    #[test]
+    fn parses_quilt_descriptor() {
+        let metadata = parse_quilt_descriptor(br#"{
+            "schema_version": 1,
+            "quilt_loader": {
+                "group": "org.quiltmc",
+                "id": "qsl",
+                "version": "8.0.0",
+                "metadata": {
+                    "name": "Quilt Standard Libraries",
+                    "description": "A set of libraries to assist in making Quilt mods.",
+                    "contributors": { "Quilt Project": "Owner", "Glitch": "Developer" }
+                }
+            }
+        }"#).unwrap();
+
+        assert_eq!(metadata.id.as_deref(), Some("qsl"));
+        assert_eq!(metadata.display_name.as_deref(), Some("Quilt Standard Libraries"));
+        assert_eq!(metadata.version.as_deref(), Some("8.0.0"));
+        assert_eq!(metadata.authors, ["Glitch", "Quilt Project"]);
+    }
+
+    #[test]
+    fn parses_first_mod_of_mods_toml() {
+        let metadata = parse_mods_toml(br#"
+            modLoader="javafml"
+            loaderVersion="[47,)"
+            license="MIT"
+
+            [[mods]]
+            modId="jei"
+            version="${file.jarVersion}"
+            displayName="Just Enough Items"
+            authors="mezz, Nickid2018"
+            description='''
+            JEI is an item and recipe viewing mod for Minecraft.
+            '''
+
+            [[mods]]
+            modId="jei_addon"
+        "#).unwrap();
+
+        assert_eq!(metadata.id.as_deref(), Some("jei"));
+        assert_eq!(metadata.display_name.as_deref(), Some("Just Enough Items"));
+        // Taken from the jar manifest by the caller
+        assert_eq!(metadata.version.as_deref(), Some("${file.jarVersion}"));
+        assert_eq!(metadata.authors, ["mezz", "Nickid2018"]);
+        assert_eq!(metadata.description.as_deref(), Some("JEI is an item and recipe viewing mod for Minecraft."));
+    }
+
+    #[test]
+    fn parses_mcmod_info_list_and_object() {
+        let list = parse_mcmod_info(br#"[{
+            "modid": "optifine",
+            "name": "OptiFine",
+            "version": "HD_U_M5",
+            "authorList": ["sp614x"]
+        }]"#).unwrap();
+        assert_eq!(list.id.as_deref(), Some("optifine"));
+        assert_eq!(list.display_name.as_deref(), Some("OptiFine"));
+        assert_eq!(list.version.as_deref(), Some("HD_U_M5"));
+        assert_eq!(list.authors, ["sp614x"]);
+
+        let object = parse_mcmod_info(br#"{
+            "modListVersion": 2,
+            "modList": [{ "modid": "journeymap", "name": "JourneyMap", "authors": ["techbrew"] }]
+        }"#).unwrap();
+        assert_eq!(object.id.as_deref(), Some("journeymap"));
+        assert_eq!(object.authors, ["techbrew"]);
+    }
+
+    #[test]
+    fn empty_descriptors_have_no_metadata() {
+        assert!(parse_mods_toml(b"modLoader=\"javafml\"").unwrap().id.is_none());
+        assert!(parse_mcmod_info(b"[]").unwrap().id.is_none());
+        assert!(parse_fabric_descriptor(b"not json").is_err());
+    }
+}
@@ -17,52 +17,92 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
 
-use anyhow::{anyhow, bail, Context, Result};
-use async_zip::read::mem::ZipFileReader;
+use anyhow::{anyhow, Context, Result};
+use path_absolutize::Absolutize;
+use async_zip::read::seek::ZipFileReader as SeekZipFileReader;
+use futures::{stream, StreamExt, TryFutureExt, TryStreamExt};
 use tokio::fs;
-use tokio::io::AsyncReadExt;
+use tokio::sync::{Mutex, OnceCell};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncWriteExt};
 use tracing::*;
 
-use crate::app::api::{ApiEndpoints, LaunchManifest, LoaderMod, LoaderSubsystem, ModSource};
+use crate::app::api::{ApiEndpoints, CurseForgeApi, LaunchManifest, LoaderMod, LoaderSubsystem, ModSource, ModrinthApi, MAX_ARCHIVE_DEPTH};
+use crate::app::app_data::LauncherConfig;
 use crate::app::gui::ShareableWindow;
 use crate::app::webview::open_download_page;
 use crate::auth::ClientAccount;
 use crate::error::LauncherError;
+use crate::minecraft::cache::{self, ExpectedChecksum};
+use crate::minecraft::dependencies::{resolve_dependencies, DependencyGraph};
+use crate::minecraft::launch_cache::{LaunchCache, INHERITED_VERSION_PROFILES, LAUNCH_MANIFEST, RESOLVED_DEPENDENCIES, VERSION_PROFILE};
 use crate::minecraft::launcher;
-use crate::minecraft::launcher::{LauncherData, LaunchingParameter};
+use crate::minecraft::rule_interpreter;
+use crate::minecraft::launcher::{is_installer_downloaded, is_mod_denied, load_installer_version_profile, GameDirLayout, LaunchResult, LauncherData, LaunchingParameter, ModLinkMode};
 use crate::minecraft::progress::{
-    get_max, get_progress, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps,
+    get_max, AggregatingProgressReceiver, ProgressPlan, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps,
 };
-use crate::minecraft::version::{VersionManifest, VersionProfile};
-use crate::utils::{download_file, get_maven_artifact_path};
-use crate::LAUNCHER_DIRECTORY;
+use crate::minecraft::version::{AssetIndex, Library, VersionManifest, VersionProfile};
+use crate::utils::{disk_of, CacheValidators, download_file_from_mirrors_if_modified, download_file_hashed, download_file_resumable, ensure_secure_url, fetch_content_length, get_maven_artifact_path, local_file_path, sha1_of, sha1sum_async, sha256sum_async, DownloadOptions};
 
 ///
-/// Prelaunching client
+/// Prelaunching client, the game is only started if this is not a dry run
+///
+/// Everything until the game is started is bound by the prelaunch timeout of the config. When it is exceeded
+/// the prelaunch is dropped, which kills the processes it spawned, and [LauncherError::PrelaunchTimeout] is returned.
 ///
 pub(crate) async fn launch(
     launch_manifest: LaunchManifest,
     launching_parameter: LaunchingParameter,
+    config: &LauncherConfig,
     additional_mods: Vec<LoaderMod>,
     launcher_data: LauncherData<ShareableWindow>,
-) -> Result<()> {
-    launcher_data.log("Loading minecraft version manifest...");
+) -> Result<Option<LaunchResult>> {
+    let Some(limit) = config.prelaunch_timeout() else {
+        return prelaunch(launch_manifest, launching_parameter, config, additional_mods, launcher_data).await;
+    };
+
+    let cancellation = launcher_data.cancellation.clone();
+    match tokio::time::timeout(limit, prelaunch(launch_manifest, launching_parameter, config, additional_mods, launcher_data)).await {
+        Ok(result) => result,
+        Err(_) => {
+            error!("Prelaunch did not finish within {} seconds, aborting", limit.as_secs());
+            // Stops background work of the prelaunch which is not owned by the dropped future
+            cancellation.cancel();
+            Err(LauncherError::PrelaunchTimeout { secs: limit.as_secs() }.into())
+        }
+    }
+}
 
-    let mc_version_manifest = VersionManifest::fetch().await?;
+async fn prelaunch(
+    launch_manifest: LaunchManifest,
+    launching_parameter: LaunchingParameter,
+    config: &LauncherConfig,
+    mut additional_mods: Vec<LoaderMod>,
+    launcher_data: LauncherData<ShareableWindow>,
+) -> Result<Option<LaunchResult>> {
+    launcher_data.set_progress_plan(ProgressPlan::default());
 
+    // Delimits the launches in the launcher log
     let build = &launch_manifest.build;
-    let subsystem = &launch_manifest.subsystem;
+    info!("==================== Launching build {} ====================", build.build_id);
+    info!("Branch: {}, commit: {}, Minecraft {} ({})", build.branch, build.commit_id, build.mc_version, build.subsystem);
+    for line in launching_parameter.log_summary() {
+        info!("{}", line);
+    }
+    info!("{}", config.log_summary());
 
-    launcher_data.progress_update(ProgressUpdate::set_max());
-    launcher_data.progress_update(ProgressUpdate::SetProgress(0));
+    let data_directory = launching_parameter.data_directory();
+    let launch_cache = LaunchCache::new(&data_directory, launch_manifest.build.build_id);
 
-    let data_directory = launching_parameter
-        .custom_data_path
+    let bundled_mods_directory = launching_parameter
+        .bundled_mods_path
         .clone()
         .map(|x| x.into())
-        .unwrap_or_else(|| LAUNCHER_DIRECTORY.data_dir().to_path_buf());
+        .unwrap_or_else(|| data_directory.join("bundled_mods"));
 
     let retriever_account = if launching_parameter.skip_advertisement {
         &launching_parameter.client_account
@@ -70,28 +110,327 @@ pub(crate) async fn launch(
         &None
     };
 
+    launcher_data.check_cancelled()?;
+    launcher_data.log("Loading version profile...");
+    let (version_json, inherited_json) = if launching_parameter.offline {
+        let inherited_json = if launch_cache.contains(INHERITED_VERSION_PROFILES) {
+            launch_cache.read::<Vec<serde_json::Value>>(INHERITED_VERSION_PROFILES).await?
+        } else {
+            Vec::new()
+        };
+
+        (launch_cache.read::<serde_json::Value>(VERSION_PROFILE).await?, inherited_json)
+    } else {
+        let Some(version_profiles) = load_version_profiles(&data_directory, &launch_manifest, config, launching_parameter.dry_run, &launcher_data).await? else {
+            let message = format!("Dry run of build {}: the installer has to be downloaded before anything else can be checked", launch_manifest.build.build_id);
+            launcher_data.log(&message);
+            launcher_data.progress_update(ProgressUpdate::set_label(message));
+            launcher_data.progress_update(ProgressUpdate::set_to_max());
+            return Ok(None);
+        };
+        version_profiles
+    };
+
+    let mut version = VersionProfile::from_json_chain(&version_json, &inherited_json, launching_parameter.library_conflicts)?;
+    if matches!(launch_manifest.subsystem, LoaderSubsystem::Fabric { .. }) {
+        ensure_intermediary(&mut version, &launch_manifest.build.mc_version);
+    }
+
+    if launching_parameter.resolve_dependencies {
+        launcher_data.log("Resolving mod dependencies...");
+        let dependencies = if launching_parameter.offline {
+            if launch_cache.contains(RESOLVED_DEPENDENCIES) {
+                launch_cache.read::<DependencyGraph>(RESOLVED_DEPENDENCIES).await?
+            } else {
+                DependencyGraph::default()
+            }
+        } else {
+            let selection = select_mods(launch_manifest.mods.iter().chain(&additional_mods), &launching_parameter.mod_denylist)?;
+            let dependencies = resolve_dependencies(&selection.mods, &launch_manifest.build.mc_version, launch_manifest.subsystem.loader_name()).await?;
+
+            if !launching_parameter.dry_run {
+                if let Err(e) = launch_cache.write(RESOLVED_DEPENDENCIES, &dependencies).await {
+                    warn!("Failed to store resolved dependencies: {:?}", e);
+                }
+            }
+            dependencies
+        };
+
+        launcher_data.log(&format!("Resolved mod dependencies: {}", dependencies));
+        additional_mods.extend(dependencies.mods);
+    }
+
+    if launching_parameter.repair_mod_cache && !launching_parameter.dry_run {
+        launcher_data.log("Verifying mod cache...");
+        launcher_data.progress_update(ProgressUpdate::set_label("Verifying mod cache..."));
+
+        let mod_cache_path = launching_parameter.mod_cache_directory();
+        let checksums = launch_manifest.mods.iter()
+            .chain(&additional_mods)
+            .filter_map(|current_mod| Some((mod_cache_path.join(current_mod.source.get_path().ok()?), ExpectedChecksum {
+                sha1: current_mod.sha1.clone(),
+                sha256: current_mod.sha256.clone(),
+            })))
+            .collect::<HashMap<_, _>>();
+
+        let removed = cache::verify_and_repair(&mod_cache_path, &checksums).await?;
+        launcher_data.log(&format!("Removed {} corrupt file(s) from the mod cache", removed));
+    }
+
+    // Mods of the manifest, additional mods and dependencies are placed together, so their file names do not collide
+    let selection = select_mods(launch_manifest.mods.iter().chain(&additional_mods), &launching_parameter.mod_denylist)?;
+    for name in &selection.denied {
+        launcher_data.log(&format!("Skipping mod {}, it is on the mod denylist", name));
+    }
+
+    let summary = DownloadSummary::collect(&data_directory, &launching_parameter, &selection.mods, &version);
+
+    // Only report what would be downloaded, without writing anything
+    if launching_parameter.dry_run {
+        launcher_data.log(&format!("Dry run of build {}: {}", launch_manifest.build.build_id, summary));
+        launcher_data.progress_update(ProgressUpdate::set_label(format!("Dry run: {}", summary)));
+        launcher_data.progress_update(ProgressUpdate::set_to_max());
+        return Ok(None);
+    }
+
+    // Fail early instead of running out of space in the middle of a download
+    launcher_data.log("Checking disk space...");
+    check_disk_space(&summary.targets(&data_directory, &launching_parameter, &launch_manifest))?;
+    launcher_data.set_progress_plan(summary.progress_plan());
+
+    let game_dir = launching_parameter.game_directory(&launch_manifest.build);
+    if launching_parameter.game_dir_layout != GameDirLayout::Branch {
+        seed_game_directory(&launching_parameter.game_directories().join(&launch_manifest.build.branch), &game_dir).await?;
+    }
+
     // Copy retrieve and copy mods from manifest
-    clear_mods(&data_directory, &launch_manifest).await?;
-    retrieve_and_copy_mods(
+    let mods_path = game_dir.join("mods");
+    launcher_data.progress_update(ProgressUpdate::StepStarted(ProgressUpdateSteps::DownloadLiquidBounceMods));
+    let managed_mods = retrieve_and_copy_mods(
         &data_directory,
+        &bundled_mods_directory,
         &launch_manifest,
-        &launch_manifest.mods,
+        selection,
         retriever_account,
+        &launching_parameter,
+        config,
         &launcher_data,
-        
     )
     .await?;
-    retrieve_and_copy_mods(
+    // Mods of the previous launch are only removed now, unchanged ones have been kept in place
+    clear_mods(&mods_path, &managed_mods.iter().cloned().collect()).await?;
+    write_managed_mods(&mods_path, &managed_mods).await?;
+    launcher_data.progress_update(ProgressUpdate::StepFinished(ProgressUpdateSteps::DownloadLiquidBounceMods));
+
+    // The mods of this launch are kept, the cache is only cleaned up once the game has started
+    let mod_cache_gc = launching_parameter.mod_cache_max_bytes.map(|max_bytes| {
+        let mod_cache_path = launching_parameter.mod_cache_directory();
+        let keep = launch_manifest.mods.iter()
+            .chain(&additional_mods)
+            .filter_map(|current_mod| current_mod.source.get_path().ok())
+            .map(|path| mod_cache_path.join(path))
+            .collect::<HashSet<_>>();
+        (mod_cache_path, max_bytes, keep)
+    });
+
+    // The manifest is handed over to the launch, it is only cached once the game has started
+    let cached_manifest = match launching_parameter.offline {
+        false => Some(serde_json::to_value(&launch_manifest)?),
+        true => None,
+    };
+
+    launcher_data.log(&format!("Launching {}...", launch_manifest.build.commit_id));
+    let game = launcher::launch(
         &data_directory,
-        &launch_manifest,
-        &additional_mods,
-        retriever_account,
-        &launcher_data,
+        launch_manifest,
+        version,
+        launching_parameter,
+        config,
+        launcher_data
     )
     .await?;
 
-    launcher_data.log("Loading version profile...");
-    let manifest_url = match subsystem {
+    if let Some((mod_cache_path, max_bytes, keep)) = mod_cache_gc {
+        match cache::gc(&mod_cache_path, max_bytes, &keep).await {
+            Ok(freed) if freed > 0 => info!("Freed {} bytes of the mod cache", freed),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to clean up the mod cache: {:?}", e),
+        }
+    }
+
+    // Only a build which has started successfully is worth launching again while offline
+    if let Some(cached_manifest) = cached_manifest {
+        if let Err(e) = store_launch_cache(&launch_cache, &cached_manifest, &version_json, &inherited_json).await {
+            warn!("Failed to store manifests for offline launches: {:?}", e);
+        }
+    }
+    Ok(Some(game))
+}
+
+/// Additional space kept free for the JRE, natives and files written by the game itself
+const DISK_SPACE_MARGIN: u64 = 512 * 1024 * 1024;
+
+/// Mods, libraries and assets which are not present yet, with their expected sizes
+#[derive(Debug, Default)]
+struct DownloadSummary {
+    mods: usize,
+    mod_bytes: u64,
+    libraries: usize,
+    library_bytes: u64,
+    client_bytes: u64,
+    asset_bytes: u64,
+}
+
+impl DownloadSummary {
+
+    fn collect(
+        data_directory: &Path,
+        launching_parameter: &LaunchingParameter,
+        selected_mods: &[&LoaderMod],
+        version: &VersionProfile,
+    ) -> Self {
+        let mod_cache_path = launching_parameter.mod_cache_directory();
+        let mods = selected_mods.iter()
+            .filter(|current_mod| !matches!(current_mod.source, ModSource::Local { .. } | ModSource::LocalFile { .. }))
+            .filter(|current_mod| current_mod.source.get_path().is_ok_and(|path| !mod_cache_path.join(path).exists()))
+            .collect::<Vec<_>>();
+
+        let libraries_folder = data_directory.join("libraries");
+        let libraries = version.libraries.iter()
+            .filter(|library| rule_interpreter::check_condition(&library.rules, &HashSet::new()).unwrap_or(false))
+            .filter_map(|library| library.get_library_download().ok())
+            .filter(|download| !libraries_folder.join(&download.path).exists())
+            .collect::<Vec<_>>();
+
+        let client_jar = version.client_jar_path(&data_directory.join("versions"));
+        let client_bytes = match version.downloads.as_ref().and_then(|x| x.client.as_ref()) {
+            Some(download) if !client_jar.exists() => download.size,
+            _ => 0,
+        };
+
+        // The objects of an index downloaded before are checked one by one, without it all of them are counted
+        let assets_folder = data_directory.join("assets");
+        let asset_bytes = match &version.asset_index_location {
+            Some(location) => match read_asset_index(&assets_folder.join("indexes").join(format!("{}.json", location.id))) {
+                Some(index) => index.objects.values()
+                    .filter(|object| !object.is_present(&assets_folder.join("objects")))
+                    .map(|object| object.size)
+                    .sum(),
+                None => location.total_size,
+            },
+            None => 0,
+        };
+
+        Self {
+            mods: mods.len(),
+            mod_bytes: mods.iter().filter_map(|current_mod| current_mod.size).sum(),
+            libraries: libraries.len(),
+            library_bytes: libraries.iter().filter_map(|download| download.size).sum::<i64>().max(0) as u64,
+            client_bytes: client_bytes.max(0) as u64,
+            asset_bytes: asset_bytes.max(0) as u64,
+        }
+    }
+
+    fn total_bytes(&self) -> u64 {
+        self.mod_bytes + self.library_bytes + self.client_bytes + self.asset_bytes
+    }
+
+    /// Bytes written to every directory, the JRE is downloaded into the runtimes of the data directory
+    fn targets(&self, data_directory: &Path, launching_parameter: &LaunchingParameter, launch_manifest: &LaunchManifest) -> Vec<(PathBuf, u64)> {
+        let mut targets = vec![
+            (data_directory.to_path_buf(), self.library_bytes + self.client_bytes + self.asset_bytes),
+            (data_directory.join("runtimes"), 0),
+            (launching_parameter.mod_cache_directory(), self.mod_bytes),
+        ];
+        // Linked mods do not take up any additional space in the game directory
+        let copied_bytes = match launching_parameter.mod_link_mode {
+            ModLinkMode::Copy => self.mod_bytes,
+            ModLinkMode::Hardlink | ModLinkMode::Symlink => 0,
+        };
+        targets.push((launching_parameter.game_directory(&launch_manifest.build), copied_bytes));
+        targets
+    }
+
+    /// The size of the JRE is not known ahead, so it does not take part in the overall progress
+    fn progress_plan(&self) -> ProgressPlan {
+        ProgressPlan::new(&[
+            (ProgressUpdateSteps::DownloadLiquidBounceMods, self.mod_bytes),
+            (ProgressUpdateSteps::DownloadClientJar, self.client_bytes),
+            (ProgressUpdateSteps::DownloadLibraries, self.library_bytes),
+            (ProgressUpdateSteps::DownloadAssets, self.asset_bytes),
+        ])
+    }
+
+}
+
+fn read_asset_index(path: &Path) -> Option<AssetIndex> {
+    let content = std::fs::read(path).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+impl fmt::Display for DownloadSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} mods ({} bytes), {} libraries ({} bytes), client ({} bytes) and assets ({} bytes) to download, {} bytes in total",
+            self.mods, self.mod_bytes, self.libraries, self.library_bytes, self.client_bytes, self.asset_bytes, self.total_bytes()
+        )
+    }
+}
+
+/// Checks every disk the targets are located on, the directories might be spread across multiple disks.
+/// Nothing is created, a game directory which does not exist yet is seeded later on.
+fn check_disk_space(targets: &[(PathBuf, u64)]) -> Result<()> {
+    let mut disks = HashMap::<PathBuf, (u64, u64)>::new();
+    for (directory, needed) in targets {
+        let Some((mount_point, available)) = disk_of(directory) else {
+            warn!("Unable to determine available disk space of {}", directory.display());
+            continue;
+        };
+        disks.entry(mount_point).or_insert((0, available)).0 += needed;
+    }
+
+    for (mount_point, (needed, available)) in disks {
+        debug!("{} bytes required, {} bytes available on {}", needed, available, mount_point.display());
+        if needed + DISK_SPACE_MARGIN > available {
+            return Err(LauncherError::InsufficientDiskSpace { needed: needed + DISK_SPACE_MARGIN, available }.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Maven of the Fabric intermediary mappings
+const FABRIC_MAVEN: &str = "https://maven.fabricmc.net/";
+
+/// Fabric maps the game to the intermediary names at runtime, profiles which do not list the mappings would not start
+fn ensure_intermediary(version: &mut VersionProfile, mc_version: &str) {
+    if version.libraries.iter().any(|library| library.name.starts_with("net.fabricmc:intermediary:")) {
+        return;
+    }
+
+    info!("Adding missing intermediary mappings of {}", mc_version);
+    version.libraries.push(Library {
+        name: format!("net.fabricmc:intermediary:{}", mc_version),
+        downloads: None,
+        natives: None,
+        extract: None,
+        rules: Vec::new(),
+        url: Some(FABRIC_MAVEN.to_string()),
+    });
+}
+
+/// Loads the raw version profile of the build and all profiles it inherits from.
+/// A dry run does not store anything, it returns none if the profile is only part of an installer which has not been downloaded yet.
+async fn load_version_profiles(
+    data_directory: &Path,
+    launch_manifest: &LaunchManifest,
+    config: &LauncherConfig,
+    dry_run: bool,
+    launcher_data: &LauncherData<ShareableWindow>,
+) -> Result<Option<(serde_json::Value, Vec<serde_json::Value>)>> {
+    let build = &launch_manifest.build;
+    let manifest_url = match &launch_manifest.subsystem {
         LoaderSubsystem::Fabric { manifest, .. } => manifest
             .replace("{MINECRAFT_VERSION}", &build.mc_version)
             .replace(
@@ -99,80 +438,278 @@ pub(crate) async fn launch(
                 &build.subsystem_specific_data.fabric_loader_version,
             ),
         LoaderSubsystem::Forge { manifest, .. } => manifest.clone(),
+        LoaderSubsystem::Quilt { manifest, .. } => manifest
+            .replace("{MINECRAFT_VERSION}", &build.mc_version)
+            .replace(
+                "{QUILT_LOADER_VERSION}",
+                &build.subsystem_specific_data.quilt_loader_version,
+            ),
+        LoaderSubsystem::NeoForge { manifest, .. } => manifest
+            .replace("{MINECRAFT_VERSION}", &build.mc_version)
+            .replace(
+                "{NEOFORGE_VERSION}",
+                &build.subsystem_specific_data.neoforge_version,
+            ),
+    };
+    let version_json = match &launch_manifest.subsystem {
+        // NeoForge publishes its version profiles only inside the installer
+        LoaderSubsystem::NeoForge { .. } if manifest_url.trim().is_empty() => {
+            if dry_run && !is_installer_downloaded(data_directory, launch_manifest) {
+                return Ok(None);
+            }
+            launcher_data.log("Loading version profile of the NeoForge installer...");
+            load_installer_version_profile(data_directory, launch_manifest, config, launcher_data).await?
+                .context("NeoForge builds without a version manifest require an installer")?
+        }
+        _ => VersionProfile::load_json(&manifest_url).await?,
     };
-    let mut version = VersionProfile::load(&manifest_url).await?;
 
-    if let Some(inherited_version) = &version.inherits_from {
-        let url = mc_version_manifest
-            .versions
-            .iter()
-            .find(|x| &x.id == inherited_version)
-            .map(|x| &x.url)
-            .ok_or_else(|| {
-                LauncherError::InvalidVersionProfile(format!(
-                    "unable to find inherited version manifest {}",
-                    inherited_version
-                ))
-            })?;
+    let mc_version_manifest = OnceCell::new();
+    let inherited_json = VersionProfile::load_inherited_json(&version_json, |inherited_version| {
+        let mc_version_manifest = &mc_version_manifest;
+        async move {
+            // Profiles installed by loader installers are not part of the minecraft version manifest
+            let local_profile = data_directory
+                .join("versions")
+                .join(&inherited_version)
+                .join(format!("{}.json", inherited_version));
+            if local_profile.exists() {
+                launcher_data.log(&format!("Loading installed version {}...", inherited_version));
+                let content = fs::read(&local_profile).await?;
+                return serde_json::from_slice::<serde_json::Value>(&content)
+                    .with_context(|| format!("Failed to parse {}", local_profile.display()));
+            }
+
+            let mc_version_manifest = mc_version_manifest.get_or_try_init(|| async {
+                launcher_data.log("Loading minecraft version manifest...");
+                VersionManifest::fetch(dry_run).await
+            }).await?;
+
+            let url = &mc_version_manifest.get_by_id(&inherited_version)?.url;
+
+            debug!(
+                "Determined {}'s download url to be {}",
+                inherited_version, url
+            );
+            launcher_data.log(&format!("Downloading inherited version {}...", inherited_version));
+
+            VersionProfile::load_json(url).await
+        }
+    }).await?;
+
+    Ok(Some((version_json, inherited_json)))
+}
+
+/// Stores the manifests of this launch, so the build can be launched again while offline
+async fn store_launch_cache(
+    launch_cache: &LaunchCache,
+    launch_manifest: &serde_json::Value,
+    version_json: &serde_json::Value,
+    inherited_json: &[serde_json::Value],
+) -> Result<()> {
+    launch_cache.write(LAUNCH_MANIFEST, launch_manifest).await?;
+    launch_cache.write(VERSION_PROFILE, version_json).await?;
+    launch_cache.write(INHERITED_VERSION_PROFILES, &inherited_json).await?;
+
+    Ok(())
+}
 
-        debug!(
-            "Determined {}'s download url to be {}",
-            inherited_version, url
-        );
-        launcher_data.log(&format!("Downloading inherited version {}...", inherited_version));
+/// Files which are taken over from the game directory of the branch, when a separate game directory is created
+const SEEDED_GAME_FILES: [&str; 2] = ["options.txt", "servers.dat"];
 
-        let parent_version = VersionProfile::load(url).await?;
-        version.merge(parent_version)?;
+/// Creates a separate game directory, taking over the settings of the game directory of the branch.
+/// Worlds and mods stay in the game directory of the branch, which is used again when switching back.
+async fn seed_game_directory(branch_game_dir: &Path, game_dir: &Path) -> Result<()> {
+    if game_dir.exists() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(game_dir).await?;
+    for file_name in SEEDED_GAME_FILES {
+        let source = branch_game_dir.join(file_name);
+        if source.exists() {
+            info!("Taking over {} from {}", file_name, branch_game_dir.display());
+            fs::copy(&source, game_dir.join(file_name)).await?;
+        }
     }
 
-    launcher_data.log(&format!("Launching {}...", launch_manifest.build.commit_id));
-    launcher::launch(
-        &data_directory,
-        launch_manifest,
-        version,
-        launching_parameter,
-        launcher_data
-    )
-    .await?;
     Ok(())
 }
 
-pub(crate) async fn clear_mods(data: &Path, manifest: &LaunchManifest) -> Result<()> {
-    let mods_path = data
-        .join("gameDir")
-        .join(&manifest.build.branch)
-        .join("mods");
+/// Lists the mods placed by the launcher, everything else in the mods directory has been added by the user
+const MANAGED_MODS_FILE: &str = ".managed.json";
+/// Hex digits of the suffix which tells apart mods placed under the same name
+const MOD_FILE_SUFFIX_LENGTH: usize = 8;
 
+/// Removes the mods placed by the previous launch which are not placed by this one, while keeping mods added by the user
+pub(crate) async fn clear_mods(mods_path: &Path, keep: &HashSet<String>) -> Result<()> {
     if !mods_path.exists() {
         return Ok(());
     }
 
+    if let Some(managed_mods) = managed_mods(mods_path).await? {
+        for file_name in managed_mods {
+            // Never follow entries outside of the mods directory
+            if Path::new(&file_name).file_name() != Some(file_name.as_ref()) || keep.contains(&file_name) {
+                continue;
+            }
+
+            let _ = fs::remove_file(mods_path.join(file_name)).await;
+        }
+        return Ok(());
+    }
+
+    warn!("No list of managed mods found in {}, clearing all mods", mods_path.display());
+
     // Clear mods directory
-    let mut mods_read = fs::read_dir(&mods_path).await?;
+    let mut mods_read = fs::read_dir(mods_path).await?;
     while let Some(entry) = mods_read.next_entry().await? {
-        if entry.file_type().await?.is_file() {
+        if entry.file_type().await?.is_file() && !keep.contains(&*entry.file_name().to_string_lossy()) {
             let _ = fs::remove_file(entry.path()).await;
         }
     }
     Ok(())
 }
 
+/// File names of the mods placed by the previous launch, none if the launcher has not placed any yet
+pub(crate) async fn managed_mods(mods_path: &Path) -> Result<Option<Vec<String>>> {
+    let managed_mods_path = mods_path.join(MANAGED_MODS_FILE);
+    if !managed_mods_path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read(&managed_mods_path).await?;
+    let managed_mods = serde_json::from_slice::<Vec<String>>(&content)
+        .with_context(|| format!("Failed to parse {}", managed_mods_path.display()))?;
+    Ok(Some(managed_mods))
+}
+
+/// Removes the jars of the mods directory which are neither mods of the manifest nor on the allowlist.
+/// Returns the removed jars, or the jars which would be removed on a dry run.
+pub(crate) async fn prune_unmanaged_mods(manifest: &LaunchManifest, mods_path: &Path, allowlist: &[String], dry_run: bool) -> Result<Vec<PathBuf>> {
+    if !mods_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let referenced = manifest.mods.iter()
+        .flat_map(mod_file_name_variants)
+        .chain(allowlist.iter().cloned())
+        .collect::<HashSet<_>>();
+
+    let mut orphaned = Vec::new();
+    let mut mods_read = fs::read_dir(mods_path).await?;
+    while let Some(entry) = mods_read.next_entry().await? {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type().await?.is_file() && file_name.ends_with(".jar") && !referenced.contains(&file_name) {
+            orphaned.push(entry.path());
+        }
+    }
+
+    if !dry_run {
+        for path in &orphaned {
+            fs::remove_file(path).await
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+    }
+    Ok(orphaned)
+}
+
+/// Name of the file the mod is placed as in the mods directory, unless another mod has the same name
+pub(crate) fn mod_file_name(current_mod: &LoaderMod) -> String {
+    match &current_mod.source {
+        ModSource::Local { file_name } => sanitize_filename::sanitize(file_name),
+        _ => sanitize_filename::sanitize(format!("{}.jar", current_mod.name)),
+    }
+}
+
+/// File name of a mod whose name collides with another mod, the suffix is derived from its source
+/// so the same mod always ends up in the same file
+fn disambiguated_mod_file_name(current_mod: &LoaderMod) -> String {
+    let file_name = mod_file_name(current_mod);
+    let identity = serde_json::to_vec(&current_mod.source).unwrap_or_else(|_| current_mod.name.clone().into_bytes());
+    let suffix = &sha1_of(&identity)[..MOD_FILE_SUFFIX_LENGTH];
+
+    match file_name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}-{}.{}", stem, suffix, extension),
+        None => format!("{}-{}", file_name, suffix),
+    }
+}
+
+/// Both names the mod can be placed as, whether it is disambiguated depends on the other mods of the launch
+pub(crate) fn mod_file_name_variants(current_mod: &LoaderMod) -> [String; 2] {
+    [mod_file_name(current_mod), disambiguated_mod_file_name(current_mod)]
+}
+
+/// Mods of a launch which are placed in the mods directory, with the file names they are placed as
+pub struct ModSelection<'a> {
+    pub mods: Vec<&'a LoaderMod>,
+    pub file_names: Vec<String>,
+    /// Names of the enabled mods which are skipped because of the mod denylist
+    pub denied: Vec<&'a str>,
+}
+
+/// Selects the enabled mods which are not denied, fails if a required mod is denied.
+/// The file names are disambiguated across all selected mods, whichever source they come from.
+pub(crate) fn select_mods<'a>(mods: impl IntoIterator<Item = &'a LoaderMod>, mod_denylist: &[String]) -> Result<ModSelection<'a>> {
+    let mut selected = Vec::new();
+    let mut denied = Vec::new();
+    for current_mod in mods.into_iter().filter(|current_mod| current_mod.required || current_mod.enabled) {
+        if is_mod_denied(mod_denylist, &current_mod.name) {
+            if current_mod.required {
+                return Err(LauncherError::RequiredModDenied { name: current_mod.name.clone() }.into());
+            }
+            denied.push(current_mod.name.as_str());
+            continue;
+        }
+        selected.push(current_mod);
+    }
+
+    let file_names = mod_file_names(&selected);
+    Ok(ModSelection { mods: selected, file_names, denied })
+}
+
+/// File names of the mods in the mods directory, names are compared ignoring their case like on Windows
+pub(crate) fn mod_file_names(mods: &[&LoaderMod]) -> Vec<String> {
+    let file_names = mods.iter().map(|current_mod| mod_file_name(current_mod)).collect::<Vec<_>>();
+    let mut occurrences = HashMap::<String, usize>::new();
+    for file_name in &file_names {
+        *occurrences.entry(file_name.to_lowercase()).or_default() += 1;
+    }
+
+    mods.iter()
+        .zip(file_names)
+        .map(|(current_mod, file_name)| match occurrences[&file_name.to_lowercase()] {
+            1 => file_name,
+            _ => disambiguated_mod_file_name(current_mod),
+        })
+        .collect()
+}
+
+async fn write_managed_mods(mods_path: &Path, managed_mods: &[String]) -> Result<()> {
+    let managed_mods_path = mods_path.join(MANAGED_MODS_FILE);
+    fs::write(&managed_mods_path, serde_json::to_vec(managed_mods)?)
+        .await
+        .with_context(|| format!("Failed to write {}", managed_mods_path.display()))
+}
+
+/// Retrieves the selected mods and copies them into the mods directory, returns the names of the copied files
+#[allow(clippy::too_many_arguments)]
 pub async fn retrieve_and_copy_mods(
     data: &Path,
+    bundled_mods_path: &Path,
     manifest: &LaunchManifest,
-    mods: &Vec<LoaderMod>,
+    selection: ModSelection<'_>,
     client_account: &Option<ClientAccount>,
+    launching_parameter: &LaunchingParameter,
+    config: &LauncherConfig,
     launcher_data: &LauncherData<ShareableWindow>,
-) -> Result<()> {
-    let mod_cache_path = data.join("mod_cache");
+) -> Result<Vec<String>> {
+    let mod_cache_path = launching_parameter.mod_cache_directory();
     let mod_custom_path = data.join("custom_mods").join(format!(
         "{}-{}",
         manifest.build.branch, manifest.build.mc_version
     ));
-    let mods_path = data
-        .join("gameDir")
-        .join(&manifest.build.branch)
-        .join("mods");
+    let mods_path = launching_parameter.game_directory(&manifest.build).join("mods");
 
     fs::create_dir_all(&mod_cache_path).await.with_context(|| {
         format!(
@@ -192,156 +729,773 @@ pub async fn retrieve_and_copy_mods(
             )
         })?;
 
-    // Download and copy mods
-    let max = get_max(mods.len());
+    let ModSelection { mods, file_names, .. } = selection;
 
-    for (mod_idx, current_mod) in mods.iter().enumerate() {
-        // Skip mods that are not needed
-        if !current_mod.required && !current_mod.enabled {
-            continue;
-        }
+    // Weight every mod by its size, so large mods move the progress bar further than small ones
+    let sizes = estimate_mod_sizes(manifest, &mods, launching_parameter, config, launcher_data).await;
+    let mod_progress = &AggregatingProgressReceiver::new(launcher_data, ProgressUpdateSteps::DownloadLiquidBounceMods, mod_weights(&sizes));
+    let download_page_lock = Mutex::new(());
 
-        if let ModSource::Local { file_name } = &current_mod.source {
-            // Copy the mod.
-            fs::copy(mod_custom_path.join(file_name), mods_path.join(file_name))
-                .await
-                .with_context(|| format!("Failed to copy custom mod {}", current_mod.name))?;
-            launcher_data.progress_update(ProgressUpdate::set_label(format!(
-                "Copied custom mod {}",
-                current_mod.name
-            )));
-            continue;
+    for (current_mod, file_name) in mods.iter().zip(&file_names) {
+        debug!("Mod {} is placed as {}", current_mod.name, file_name);
+    }
+    launcher_data.log(&format!("Placing mods as {}", file_names.join(", ")));
+
+    let placed = stream::iter(mods.into_iter().zip(file_names.iter()).enumerate())
+        .map(|(task, (current_mod, file_name))| retrieve_and_copy_mod(
+            manifest,
+            current_mod,
+            file_name,
+            task,
+            mod_progress,
+            &mod_cache_path,
+            &mod_custom_path,
+            bundled_mods_path,
+            &mods_path,
+            client_account,
+            launching_parameter,
+            config,
+            &download_page_lock,
+            launcher_data,
+        ).inspect_ok(move |_| {
+            // Mods which have not been downloaded count as done as well
+            mod_progress.finish_task(task);
+        }))
+        .buffer_unordered(config.concurrent_mod_downloads.max(1) as usize)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let skipped = placed.iter().filter(|(_, copied)| !copied).count();
+    if skipped > 0 {
+        launcher_data.log(&format!("Skipped copying {} of {} mods, they are already up to date", skipped, placed.len()));
+    }
+
+    Ok(placed.into_iter().map(|(file_name, _)| file_name).collect())
+}
+
+/// Size of every mod from the manifest, or from the Content-Length of its repository if the manifest does not know it
+async fn estimate_mod_sizes(
+    manifest: &LaunchManifest,
+    mods: &[&LoaderMod],
+    launching_parameter: &LaunchingParameter,
+    config: &LauncherConfig,
+    launcher_data: &LauncherData<ShareableWindow>,
+) -> Vec<Option<u64>> {
+    let download_options = config.download_options(&launcher_data.cancellation);
+
+    stream::iter(mods)
+        .map(|current_mod| {
+            let download_options = &download_options;
+            async move {
+                if current_mod.size.is_some() || launching_parameter.offline {
+                    return current_mod.size;
+                }
+                // Only repository mods have a direct url which is known ahead
+                let ModSource::Repository { repository, artifact } = &current_mod.source else {
+                    return None;
+                };
+                let url = manifest.repositories.get(repository)?.urls().first()?.clone()
+                    + &get_maven_artifact_path(artifact).ok()?;
+                fetch_content_length(&url, download_options).await
+            }
+        })
+        .buffered(config.concurrent_mod_downloads.max(1) as usize)
+        .collect()
+        .await
+}
+
+/// Mods of unknown size weigh as much as an average known mod, or 100 units each if no size is known at all
+fn mod_weights(sizes: &[Option<u64>]) -> Vec<u64> {
+    let known = sizes.iter().flatten().copied().collect::<Vec<_>>();
+    let fallback = if known.is_empty() {
+        get_max(1)
+    } else {
+        (known.iter().sum::<u64>() / known.len() as u64).max(1)
+    };
+
+    sizes.iter().map(|size| size.unwrap_or(fallback).max(1)).collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn retrieve_and_copy_mod(
+    manifest: &LaunchManifest,
+    current_mod: &LoaderMod,
+    file_name: &str,
+    task: usize,
+    mod_progress: &AggregatingProgressReceiver<'_, LauncherData<ShareableWindow>>,
+    mod_cache_path: &Path,
+    mod_custom_path: &Path,
+    bundled_mods_path: &Path,
+    mods_path: &Path,
+    client_account: &Option<ClientAccount>,
+    launching_parameter: &LaunchingParameter,
+    config: &LauncherConfig,
+    download_page_lock: &Mutex<()>,
+    launcher_data: &LauncherData<ShareableWindow>,
+) -> Result<(String, bool)> {
+    if let ModSource::Local { file_name: source_name } = &current_mod.source {
+        // Copy the mod.
+        let copied = place_mod(&mod_custom_path.join(source_name), &mods_path.join(file_name), launching_parameter.mod_link_mode, None)
+            .await
+            .with_context(|| format!("Failed to copy custom mod {} from {}", current_mod.name, mod_custom_path.join(source_name).display()))?;
+        launcher_data.progress_update(ProgressUpdate::set_label(format!(
+            "Copied custom mod {}",
+            current_mod.name
+        )));
+        return Ok((file_name.to_string(), copied));
+    }
+
+    if let ModSource::LocalFile { path } = &current_mod.source {
+        let file_path = resolve_bundled_mod(bundled_mods_path, path).ok_or_else(|| {
+            LauncherError::InvalidLocalFile {
+                name: current_mod.name.clone(),
+                reason: format!("{} is outside of the bundled mods directory", path),
+            }
+        })?;
+        if !file_path.is_file() {
+            return Err(LauncherError::InvalidLocalFile {
+                name: current_mod.name.clone(),
+                reason: format!("{} does not exist", file_path.display()),
+            }
+            .into());
         }
 
+        // Copy the mod.
+        let copied = place_mod(&file_path, &mods_path.join(file_name), launching_parameter.mod_link_mode, None)
+            .await
+            .with_context(|| format!("Failed to copy bundled mod {} from {}", current_mod.name, file_path.display()))?;
         launcher_data.progress_update(ProgressUpdate::set_label(format!(
-            "Downloading recommended mod {}",
+            "Copied bundled mod {}",
             current_mod.name
         )));
+        return Ok((file_name.to_string(), copied));
+    }
 
-        let current_mod_path = mod_cache_path.join(current_mod.source.get_path()?);
-
-        // Do we need to download the mod?
-        if !current_mod_path.exists() {
-            // Make sure that the parent directory exists
-            fs::create_dir_all(&current_mod_path.parent().unwrap()).await?;
-
-            let contents = match &current_mod.source {
-                ModSource::SkipAd {
-                    artifact_name: _,
-                    url,
-                    extract,
-                } => {
-                    launcher_data.log(&format!(
-                        "Opening download page for mod {} on {}",
-                        current_mod.name, url
-                    ));
-                    launcher_data.progress_update(ProgressUpdate::set_label(format!(
-                        "Opening download page for mod {}",
-                        current_mod.name
-                    )));
-
-                    let direct_url = match client_account {
-                        Some(account) => {
-                            // PID is taken from the URL which is the last part of the URL
-                            // https://dl.liquidbounce.net/skip/c7kMT2q00U -> c7kMT2q00U
-                            let pid = url.split('/').last().context("Failed to get PID")?;
-                            let skip_file_resolve = ApiEndpoints::resolve_skip_file(account, pid).await?;
-                            debug!("{:?}", skip_file_resolve);
-                            
-                            // If the skip file resolve has a direct URL, use it - if not it means that the account is not allowed for direct downloads
-                            skip_file_resolve.direct_url.ok_or_else(|| anyhow!(
-                                "Failed to get direct URL for mod {}",
-                                current_mod.name
-                            ))?
-                        }
-                        None => open_download_page(url, launcher_data).await?,
-                    };
-
-                    launcher_data.log(&format!("Downloading mod {} from {}", current_mod.name, direct_url));
-                    launcher_data.progress_update(ProgressUpdate::set_label(format!(
-                        "Downloading mod {}",
-                        current_mod.name
-                    )));
-                    let retrieved_bytes = download_file(&direct_url, |a, b| {
-                        launcher_data.progress_update(ProgressUpdate::set_for_step(
-                            ProgressUpdateSteps::DownloadLiquidBounceMods,
-                            get_progress(mod_idx, a, b) as u64,
-                            max,
-                        ))
-                    })
-                    .await?;
-
-                    // Extract bytes
-                    if *extract {
-                        let reader = ZipFileReader::new(retrieved_bytes).await?;
-
-                        // Find .JAR file in archive and get index of it
-                        let index_of_file_to_extract = reader
-                            .file()
-                            .entries()
-                            .iter()
-                            .position(|x| x.entry().filename().ends_with(".jar"))
-                            .ok_or_else(|| {
-                                LauncherError::InvalidVersionProfile(
-                                    "There is no JAR in the downloaded archive".to_string(),
-                                )
-                            })?;
-                        let entry = reader.file().entries()[index_of_file_to_extract].entry();
-
-                        // Read file to extract
-                        let mut entry_reader = reader.entry(index_of_file_to_extract).await?;
-
-                        let mut output = Vec::with_capacity(entry.uncompressed_size() as usize);
-                        entry_reader.read_to_end(&mut output).await?;
-
-                        output
-                    } else {
-                        retrieved_bytes
+    launcher_data.check_cancelled()?;
+    let download_options = config.download_options(&launcher_data.cancellation);
+
+    launcher_data.progress_update(ProgressUpdate::set_label(format!(
+        "Downloading recommended mod {}",
+        current_mod.name
+    )));
+
+    let current_mod_path = mod_cache_path.join(current_mod.source.get_path()?);
+
+    // Re-validate cached mod, a half-written file of a killed process has to be downloaded again
+    if current_mod_path.exists() {
+        if let Err(e) = verify_cached_mod(current_mod, &current_mod_path).await {
+            warn!("Cached mod {} is invalid, downloading again: {:?}", current_mod.name, e);
+            fs::remove_file(&current_mod_path).await
+                .with_context(|| format!("Failed to remove invalid cached mod {}", current_mod.name))?;
+        }
+    }
+
+    if launching_parameter.offline && !current_mod_path.exists() {
+        return Err(LauncherError::NotAvailableOffline(format!("Mod {}", current_mod.name)).into());
+    }
+
+    // Repository mods might change under the same name, so the cached file is revalidated with the server
+    let cached_validators = match &current_mod.source {
+        ModSource::Repository { .. } if !launching_parameter.offline => CacheValidators::load(&current_mod_path).await,
+        _ => None,
+    };
+    let mut validators = None;
+
+    // Do we need to download the mod?
+    if !current_mod_path.exists() || cached_validators.is_some() {
+        // Make sure that the parent directory exists
+        fs::create_dir_all(&current_mod_path.parent().unwrap()).await?;
+
+        // The mod is streamed to disk and only moved into the cache once it has been verified
+        let download_path = with_extra_extension(&current_mod_path, "download");
+        let on_progress = |a, b| mod_progress.update_task(task, a, b);
+
+        // Checksum provided by the source itself
+        let source_checksum = match &current_mod.source {
+            ModSource::SkipAd {
+                artifact_name: _,
+                url,
+                extract,
+                extract_file,
+            } => {
+                launcher_data.log(&format!(
+                    "Opening download page for mod {} on {}",
+                    current_mod.name, url
+                ));
+                launcher_data.progress_update(ProgressUpdate::set_label(format!(
+                    "Opening download page for mod {}",
+                    current_mod.name
+                )));
+
+                let direct_url = match client_account {
+                    // Local archives are copied right away, there is no download page to open
+                    _ if local_file_path(url).is_some() => url.clone(),
+                    Some(account) => {
+                        // PID is taken from the URL which is the last part of the URL
+                        // https://dl.liquidbounce.net/skip/c7kMT2q00U -> c7kMT2q00U
+                        let pid = url.split('/').last().context("Failed to get PID")?;
+                        let skip_file_resolve = ApiEndpoints::resolve_skip_file(account, pid).await?;
+                        debug!("{:?}", skip_file_resolve);
+                        
+                        // If the skip file resolve has a direct URL, use it - if not it means that the account is not allowed for direct downloads
+                        skip_file_resolve.direct_url.ok_or_else(|| anyhow!(
+                            "Failed to get direct URL for mod {}",
+                            current_mod.name
+                        ))?
                     }
+                    None => {
+                        ensure_secure_url(url, config.allow_insecure_downloads)?;
+
+                        // There is only a single download view, so only one download page can be open at a time
+                        let _guard = download_page_lock.lock().await;
+                        open_download_page(url, launcher_data).await?
+                    }
+                };
+
+                launcher_data.log(&format!("Downloading mod {} from {}", current_mod.name, direct_url));
+                launcher_data.progress_update(ProgressUpdate::set_label(format!(
+                    "Downloading mod {}",
+                    current_mod.name
+                )));
+
+                // Download pages redirect through several hosts, the file itself has to come from a trusted one
+                let download_options = DownloadOptions {
+                    allowed_hosts: Some(manifest.allowed_download_hosts.clone()).filter(|hosts| !hosts.is_empty()),
+                    ..download_options.clone()
+                };
+
+                // Extract the JAR from the downloaded archive
+                let max_depth = extract.max_depth();
+                if max_depth > 0 {
+                    let archive_path = with_extra_extension(&current_mod_path, "archive");
+                    download_file_resumable(&direct_url, &archive_path, &download_options, launcher_data, on_progress)
+                        .await
+                        .with_context(|| format!("Failed to download mod {} (SkipAd) from {}", current_mod.name, direct_url))?;
+                    extract_jar(&archive_path, &download_path, extract_file.as_deref(), max_depth, &|file_name, extracted, size| {
+                        launcher_data.progress_update(ProgressUpdate::set_label(format!(
+                            "Extracting {} ({}%)",
+                            file_name, extracted.min(size) * 100 / size.max(1)
+                        )));
+                    }).await
+                        .with_context(|| format!("Failed to extract mod {} from the archive of {}", current_mod.name, direct_url))?;
+                    fs::remove_file(&archive_path).await?;
+
+                    // The archive might have contained a different file than the mod
+                    if let Err(e) = validate_mod_descriptor(&current_mod.name, &download_path, &manifest.subsystem).await {
+                        let _ = fs::remove_file(&download_path).await;
+                        return Err(e);
+                    }
+                } else {
+                    download_file_resumable(&direct_url, &download_path, &download_options, launcher_data, on_progress)
+                        .await
+                        .with_context(|| format!("Failed to download mod {} (SkipAd) from {}", current_mod.name, direct_url))?;
                 }
-                ModSource::Repository {
-                    repository,
-                    artifact,
-                } => {
-                    launcher_data.log(&format!("Downloading mod {} from {}", artifact, repository));
-                    let repository_url =
-                        manifest.repositories.get(repository).ok_or_else(|| {
-                            LauncherError::InvalidVersionProfile(format!(
-                                "There is no repository specified with the name {}",
-                                repository
-                            ))
-                        })?;
-
-                    let retrieved_bytes = download_file(
-                        &format!("{}{}", repository_url, get_maven_artifact_path(artifact)?),
-                        |a, b| {
-                            launcher_data.progress_update(ProgressUpdate::set_for_step(
-                                ProgressUpdateSteps::DownloadLiquidBounceMods,
-                                get_progress(mod_idx, a, b),
-                                max,
-                            ));
-                        },
-                    )
-                    .await?;
-
-                    retrieved_bytes
+
+                None
+            }
+            ModSource::Repository {
+                repository,
+                artifact,
+            } => {
+                launcher_data.log(&format!("Downloading mod {} from {}", artifact, repository));
+                let repository_urls =
+                    manifest.repositories.get(repository).ok_or_else(|| {
+                        LauncherError::UnknownRepository {
+                            name: repository.clone(),
+                            available: manifest.repositories.keys().cloned().collect(),
+                            artifact: artifact.clone(),
+                        }
+                    })?;
+                let artifact_path = get_maven_artifact_path(artifact)?;
+                let urls = repository_urls.urls()
+                    .iter()
+                    .map(|repository_url| format!("{}{}", repository_url, artifact_path))
+                    .collect::<Vec<_>>();
+
+                let downloaded = download_file_from_mirrors_if_modified(&urls, &download_path, cached_validators.as_ref(), &download_options, launcher_data, on_progress)
+                    .await
+                    .with_context(|| format!("Failed to download mod {} (repository {}) from {}", current_mod.name, repository, urls.join(", ")))?;
+                if downloaded.is_none() {
+                    debug!("Mod {} has not been modified, using the cached file", current_mod.name);
+                    return place_cached_mod(current_mod, &current_mod_path, mods_path, file_name, launching_parameter.mod_link_mode).await;
                 }
-                _ => bail!("unsupported mod source: {:?}", current_mod.source),
-            };
 
-            fs::write(&current_mod_path, contents)
-                .await
-                .with_context(|| format!("Failed to write mod {}", current_mod.name))?;
+                validators = downloaded;
+                None
+            }
+            ModSource::Modrinth {
+                project_id,
+                version_id,
+            } => {
+                launcher_data.log(&format!(
+                    "Downloading mod {} from Modrinth project {}",
+                    current_mod.name, project_id
+                ));
+                let version = ModrinthApi::version(version_id).await.with_context(|| {
+                    format!("Failed to request Modrinth version {} of mod {}", version_id, current_mod.name)
+                })?;
+                let file = version.primary_file().ok_or_else(|| {
+                    LauncherError::Validation(format!(
+                        "Modrinth version {} of mod {} has no files",
+                        version_id, current_mod.name
+                    ))
+                })?;
+
+                let actual = download_file_hashed(&file.url, &download_path, &download_options, launcher_data, on_progress)
+                    .await
+                    .with_context(|| format!("Failed to download mod {} (Modrinth) from {}", current_mod.name, file.url))?;
+
+                // Modrinth provides the hashes of its files
+                Some(SourceChecksum { expected: file.hashes.sha1.clone(), actual })
+            }
+            ModSource::CurseForge {
+                project_id,
+                file_id,
+            } => {
+                launcher_data.log(&format!(
+                    "Downloading mod {} from CurseForge project {}",
+                    current_mod.name, project_id
+                ));
+                let api_key = launching_parameter.curseforge_api_key.as_ref().ok_or_else(|| {
+                    anyhow!("A CurseForge API key is required to download mod {}", current_mod.name)
+                })?;
+                let file = CurseForgeApi::file(api_key, *project_id, *file_id).await.with_context(|| {
+                    format!("Failed to request CurseForge file {} of mod {}", file_id, current_mod.name)
+                })?;
+
+                // The author might have opted out of third-party downloads
+                let download_url = file.download_url.as_ref().ok_or_else(|| {
+                    LauncherError::ManualDownloadRequired {
+                        name: current_mod.name.clone(),
+                        url: format!("https://www.curseforge.com/projects/{}", project_id),
+                    }
+                })?;
+
+                let actual = download_file_hashed(download_url, &download_path, &download_options, launcher_data, on_progress)
+                    .await
+                    .with_context(|| format!("Failed to download mod {} (CurseForge) from {}", current_mod.name, download_url))?;
+
+                file.sha1().map(|expected| SourceChecksum { expected: expected.to_string(), actual })
+            }
+            _ => return Err(LauncherError::Validation(format!("unsupported mod source: {:?}", current_mod.source)).into()),
+        };
+
+        if let Err(e) = verify_downloaded_mod(current_mod, &download_path, source_checksum).await {
+            let _ = fs::remove_file(&download_path).await;
+            return Err(e);
         }
 
-        // Copy the mod.
-        fs::copy(
-            &current_mod_path,
-            mods_path.join(format!("{}.jar", current_mod.name)),
-        )
+        fs::rename(&download_path, &current_mod_path)
+            .await
+            .with_context(|| format!("Failed to write mod {}", current_mod.name))?;
+        if let Some(validators) = validators {
+            validators.store(&current_mod_path).await
+                .with_context(|| format!("Failed to store the cache validators of mod {}", current_mod.name))?;
+        }
+    }
+
+    place_cached_mod(current_mod, &current_mod_path, mods_path, file_name, launching_parameter.mod_link_mode).await
+}
+
+/// Places the mod from the mod cache into the mods directory, returns the file name and whether it has been copied
+async fn place_cached_mod(current_mod: &LoaderMod, current_mod_path: &Path, mods_path: &Path, file_name: &str, link_mode: ModLinkMode) -> Result<(String, bool)> {
+    let checksum = ExpectedChecksum { sha1: current_mod.sha1.clone(), sha256: current_mod.sha256.clone() };
+    let copied = place_mod(current_mod_path, &mods_path.join(file_name), link_mode, Some(&checksum))
         .await
-        .with_context(|| format!("Failed to copy mod {}", current_mod.name))?;
+        .with_context(|| format!("Failed to copy mod {} from {}", current_mod.name, current_mod_path.display()))?;
+
+    Ok((file_name.to_string(), copied))
+}
+
+/// Places the mod into the mods directory, falls back to copying if the mod can not be linked.
+/// Returns false if the mod has already been in place.
+async fn place_mod(source: &Path, target: &Path, link_mode: ModLinkMode, checksum: Option<&ExpectedChecksum>) -> Result<bool> {
+    if is_mod_in_place(source, target, checksum).await {
+        debug!("{} is already up to date", target.display());
+        return Ok(false);
+    }
+
+    // Writing to an existing link would modify the linked file instead
+    if fs::symlink_metadata(target).await.is_ok() {
+        fs::remove_file(target).await?;
+    }
+
+    let linked = match link_mode {
+        ModLinkMode::Copy => None,
+        ModLinkMode::Hardlink => Some(fs::hard_link(source, target).await),
+        ModLinkMode::Symlink => Some(symlink_file(&source.absolutize()?, target).await),
+    };
+
+    match linked {
+        Some(Ok(())) => return Ok(true),
+        // Linking across devices or without the privilege to create symlinks on Windows fails
+        Some(Err(e)) => debug!("Unable to link {} to {}, copying instead: {:?}", source.display(), target.display(), e),
+        None => {}
+    }
+
+    fs::copy(source, target).await?;
+    Ok(true)
+}
+
+/// Whether the file in the mods directory matches the source, by its checksums if they are known,
+/// otherwise a copy is newer than its source and a hard link shares its modification time
+async fn is_mod_in_place(source: &Path, target: &Path, checksum: Option<&ExpectedChecksum>) -> bool {
+    let (Ok(source_metadata), Ok(target_metadata)) = (fs::metadata(source).await, fs::symlink_metadata(target).await) else {
+        return false;
+    };
+
+    // A link of a previous launch might point to an outdated file
+    if target_metadata.is_symlink() {
+        return matches!((fs::canonicalize(source).await, fs::canonicalize(target).await), (Ok(a), Ok(b)) if a == b);
+    }
+    if source_metadata.len() != target_metadata.len() {
+        return false;
     }
 
+    match checksum {
+        Some(ExpectedChecksum { sha256: Some(expected), .. }) => {
+            sha256sum_async(target).await.is_ok_and(|actual| actual.eq_ignore_ascii_case(expected))
+        }
+        Some(ExpectedChecksum { sha1: Some(expected), .. }) => {
+            sha1sum_async(target).await.is_ok_and(|actual| actual.eq_ignore_ascii_case(expected))
+        }
+        _ => matches!((source_metadata.modified(), target_metadata.modified()), (Ok(source), Ok(target)) if target >= source),
+    }
+}
+
+#[cfg(unix)]
+async fn symlink_file(source: &Path, target: &Path) -> std::io::Result<()> {
+    fs::symlink(source, target).await
+}
+
+#[cfg(windows)]
+async fn symlink_file(source: &Path, target: &Path) -> std::io::Result<()> {
+    fs::symlink_file(source, target).await
+}
+
+/// Resolves the path of a bundled mod, returns None if the path would escape the base directory
+fn resolve_bundled_mod(base: &Path, path: &str) -> Option<PathBuf> {
+    let relative = Path::new(path);
+    let is_contained = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+    if !is_contained {
+        return None;
+    }
+    Some(base.join(relative))
+}
+
+/// SHA-1 provided by the source of a mod and the one computed while downloading it
+struct SourceChecksum {
+    expected: String,
+    actual: String,
+}
+
+/// Verifies a downloaded mod against the checksum of its source, which has been computed while downloading, and the manifest
+async fn verify_downloaded_mod(current_mod: &LoaderMod, path: &PathBuf, source_checksum: Option<SourceChecksum>) -> Result<()> {
+    if let Some(SourceChecksum { expected, actual }) = source_checksum {
+        compare_checksum(&current_mod.name, &expected, actual)?;
+    }
+    verify_cached_mod(current_mod, path).await
+}
+
+/// Verifies a mod in the mod cache against the checksums specified by the manifest
+async fn verify_cached_mod(current_mod: &LoaderMod, path: &PathBuf) -> Result<()> {
+    // Stubs of crashed runs are common enough to deserve a clear error
+    if fs::metadata(path).await?.len() == 0 {
+        return Err(LauncherError::Validation(format!("{} is empty", current_mod.name)).into());
+    }
+
+    if let Some(expected) = &current_mod.sha256 {
+        compare_checksum(&current_mod.name, expected, sha256sum_async(path).await?)?;
+    }
+    if let Some(expected) = &current_mod.sha1 {
+        compare_checksum(&current_mod.name, expected, sha1sum_async(path).await?)?;
+    }
+    validate_jar(&current_mod.name, fs::File::open(path).await?).await
+}
+
+/// Upper limit of bytes extracted from a downloaded archive, including nested archives
+const MAX_EXTRACTED_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Extracted bytes between two progress reports of an entry
+const EXTRACT_PROGRESS_INTERVAL: u64 = 1024 * 1024;
+
+/// Reports the name of the extracted entry, the bytes extracted so far and the uncompressed size of the entry
+type ExtractProgress<'a> = &'a (dyn Fn(&str, u64, u64) + Sync);
+
+/// Extracts the JAR of the archive to the output path, descending into archives which only contain
+/// another archive up to the maximum depth
+async fn extract_jar(archive_path: &Path, output_path: &Path, expected_file: Option<&str>, max_depth: u32, on_progress: ExtractProgress<'_>) -> Result<()> {
+    let max_depth = max_depth.min(MAX_ARCHIVE_DEPTH);
+    let mut nested_archives = Vec::new();
+    let result = extract_nested_jar(archive_path, output_path, expected_file, max_depth, &mut nested_archives, on_progress).await;
+
+    for nested_archive in nested_archives {
+        let _ = fs::remove_file(nested_archive).await;
+    }
+    result
+}
+
+async fn extract_nested_jar(
+    archive_path: &Path,
+    output_path: &Path,
+    expected_file: Option<&str>,
+    max_depth: u32,
+    nested_archives: &mut Vec<PathBuf>,
+    on_progress: ExtractProgress<'_>,
+) -> Result<()> {
+    let mut current_archive = archive_path.to_path_buf();
+    let mut remaining_bytes = MAX_EXTRACTED_BYTES;
+
+    for depth in 1..=max_depth {
+        let mut reader = SeekZipFileReader::new(fs::File::open(&current_archive).await?).await?;
+
+        let filenames = reader
+            .file()
+            .entries()
+            .iter()
+            .map(|x| x.entry().filename())
+            .collect::<Vec<_>>();
+
+        if let Some(index) = select_jar(&filenames, expected_file) {
+            extract_entry(&mut reader, index, output_path, remaining_bytes, on_progress).await?;
+            return Ok(());
+        }
+
+        // Descend if the archive only wraps another archive
+        let files = filenames.iter()
+            .enumerate()
+            .filter(|(_, filename)| !filename.ends_with('/'))
+            .collect::<Vec<_>>();
+        let nested_index = match files.as_slice() {
+            [(index, filename)] if filename.ends_with(".zip") => *index,
+            _ => return Err(LauncherError::JarNotFoundInArchive {
+                expected: expected_file.map(|x| x.to_string()),
+                contents: filenames.iter().map(|x| x.to_string()).collect(),
+            }.into()),
+        };
+
+        if depth == max_depth {
+            return Err(LauncherError::ArchiveTooDeep { max_depth }.into());
+        }
+
+        let nested_archive = with_extra_extension(archive_path, &depth.to_string());
+        nested_archives.push(nested_archive.clone());
+        remaining_bytes -= extract_entry(&mut reader, nested_index, &nested_archive, remaining_bytes, on_progress).await?;
+        current_archive = nested_archive;
+    }
+
+    Err(LauncherError::ArchiveTooDeep { max_depth }.into())
+}
+
+/// Extracts a single entry, fails if it is larger than the remaining bytes. Returns the size of the entry.
+async fn extract_entry<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut SeekZipFileReader<R>,
+    index: usize,
+    output_path: &Path,
+    remaining_bytes: u64,
+    on_progress: ExtractProgress<'_>,
+) -> Result<u64> {
+    let entry = reader.file().entries()[index].entry();
+    let (file_name, size) = (entry.filename().to_string(), entry.uncompressed_size());
+
+    let entry_reader = reader.entry(index).await?;
+    let mut output = fs::File::create(output_path).await?;
+    // Read one byte more than allowed to detect exceeding the limit
+    let mut limited_reader = entry_reader.take(remaining_bytes + 1);
+    let mut buffer = vec![0; 64 * 1024];
+    let mut written = 0;
+    let mut reported = 0;
+
+    on_progress(&file_name, 0, size);
+    loop {
+        let read = limited_reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        output.write_all(&buffer[..read]).await?;
+        written += read as u64;
+
+        if written - reported >= EXTRACT_PROGRESS_INTERVAL {
+            on_progress(&file_name, written, size);
+            reported = written;
+        }
+    }
+    output.flush().await?;
+    on_progress(&file_name, written, size);
+
+    if written > remaining_bytes {
+        return Err(LauncherError::ArchiveTooLarge { limit: MAX_EXTRACTED_BYTES }.into());
+    }
+    Ok(written)
+}
+
+/// Selects the jar of the mod from the archive entries. Without an expected file name the archive
+/// has to contain exactly one jar, not counting sources and javadoc jars
+fn select_jar(filenames: &[&str], expected_file: Option<&str>) -> Option<usize> {
+    if let Some(expected_file) = expected_file {
+        return filenames.iter().position(|filename| {
+            *filename == expected_file || filename.rsplit('/').next() == Some(expected_file)
+        });
+    }
+
+    let mut jars = filenames.iter().enumerate().filter(|(_, filename)| {
+        filename.ends_with(".jar")
+            && !filename.ends_with("-sources.jar")
+            && !filename.ends_with("-javadoc.jar")
+    });
+
+    match (jars.next(), jars.next()) {
+        (Some((index, _)), None) => Some(index),
+        _ => None,
+    }
+}
+
+/// Appends an extension to the path, e.g. `mod.jar` to `mod.jar.download`
+fn with_extra_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Makes sure the mod is an actual jar, servers might respond with an error page instead
+async fn validate_jar<R: AsyncRead + AsyncSeek + Unpin>(name: &str, reader: R) -> Result<()> {
+    let invalid_jar = || LauncherError::InvalidJar { name: name.to_string() };
+
+    let reader = SeekZipFileReader::new(reader).await.map_err(|_| invalid_jar())?;
+    let is_jar = reader.file().entries().iter().any(|x| {
+        let filename = x.entry().filename();
+        filename.starts_with("META-INF/")
+            || filename.ends_with(".class")
+            || filename == "fabric.mod.json"
+            || filename == "quilt.mod.json"
+    });
+
+    if !is_jar {
+        return Err(invalid_jar().into());
+    }
     Ok(())
 }
+
+/// Makes sure the jar is a mod of the loader of the build
+async fn validate_mod_descriptor(name: &str, path: &Path, subsystem: &LoaderSubsystem) -> Result<()> {
+    let reader = SeekZipFileReader::new(fs::File::open(path).await?).await
+        .map_err(|_| LauncherError::InvalidJar { name: name.to_string() })?;
+    let descriptors = subsystem.mod_descriptors();
+    let has_descriptor = reader.file().entries().iter()
+        .any(|x| descriptors.contains(&x.entry().filename()));
+
+    if !has_descriptor {
+        return Err(LauncherError::InvalidJar { name: name.to_string() }.into());
+    }
+    Ok(())
+}
+
+fn compare_checksum(name: &str, expected: &str, actual: String) -> Result<()> {
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(LauncherError::ChecksumMismatch {
+            expected: expected.to_string(),
+            actual,
+            name: name.to_string(),
+        }.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_zip::write::ZipFileWriter;
+    use async_zip::{Compression, ZipEntryBuilder};
+
+    use crate::utils::test_support::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn selects_main_jar_over_sources() {
+        let filenames = ["LICENSE", "mod-1.0.0-sources.jar", "mod-1.0.0.jar", "mod-1.0.0-javadoc.jar"];
+        assert_eq!(select_jar(&filenames, None), Some(2));
+    }
+
+    #[test]
+    fn selects_expected_jar() {
+        let filenames = ["mods/other-2.0.jar", "mods/mod-1.0.0.jar"];
+        assert_eq!(select_jar(&filenames, Some("mod-1.0.0.jar")), Some(1));
+        assert_eq!(select_jar(&filenames, Some("missing.jar")), None);
+    }
+
+    #[test]
+    fn requires_single_jar() {
+        assert_eq!(select_jar(&["mod-a.jar", "mod-b.jar"], None), None);
+        assert_eq!(select_jar(&["mod-1.0.0-sources.jar", "README.md"], None), None);
+    }
+
+    async fn write_archive(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut writer = ZipFileWriter::new(Vec::new());
+        for (filename, data) in entries {
+            writer.write_entry_whole(ZipEntryBuilder::new(filename.to_string(), Compression::Stored), data).await.unwrap();
+        }
+        fs::write(path, writer.close().await.unwrap()).await.unwrap();
+    }
+
+    fn loader_mod(name: &str, required: bool, source: ModSource) -> LoaderMod {
+        LoaderMod { required, enabled: true, name: name.to_string(), source, sha1: None, sha256: None, size: None }
+    }
+
+    #[test]
+    fn disambiguates_file_names_across_manifest_and_dependency_mods() {
+        let manifest_mods = [
+            loader_mod("sodium", true, ModSource::Repository { repository: "liquidbounce".to_string(), artifact: "net.caffeinemc:sodium:0.5.8".to_string() }),
+            loader_mod("modmenu", false, ModSource::Repository { repository: "liquidbounce".to_string(), artifact: "com.terraformersmc:modmenu:9.0.0".to_string() }),
+        ];
+        let dependencies = [
+            loader_mod("Sodium", true, ModSource::Modrinth { project_id: "AANobbMI".to_string(), version_id: "b4hTi3mo".to_string() }),
+        ];
+
+        let selection = select_mods(manifest_mods.iter().chain(&dependencies), &["ModMenu".to_string()]).unwrap();
+
+        assert_eq!(selection.denied, ["modmenu"]);
+        assert_eq!(selection.mods.len(), 2);
+        let [manifest_name, dependency_name] = &selection.file_names[..] else {
+            panic!("{:?}", selection.file_names);
+        };
+        assert!(!manifest_name.eq_ignore_ascii_case(dependency_name), "{:?}", selection.file_names);
+        assert!(manifest_name.starts_with("sodium-") && dependency_name.starts_with("Sodium-"));
+    }
+
+    #[test]
+    fn denied_required_mod_fails_selection() {
+        let mods = [loader_mod("sodium", true, ModSource::Local { file_name: "sodium.jar".to_string() })];
+
+        let error = select_mods(&mods, &["sodium".to_string()]).err().unwrap();
+        assert!(matches!(error.downcast_ref::<LauncherError>(), Some(LauncherError::RequiredModDenied { .. })));
+    }
+
+    fn fabric() -> LoaderSubsystem {
+        LoaderSubsystem::Fabric { manifest: String::new(), mod_directory: String::new() }
+    }
+
+    fn is_invalid_jar(result: Result<()>) -> bool {
+        matches!(result.map_err(|e| e.downcast::<LauncherError>()), Err(Ok(LauncherError::InvalidJar { .. })))
+    }
+
+    #[tokio::test]
+    async fn rejects_bogus_extracted_archives() {
+        let directory = TempDir::new("bogus-archive");
+        let path = directory.join("bogus.jar");
+
+        // Download pages are served instead of the archive if the link has expired
+        fs::write(&path, b"<!DOCTYPE html><html><body>Your download will start shortly</body></html>").await.unwrap();
+        assert!(is_invalid_jar(validate_mod_descriptor("bogus", &path, &fabric()).await));
+
+        // Archives might contain the mod of another loader
+        write_archive(&path, &[("META-INF/mods.toml", b"modLoader=\"javafml\""), ("Mod.class", b"")]).await;
+        assert!(is_invalid_jar(validate_mod_descriptor("forge-mod", &path, &fabric()).await));
+
+        write_archive(&path, &[("fabric.mod.json", b"{}"), ("Mod.class", b"")]).await;
+        assert!(validate_mod_descriptor("fabric-mod", &path, &fabric()).await.is_ok());
+    }
+}
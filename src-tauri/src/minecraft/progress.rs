@@ -17,39 +17,89 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
  
-use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug)]
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProgressUpdateSteps {
     DownloadLiquidBounceMods,
-    DownloadJRE,
+    DownloadJava,
     DownloadClientJar,
     DownloadLibraries,
     DownloadAssets,
 }
 
+/// An unknown maximum (0) does not advance the progress of the current entry
 pub fn get_progress(idx: usize, curr: u64, max: u64) -> u64 {
-    idx as u64 * 100 + (curr * 100 / max.max(1))
+    idx as u64 * 100 + (curr.min(max) * 100 / max.max(1))
 }
 
 pub fn get_max(len: usize) -> u64 {
     len as u64 * 100
 }
 
+/// Like [get_progress], but every entry is worth its weight instead of 100 units, see [get_weighted_max]
+pub fn get_weighted_progress(weights: &[u64], idx: usize, curr: u64, max: u64) -> u64 {
+    let offset = weights.iter().take(idx).sum::<u64>();
+    let weight = weights.get(idx).copied().unwrap_or_default();
+
+    offset + (curr.min(max) as u128 * weight as u128 / max.max(1) as u128) as u64
+}
+
+pub fn get_weighted_max(weights: &[u64]) -> u64 {
+    weights.iter().sum()
+}
+
 impl ProgressUpdateSteps {
+    const ALL: [ProgressUpdateSteps; 5] = [
+        ProgressUpdateSteps::DownloadLiquidBounceMods,
+        ProgressUpdateSteps::DownloadJava,
+        ProgressUpdateSteps::DownloadClientJar,
+        ProgressUpdateSteps::DownloadLibraries,
+        ProgressUpdateSteps::DownloadAssets,
+    ];
+
     fn len() -> usize {
-        5
+        Self::ALL.len()
     }
 
     fn step_idx(&self) -> usize {
         match self {
             ProgressUpdateSteps::DownloadLiquidBounceMods => 0,
-            ProgressUpdateSteps::DownloadJRE => 1,
+            ProgressUpdateSteps::DownloadJava => 1,
             ProgressUpdateSteps::DownloadClientJar => 2,
             ProgressUpdateSteps::DownloadLibraries => 3,
             ProgressUpdateSteps::DownloadAssets => 4,
         }
     }
+
+    /// Stable identifier of the step, used by the frontend
+    pub fn id(&self) -> &'static str {
+        match self {
+            ProgressUpdateSteps::DownloadLiquidBounceMods => "downloadMods",
+            ProgressUpdateSteps::DownloadJava => "downloadJava",
+            ProgressUpdateSteps::DownloadClientJar => "downloadClientJar",
+            ProgressUpdateSteps::DownloadLibraries => "downloadLibraries",
+            ProgressUpdateSteps::DownloadAssets => "downloadAssets",
+        }
+    }
+}
+
+/// Serialized with its position, so the frontend is able to show "step 3 of 5"
+impl Serialize for ProgressUpdateSteps {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut step = serializer.serialize_struct("ProgressUpdateSteps", 3)?;
+        step.serialize_field("id", self.id())?;
+        step.serialize_field("index", &self.step_idx())?;
+        step.serialize_field("count", &Self::len())?;
+        step.end()
+    }
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -59,27 +109,176 @@ pub enum ProgressUpdate {
     SetMax(u64),
     #[serde(rename = "progress")] 
     SetProgress(u64),
+    /// Overall progress between 0.0 and 1.0, sent along with every [ProgressUpdate::SetProgress]
+    #[serde(rename = "fraction")]
+    SetFraction(f64),
     #[serde(rename = "label")] 
     SetLabel(String),
+    #[serde(rename = "stats", rename_all = "camelCase")]
+    SetStats {
+        bytes_done: u64,
+        bytes_total: u64,
+        bytes_per_sec: u64,
+        eta_secs: Option<u64>,
+    },
+    #[serde(rename = "stepStarted")]
+    StepStarted(ProgressUpdateSteps),
+    #[serde(rename = "stepFinished")]
+    StepFinished(ProgressUpdateSteps),
+    /// Progress within a single step, turned into a [ProgressUpdate::SetProgress] by the [ProgressTracker]
+    #[serde(rename = "stepProgress")]
+    StepProgress {
+        step: ProgressUpdateSteps,
+        progress: u64,
+        max: u64,
+    },
+    /// Statistics of a single transfer, summed up with all other running transfers
+    /// into a [ProgressUpdate::SetStats] by the [ProgressTracker]
+    #[serde(rename = "transferStats", rename_all = "camelCase")]
+    TransferStats {
+        transfer: u64,
+        bytes_done: u64,
+        bytes_total: u64,
+        bytes_per_sec: u64,
+        finished: bool,
+    },
 }
 
+/// Weight of every step as long as nothing is known about the work of the steps
 const PER_STEP: u64 = 1024;
 
 impl ProgressUpdate {
+    /// The progress is clamped to the step, an empty step (`max` of 0) stays at its start
     pub fn set_for_step(step: ProgressUpdateSteps, progress: u64, max: u64) -> Self {
-        Self::SetProgress(step.step_idx() as u64 * PER_STEP + (progress * PER_STEP / max))
+        Self::StepProgress { step, progress, max }
     }
     pub fn set_to_max() -> Self {
-        Self::SetProgress(ProgressUpdateSteps::len() as u64 * PER_STEP)
-    }
-    pub fn set_max() -> Self {
-        let max = ProgressUpdateSteps::len() as u64;
-
-        Self::SetMax(max * PER_STEP)
+        Self::StepProgress { step: ProgressUpdateSteps::DownloadAssets, progress: 1, max: 1 }
     }
     pub fn set_label<S: AsRef<str>>(str: S) -> Self {
         return Self::SetLabel(str.as_ref().to_owned());
     }
+
+    /// Updates which are turned into the overall progress by the [ProgressTracker] instead of being passed on
+    pub fn is_translated(&self) -> bool {
+        matches!(self, Self::StepProgress { .. } | Self::TransferStats { .. })
+    }
+
+    /// Step this update belongs to
+    pub fn current_step(&self) -> Option<ProgressUpdateSteps> {
+        match self {
+            Self::StepStarted(step) | Self::StepFinished(step) | Self::StepProgress { step, .. } => Some(*step),
+            _ => None,
+        }
+    }
+}
+
+/// Share of every step in the overall progress, in planned bytes
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressPlan {
+    weights: [u64; 5],
+}
+
+impl Default for ProgressPlan {
+    fn default() -> Self {
+        Self { weights: [PER_STEP; 5] }
+    }
+}
+
+impl ProgressPlan {
+    /// Steps without planned bytes do not advance the overall progress,
+    /// if nothing is planned at all every step weighs the same
+    pub fn new(planned_bytes: &[(ProgressUpdateSteps, u64)]) -> Self {
+        let mut weights = [0; 5];
+        for (step, bytes) in planned_bytes {
+            weights[step.step_idx()] += bytes;
+        }
+
+        if weights.iter().all(|weight| *weight == 0) {
+            return Self::default();
+        }
+        Self { weights }
+    }
+
+    pub fn total(&self) -> u64 {
+        get_weighted_max(&self.weights)
+    }
+
+    /// Overall progress of `progress` out of `max` within the step
+    fn progress(&self, step: ProgressUpdateSteps, progress: u64, max: u64) -> u64 {
+        get_weighted_progress(&self.weights, step.step_idx(), progress, max)
+    }
+}
+
+/// Reports the progress of the steps as a single progress against the total of the [ProgressPlan]
+#[derive(Default)]
+pub struct ProgressTracker {
+    plan: Mutex<ProgressPlan>,
+    progress: AtomicU64,
+    /// Latest statistics of every running transfer
+    transfers: Mutex<HashMap<u64, TransferState>>,
+}
+
+#[derive(Clone, Copy)]
+struct TransferState {
+    bytes_done: u64,
+    bytes_total: u64,
+    bytes_per_sec: u64,
+}
+
+impl ProgressTracker {
+    /// Replaces the plan and starts over, returns the updates announcing the new maximum
+    pub fn set_plan(&self, plan: ProgressPlan) -> Vec<ProgressUpdate> {
+        *self.plan.lock().unwrap() = plan;
+        self.progress.store(0, Ordering::SeqCst);
+
+        vec![ProgressUpdate::SetMax(plan.total()), ProgressUpdate::SetProgress(0), ProgressUpdate::SetFraction(0.0)]
+    }
+
+    /// Turns step progress into overall progress and its fraction, which never move backwards,
+    /// even if concurrent downloads report out of order
+    pub fn translate(&self, update: ProgressUpdate) -> Vec<ProgressUpdate> {
+        if let ProgressUpdate::TransferStats { transfer, bytes_done, bytes_total, bytes_per_sec, finished } = update {
+            return self.aggregate_stats(transfer, TransferState { bytes_done, bytes_total, bytes_per_sec }, finished)
+                .into_iter()
+                .collect();
+        }
+        let ProgressUpdate::StepProgress { step, progress, max } = update else {
+            return vec![update];
+        };
+
+        let plan = *self.plan.lock().unwrap();
+        let progress = plan.progress(step, progress, max);
+        let progress = self.progress.fetch_max(progress, Ordering::SeqCst).max(progress);
+        let total = plan.total();
+
+        vec![ProgressUpdate::SetProgress(progress), ProgressUpdate::SetFraction(progress.min(total) as f64 / total.max(1) as f64)]
+    }
+
+    /// Sums up the statistics of all running transfers, so concurrent downloads do not overwrite each other.
+    /// Nothing is reported once the last transfer has finished.
+    fn aggregate_stats(&self, transfer: u64, state: TransferState, finished: bool) -> Option<ProgressUpdate> {
+        let mut transfers = self.transfers.lock().unwrap();
+        if finished {
+            transfers.remove(&transfer);
+        } else {
+            transfers.insert(transfer, state);
+        }
+        if transfers.is_empty() {
+            return None;
+        }
+
+        let (bytes_done, bytes_total, bytes_per_sec) = transfers.values()
+            .fold((0, 0, 0), |(done, total, rate), state| {
+                (done + state.bytes_done, total + state.bytes_total, rate + state.bytes_per_sec)
+            });
+        // A transfer of unknown size leaves the remaining time unknown as well
+        let sizes_known = transfers.values().all(|state| state.bytes_total > 0);
+        let eta_secs = (sizes_known && bytes_per_sec > 0)
+            .then(|| bytes_total.saturating_sub(bytes_done) / bytes_per_sec);
+
+        Some(ProgressUpdate::SetStats { bytes_done, bytes_total, bytes_per_sec, eta_secs })
+    }
 }
 
 pub trait ProgressReceiver {
@@ -87,3 +286,225 @@ pub trait ProgressReceiver {
     fn log(&self, msg: &str);
 }
 
+/// Sums the progress of concurrent tasks of a step into a single progress of the step.
+///
+/// Every task covers its weight of the step. The progress of a task only ever grows and the sum is reported
+/// while holding the lock, so the inner receiver never sees the progress decrease, no matter in which order
+/// the tasks report or finish. The [ProgressTracker] turns it into the overall progress and its fraction.
+pub struct AggregatingProgressReceiver<'a, R: ProgressReceiver> {
+    inner: &'a R,
+    step: ProgressUpdateSteps,
+    weights: Vec<u64>,
+    state: Mutex<AggregatedProgress>,
+}
+
+struct AggregatedProgress {
+    /// Units of its weight every task has done
+    done: Vec<u64>,
+    total: u64,
+}
+
+impl<'a, R: ProgressReceiver> AggregatingProgressReceiver<'a, R> {
+    pub fn new(inner: &'a R, step: ProgressUpdateSteps, weights: Vec<u64>) -> Self {
+        let state = AggregatedProgress { done: vec![0; weights.len()], total: 0 };
+        Self { inner, step, weights, state: Mutex::new(state) }
+    }
+
+    /// Sum of the weights of all tasks
+    pub fn max(&self) -> u64 {
+        get_weighted_max(&self.weights)
+    }
+
+    /// Reports that the task has done `curr` out of `max`, an unknown maximum (0) does not advance the task
+    pub fn update_task(&self, task: usize, curr: u64, max: u64) {
+        let Some(weight) = self.weights.get(task).copied() else {
+            return;
+        };
+        // Tasks run concurrently, so only the share of the task itself is counted without the tasks before it
+        self.advance(task, get_weighted_progress(&[weight], 0, curr, max));
+    }
+
+    /// Marks the task as done, e.g. if it did not have to download anything
+    pub fn finish_task(&self, task: usize) {
+        if let Some(weight) = self.weights.get(task).copied() {
+            self.advance(task, weight);
+        }
+    }
+
+    fn advance(&self, task: usize, done: u64) {
+        let mut state = self.state.lock().unwrap();
+        if done <= state.done[task] {
+            return;
+        }
+
+        state.total += done - state.done[task];
+        state.done[task] = done;
+        self.inner.progress_update(ProgressUpdate::set_for_step(self.step, state.total, self.max()));
+    }
+}
+
+impl<R: ProgressReceiver> ProgressReceiver for AggregatingProgressReceiver<'_, R> {
+    fn progress_update(&self, update: ProgressUpdate) {
+        self.inner.progress_update(update);
+    }
+    fn log(&self, msg: &str) {
+        self.inner.log(msg);
+    }
+}
+
+/// Receiver writing every update as a line of JSON, for tools consuming the progress of a launch.
+///
+/// It receives the progress of the steps and of every transfer along with the overall progress they are
+/// turned into, every line carries the current step, the overall fraction and the bytes of the running downloads.
+pub struct JsonProgressWriter<W: Write + Send> {
+    writer: Mutex<(W, JsonProgressState)>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct JsonProgressState {
+    step: Option<ProgressUpdateSteps>,
+    fraction: f64,
+    bytes: Option<JsonProgressBytes>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+struct JsonProgressBytes {
+    done: u64,
+    total: u64,
+}
+
+#[derive(Serialize)]
+struct JsonProgressLine<'a> {
+    /// Milliseconds since the unix epoch
+    timestamp: u128,
+    step: Option<ProgressUpdateSteps>,
+    /// Overall progress between 0.0 and 1.0
+    fraction: f64,
+    /// Bytes of the running downloads
+    bytes: Option<JsonProgressBytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    update: Option<&'a ProgressUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log: Option<&'a str>,
+}
+
+impl JsonProgressState {
+    fn apply(&mut self, update: &ProgressUpdate) {
+        if let Some(step) = update.current_step() {
+            self.step = Some(step);
+        }
+
+        match update {
+            ProgressUpdate::SetFraction(fraction) => self.fraction = *fraction,
+            ProgressUpdate::SetStats { bytes_done, bytes_total, .. } => {
+                self.bytes = Some(JsonProgressBytes { done: *bytes_done, total: *bytes_total });
+            }
+            // The last transfer has finished, no downloads are running anymore
+            ProgressUpdate::TransferStats { finished: true, .. } | ProgressUpdate::StepFinished(_) => self.bytes = None,
+            _ => {}
+        }
+    }
+}
+
+impl<W: Write + Send> JsonProgressWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer: Mutex::new((writer, JsonProgressState::default())) }
+    }
+
+    /// Failing writes are ignored, the progress stream must not break the launch
+    fn write_line(&self, update: Option<&ProgressUpdate>, log: Option<&str>) {
+        let Ok(mut guard) = self.writer.lock() else {
+            return;
+        };
+        let (writer, state) = &mut *guard;
+        if let Some(update) = update {
+            state.apply(update);
+        }
+
+        let line = JsonProgressLine {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_millis()).unwrap_or_default(),
+            step: state.step,
+            fraction: state.fraction,
+            bytes: state.bytes,
+            update,
+            log,
+        };
+
+        if serde_json::to_writer(&mut *writer, &line).is_ok() {
+            let _ = writer.write_all(b"\n");
+            let _ = writer.flush();
+        }
+    }
+}
+
+impl<W: Write + Send> ProgressReceiver for JsonProgressWriter<W> {
+    fn progress_update(&self, update: ProgressUpdate) {
+        self.write_line(Some(&update), None);
+    }
+    fn log(&self, msg: &str) {
+        self.write_line(None, Some(msg));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_progress_advances_by_weight() {
+        let weights = [40_000_000, 20_000, 100];
+        assert_eq!(get_weighted_max(&weights), 40_020_100);
+        assert_eq!(get_weighted_progress(&weights, 0, 1, 2), 20_000_000);
+        assert_eq!(get_weighted_progress(&weights, 1, 1, 2), 40_010_000);
+        assert_eq!(get_weighted_progress(&weights, 2, 2, 2), get_weighted_max(&weights));
+        // Equal weights of 100 units match the unweighted progress
+        assert_eq!(get_weighted_progress(&[100; 3], 2, 1, 4), get_progress(2, 1, 4));
+    }
+
+    #[test]
+    fn unknown_maximum_does_not_advance() {
+        assert_eq!(get_progress(2, 500, 0), 200);
+        assert_eq!(get_weighted_progress(&[10, 30], 1, 500, 0), 10);
+
+        let tracker = ProgressTracker::default();
+        let updates = tracker.translate(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadJava, 500, 0));
+        assert!(matches!(updates[0], ProgressUpdate::SetProgress(progress) if progress == PER_STEP));
+        assert!(matches!(updates[1], ProgressUpdate::SetFraction(fraction) if fraction == 0.2));
+    }
+
+    #[test]
+    fn json_stream_reports_step_fraction_and_bytes() {
+        let writer = JsonProgressWriter::new(Vec::new());
+        let tracker = ProgressTracker::default();
+        // Like the launcher data, the stream receives the update of the step and what it is turned into
+        for update in [
+            ProgressUpdate::StepStarted(ProgressUpdateSteps::DownloadJava),
+            ProgressUpdate::TransferStats { transfer: 1, bytes_done: 512, bytes_total: 2048, bytes_per_sec: 256, finished: false },
+            ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadJava, 512, 2048),
+        ] {
+            if update.is_translated() {
+                writer.progress_update(update.clone());
+            }
+            for translated in tracker.translate(update) {
+                writer.progress_update(translated);
+            }
+        }
+        let (buffer, _) = writer.writer.into_inner().unwrap();
+
+        let lines = String::from_utf8(buffer).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        let step_progress = lines.iter().find(|line| line["update"]["type"] == "stepProgress").unwrap();
+        assert_eq!(step_progress["step"]["id"], "downloadJava");
+        assert_eq!(step_progress["update"]["value"]["progress"], 512);
+        assert_eq!(step_progress["update"]["value"]["max"], 2048);
+        assert_eq!(step_progress["bytes"], serde_json::json!({ "done": 512, "total": 2048 }));
+
+        let last = lines.last().unwrap();
+        assert_eq!(last["update"]["type"], "fraction");
+        assert_eq!(last["step"]["id"], "downloadJava");
+        // A quarter of the second of five equally weighted steps, (1 + 0.25) / 5
+        assert_eq!(last["fraction"], 0.25);
+    }
+}
@@ -23,15 +23,21 @@ use anyhow::Result;
 use regex::Regex;
 
 use crate::minecraft::version::{Rule, RuleAction};
-use crate::utils::{OS, ARCHITECTURE, OS_VERSION};
+use crate::utils::{Architecture, OS, ARCHITECTURE, OS_VERSION};
 
-pub fn check_condition(rules: &Vec<Rule>, features: &HashSet<String>) -> Result<bool> {
+pub fn check_condition(rules: &[Rule], features: &HashSet<String>) -> Result<bool> {
     if rules.is_empty() {
         return Ok(true);
     }
 
-    let os_name = OS.get_simple_name()?;
-    let os_version = &*OS_VERSION.clone();
+    check_condition_on(rules, features, OS.get_simple_name()?, &ARCHITECTURE, &OS_VERSION)
+}
+
+/// Evaluates the rules on the given system
+fn check_condition_on(rules: &[Rule], features: &HashSet<String>, os_name: &str, architecture: &Architecture, os_version: &str) -> Result<bool> {
+    if rules.is_empty() {
+        return Ok(true);
+    }
 
     let mut allow = false;
 
@@ -43,12 +49,12 @@ pub fn check_condition(rules: &Vec<Rule>, features: &HashSet<String>) -> Result<
                 rule_applies = false;
             }
             if let Some(arch) = &os_requirement.arch {
-                if *arch != ARCHITECTURE {
+                if arch != architecture {
                     rule_applies = false;
                 }
             }
             if let Some(version_regex) = &os_requirement.version {
-                if !Regex::new(version_regex)?.is_match(&os_version) {
+                if !Regex::new(version_regex)?.is_match(os_version) {
                     rule_applies = false;
                 }
             }
@@ -70,4 +76,65 @@ pub fn check_condition(rules: &Vec<Rule>, features: &HashSet<String>) -> Result<
     }
 
     Ok(allow)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(json: serde_json::Value) -> Vec<Rule> {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn allowed_on(rules: &[Rule], os_name: &str, architecture: Architecture) -> bool {
+        check_condition_on(rules, &HashSet::new(), os_name, &architecture, "14.4").unwrap()
+    }
+
+    #[test]
+    fn allows_only_osx() {
+        let rules = rules(serde_json::json!([{ "action": "allow", "os": { "name": "osx" } }]));
+
+        assert!(allowed_on(&rules, "osx", Architecture::AARCH64));
+        assert!(!allowed_on(&rules, "linux", Architecture::X64));
+        assert!(!allowed_on(&rules, "windows", Architecture::X64));
+    }
+
+    #[test]
+    fn disallows_osx() {
+        let rules = rules(serde_json::json!([
+            { "action": "allow" },
+            { "action": "disallow", "os": { "name": "osx" } }
+        ]));
+
+        assert!(allowed_on(&rules, "linux", Architecture::X64));
+        assert!(allowed_on(&rules, "windows", Architecture::X86));
+        assert!(!allowed_on(&rules, "osx", Architecture::X64));
+    }
+
+    #[test]
+    fn disallows_arm_mac() {
+        let rules = rules(serde_json::json!([
+            { "action": "allow", "os": { "name": "osx" } },
+            { "action": "disallow", "os": { "name": "osx", "arch": "arm64" } }
+        ]));
+
+        assert!(allowed_on(&rules, "osx", Architecture::X64));
+        assert!(!allowed_on(&rules, "osx", Architecture::AARCH64));
+        assert!(!allowed_on(&rules, "linux", Architecture::AARCH64));
+    }
+
+    #[test]
+    fn matches_os_version() {
+        let rules = rules(serde_json::json!([
+            { "action": "allow" },
+            { "action": "disallow", "os": { "name": "osx", "version": "^10\\.5\\.\\d$" } }
+        ]));
+
+        assert!(!check_condition_on(&rules, &HashSet::new(), "osx", &Architecture::X64, "10.5.8").unwrap());
+        assert!(check_condition_on(&rules, &HashSet::new(), "osx", &Architecture::X64, "10.15.7").unwrap());
+    }
+
+    #[test]
+    fn allows_without_rules() {
+        assert!(allowed_on(&[], "linux", Architecture::ARM));
+    }
+}
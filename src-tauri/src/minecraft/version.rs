@@ -17,7 +17,7 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
  
-use std::{collections::HashMap, fmt, marker::PhantomData, path::{Path, PathBuf}, str::FromStr};
+use std::{cmp::Ordering, collections::HashMap, fmt, future::Future, marker::PhantomData, path::{Path, PathBuf}, str::FromStr};
 
 use anyhow::{Result, Context};
 use tracing::{debug, info};
@@ -25,8 +25,9 @@ use tokio::fs;
 use serde::{Deserialize, Deserializer, de::{self, MapAccess, Visitor}};
 use void::Void;
 use std::collections::HashSet;
-use crate::{error::LauncherError, HTTP_CLIENT, utils::{download_file_untracked, Architecture}};
-use crate::utils::{get_maven_artifact_path, sha1sum};
+use std::time::Duration;
+use crate::{error::LauncherError, HTTP_CLIENT, utils::{download_file_untracked, Architecture, DownloadOptions, OS}};
+use crate::utils::{compare_maven_versions, get_cached, get_cached_read_only, get_maven_artifact_path, sha1sum_async, split_maven_version};
 use crate::minecraft::launcher::LaunchingParameter;
 use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
 
@@ -34,23 +35,107 @@ use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
 
 #[derive(Deserialize)]
 pub struct VersionManifest {
+    #[serde(default)]
+    pub latest: Option<LatestVersions>,
     pub versions: Vec<ManifestVersion>
 }
 
+#[derive(Deserialize)]
+pub struct LatestVersions {
+    pub release: String,
+    pub snapshot: String,
+}
+
+const VERSION_MANIFEST_URL: &str = "https://launchermeta.mojang.com/mc/game/version_manifest.json";
+/// New versions are released rarely, the manifest is only requested again after this time
+const VERSION_MANIFEST_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Number of similar versions suggested if a version does not exist
+const VERSION_SUGGESTIONS: usize = 3;
+
 impl VersionManifest {
 
-    pub async fn fetch() -> Result<Self> {
-        let response = HTTP_CLIENT.get("https://launchermeta.mojang.com/mc/game/version_manifest.json")
-            .send().await
-            .context("Connection to https://launchermeta.mojang.com/ failed. Check your internet connection.")?
-            .error_for_status()
-            .context("https://launchermeta.mojang.com/ returned with an error code, try again later!")?;
-        let manifest = response.json::<VersionManifest>().await
+    /// A dry run only reads the cached manifest, a newer manifest is not stored
+    pub async fn fetch(dry_run: bool) -> Result<Self> {
+        let content = match dry_run {
+            false => get_cached(VERSION_MANIFEST_URL, "version_manifest.json", VERSION_MANIFEST_TTL).await,
+            true => get_cached_read_only(VERSION_MANIFEST_URL, "version_manifest.json", VERSION_MANIFEST_TTL).await,
+        }
+            .context("Connection to https://launchermeta.mojang.com/ failed. Check your internet connection.")?;
+        let manifest = serde_json::from_slice::<VersionManifest>(&content)
             .context("Failed to parse Version Manifest, Mojang Server responded with not valid format.")?;
 
         Ok(manifest)
     }
 
+    /// Finds a version of any type, including snapshots and old alpha and beta versions
+    pub fn find_by_id(&self, id: &str) -> Option<&ManifestVersion> {
+        self.versions.iter().find(|x| x.id == id)
+    }
+
+    /// Like [VersionManifest::find_by_id], but fails with the most similar versions if there is no such version
+    pub fn get_by_id(&self, id: &str) -> Result<&ManifestVersion> {
+        self.find_by_id(id).ok_or_else(|| LauncherError::UnknownVersion {
+            id: id.to_string(),
+            suggestions: self.suggestions(id),
+        }.into())
+    }
+
+    pub fn latest_release(&self) -> Option<&ManifestVersion> {
+        match &self.latest {
+            Some(latest) => self.find_by_id(&latest.release),
+            // Versions are listed newest first
+            None => self.versions.iter().find(|x| x.version_type == "release"),
+        }
+    }
+
+    pub fn latest_snapshot(&self) -> Option<&ManifestVersion> {
+        match &self.latest {
+            Some(latest) => self.find_by_id(&latest.snapshot),
+            None => self.versions.iter().find(|x| x.version_type == "snapshot"),
+        }
+    }
+
+    /// The most similar versions, followed by the latest release and snapshot
+    fn suggestions(&self, id: &str) -> Vec<String> {
+        let mut suggestions = self.closest_matches(id, VERSION_SUGGESTIONS);
+        for latest in [self.latest_release(), self.latest_snapshot()].into_iter().flatten() {
+            if !suggestions.contains(&latest.id) {
+                suggestions.push(latest.id.clone());
+            }
+        }
+        suggestions
+    }
+
+    /// Ids of the versions with the smallest edit distance to the id
+    fn closest_matches(&self, id: &str, count: usize) -> Vec<String> {
+        let mut versions = self.versions.iter()
+            .map(|x| (edit_distance(id, &x.id), &x.id))
+            .collect::<Vec<_>>();
+        versions.sort_by_key(|(distance, _)| *distance);
+        versions.into_iter()
+            .take(count)
+            .map(|(_, id)| id.clone())
+            .collect()
+    }
+
+}
+
+/// Levenshtein distance of the two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
 }
 
 #[derive(Deserialize)]
@@ -72,11 +157,16 @@ pub struct VersionProfile {
     pub assets: Option<String>,
     #[serde(rename = "inheritsFrom")]
     pub inherits_from: Option<String>,
+    /// Version providing the client jar, the vanilla version for modded profiles
+    #[serde(default)]
+    pub jar: Option<String>,
     #[serde(rename = "minimumLauncherVersion")]
     pub minimum_launcher_version: Option<i32>,
     pub downloads: Option<Downloads>,
     #[serde(rename = "complianceLevel")]
     pub compliance_level: Option<i32>,
+    #[serde(rename = "javaVersion")]
+    pub java_version: Option<JavaVersion>,
     pub libraries: Vec<Library>,
     #[serde(rename = "mainClass")]
     pub main_class: Option<String>,
@@ -88,39 +178,69 @@ pub struct VersionProfile {
 }
 
 impl VersionProfile {
-    pub(crate) fn merge(&mut self, mut parent: VersionProfile) -> Result<()> {
+    /// Client jar of the version, shared by all profiles inheriting from the same version
+    pub fn client_jar_path(&self, versions_folder: &Path) -> PathBuf {
+        let id = self.jar.as_deref().unwrap_or(&self.id);
+        versions_folder.join(id).join(format!("{}.jar", id))
+    }
+
+    pub(crate) fn merge(&mut self, mut parent: VersionProfile, library_conflicts: LibraryConflictResolution) -> Result<()> {
         Self::merge_options(&mut self.asset_index_location, parent.asset_index_location);
         Self::merge_options(&mut self.assets, parent.assets);
 
         Self::merge_larger(&mut self.minimum_launcher_version, parent.minimum_launcher_version);
+        if self.downloads.is_none() && self.jar.is_none() {
+            self.jar = Some(parent.jar.clone().unwrap_or_else(|| parent.id.clone()));
+        }
         Self::merge_options(&mut self.downloads, parent.downloads);
         Self::merge_larger(&mut self.compliance_level, parent.compliance_level);
+        Self::merge_options(&mut self.java_version, parent.java_version);
 
-        self.libraries.append(&mut parent.libraries);
+        self.merge_libraries(std::mem::take(&mut parent.libraries), library_conflicts);
         Self::merge_options(&mut self.main_class, parent.main_class);
         Self::merge_options(&mut self.logging, parent.logging);
 
-        match &mut self.arguments {
-            ArgumentDeclaration::V14(v14_a) => {
-                if let ArgumentDeclaration::V14(v14_b) = parent.arguments {
-                    Self::merge_options(&mut v14_a.minecraft_arguments, v14_b.minecraft_arguments);
-                } else {
-                    return Err(LauncherError::InvalidVersionProfile("version profile inherits from incompatible profile".to_string()).into());
-                }
-            }
-            ArgumentDeclaration::V21(v21_a) => {
-                if let ArgumentDeclaration::V21(mut v21_b) = parent.arguments {
-                    v21_a.arguments.game.append(&mut v21_b.arguments.game);
-                    v21_a.arguments.jvm.append(&mut v21_b.arguments.jvm);
-                } else {
-                    return Err(LauncherError::InvalidVersionProfile("version profile inherits from incompatible profile".to_string()).into());
-                }
-            }
-        }
+        // Legacy and modern profiles can inherit from each other, so both are merged in the structured form
+        let arguments = std::mem::replace(&mut self.arguments, ArgumentDeclaration::V21(V21ArgumentDeclaration::default()));
+        let mut arguments = arguments.into_structured();
+        let parent_arguments = parent.arguments.into_structured();
+        merge_arguments(&mut arguments.arguments.game, parent_arguments.arguments.game);
+        merge_arguments(&mut arguments.arguments.jvm, parent_arguments.arguments.jvm);
+        self.arguments = ArgumentDeclaration::V21(arguments);
 
         Ok(())
     }
 
+    /// Appends the libraries of the parent, a library specified by both profiles is only kept once
+    fn merge_libraries(&mut self, parent: Vec<Library>, library_conflicts: LibraryConflictResolution) {
+        let child_libraries = self.libraries.len();
+
+        for library in parent {
+            let conflict = library.conflict_key().and_then(|(artifact, version)| {
+                self.libraries[..child_libraries].iter().position(|child| {
+                    child.conflict_key().is_some_and(|(child_artifact, _)| child_artifact == artifact)
+                }).map(|index| (index, version))
+            });
+
+            let Some((index, version)) = conflict else {
+                self.libraries.push(library);
+                continue;
+            };
+
+            let child = &self.libraries[index];
+            let child_version = child.conflict_key().map(|(_, version)| version).unwrap_or_default();
+            let keep_parent = library_conflicts == LibraryConflictResolution::Highest
+                && compare_maven_versions(&version, &child_version) == Ordering::Greater;
+
+            if keep_parent {
+                info!("Dropping library {} in favour of inherited {}", child.name, library.name);
+                self.libraries[index] = library;
+            } else {
+                info!("Dropping inherited library {} in favour of {}", library.name, child.name);
+            }
+        }
+    }
+
     fn merge_options<T>(a: &mut Option<T>, b: Option<T>) {
         if !a.is_some() {
             *a = b;
@@ -138,6 +258,84 @@ impl VersionProfile {
     }
 }
 
+/// Appends the arguments of the parent to the arguments of the child.
+/// Options of the parent are left out if the child already specifies them, which also removes identical tokens.
+fn merge_arguments(arguments: &mut Vec<Argument>, parent: Vec<Argument>) {
+    let options = group_options(std::mem::take(arguments));
+    let keys = options.iter()
+        .filter_map(|option| option_key(option))
+        .collect::<HashSet<_>>();
+    let plain_options = options.iter()
+        .filter_map(|option| plain_tokens(option))
+        .collect::<HashSet<_>>();
+
+    let parent_options = group_options(parent).into_iter()
+        .filter(|option| {
+            let Some(tokens) = plain_tokens(option) else {
+                return true;
+            };
+
+            let specified_by_child = option_key(option)
+                .is_some_and(|key| keys.contains(&key) && !REPEATABLE_OPTIONS.contains(&key.as_str()));
+            !specified_by_child && !plain_options.contains(&tokens)
+        })
+        .collect::<Vec<_>>();
+
+    arguments.extend(options.into_iter().flatten());
+    arguments.extend(parent_options.into_iter().flatten());
+}
+
+/// Groups the arguments into options, an option is a flag followed by its values.
+/// Arguments with rules always form an option of their own.
+fn group_options(arguments: Vec<Argument>) -> Vec<Vec<Argument>> {
+    let mut options: Vec<Vec<Argument>> = Vec::new();
+
+    for argument in arguments {
+        let is_value = matches!(&argument, Argument { rules: None, value: ArgumentValue::SINGLE(value) } if !value.starts_with('-'));
+
+        match options.last_mut() {
+            Some(option) if is_value && plain_tokens(option).is_some() => option.push(argument),
+            _ => options.push(vec![argument]),
+        }
+    }
+
+    options
+}
+
+/// Tokens of an option without any rules
+fn plain_tokens(option: &[Argument]) -> Option<Vec<String>> {
+    option.iter()
+        .map(|argument| match argument {
+            Argument { rules: None, value: ArgumentValue::SINGLE(value) } => Some(value.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Name of the flag of an option without any rules, system properties are identified by their name only
+fn option_key(option: &[Argument]) -> Option<String> {
+    let tokens = plain_tokens(option)?;
+    let flag = tokens.first().filter(|x| x.starts_with('-'))?;
+
+    Some(flag.split('=').next().unwrap_or(flag).to_string())
+}
+
+/// Decides which library is kept if a profile and its parent specify different versions of the same library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibraryConflictResolution {
+    /// Keep the highest version
+    Highest,
+    /// Keep the version of the inheriting profile
+    Child,
+}
+
+#[derive(Deserialize)]
+pub struct JavaVersion {
+    pub component: Option<String>,
+    #[serde(rename = "majorVersion")]
+    pub major_version: u32
+}
+
 #[derive(Deserialize)]
 #[serde(untagged)] // TODO: Might guess from minimum_launcher_version just to be sure.
 pub enum ArgumentDeclaration {
@@ -147,10 +345,50 @@ pub enum ArgumentDeclaration {
     V14(V14ArgumentDeclaration),
 }
 
+/// JVM arguments of legacy profiles, which do not specify any
+const LEGACY_JVM_ARGUMENTS: [&str; 3] = ["-Djava.library.path=${natives_directory}", "-cp", "${classpath}"];
+
+/// Options which are allowed to be passed more than once
+const REPEATABLE_OPTIONS: [&str; 4] = ["--tweakClass", "--add-opens", "--add-exports", "--add-modules"];
+
 impl ArgumentDeclaration {
 
+    /// Whether any game argument is conditional on the feature
+    pub(crate) fn has_feature(&self, feature: &str) -> bool {
+        match self {
+            ArgumentDeclaration::V14(_) => false,
+            ArgumentDeclaration::V21(decl) => decl.arguments.game.iter()
+                .flat_map(|argument| argument.rules.iter().flatten())
+                .any(|rule| rule.features.as_ref().is_some_and(|features| features.contains_key(feature))),
+        }
+    }
+
+    /// Converts the legacy argument string into the structured form
+    fn into_structured(self) -> V21ArgumentDeclaration {
+        match self {
+            ArgumentDeclaration::V14(decl) => V21ArgumentDeclaration {
+                arguments: Arguments {
+                    game: decl.minecraft_arguments
+                        .unwrap_or_default()
+                        .split(' ')
+                        .filter(|x| !x.is_empty())
+                        .map(|x| Argument { value: ArgumentValue::SINGLE(x.to_string()), rules: None })
+                        .collect(),
+                    jvm: LEGACY_JVM_ARGUMENTS.iter()
+                        .map(|x| Argument { value: ArgumentValue::SINGLE(x.to_string()), rules: None })
+                        .collect(),
+                },
+            },
+            ArgumentDeclaration::V21(decl) => decl,
+        }
+    }
+
     pub(crate) fn add_jvm_args_to_vec(&self, command_arguments: &mut Vec<String>, parameter: &LaunchingParameter, features: &HashSet<String>) -> Result<()> {
-        command_arguments.push(format!("-Xmx{}M", parameter.memory));
+        let (min_memory, max_memory) = parameter.heap_size();
+        if let Some(min_memory) = min_memory {
+            command_arguments.push(format!("-Xms{}M", min_memory));
+        }
+        command_arguments.push(format!("-Xmx{}M", max_memory));
         command_arguments.push("-XX:+UnlockExperimentalVMOptions".to_string());
         command_arguments.push("-XX:+UseG1GC".to_string());
         command_arguments.push("-XX:G1NewSizePercent=20".to_string());
@@ -159,7 +397,7 @@ impl ArgumentDeclaration {
         command_arguments.push("-XX:G1HeapRegionSize=32M".to_string());
 
         match self {
-            ArgumentDeclaration::V14(_) => command_arguments.append(&mut vec!["-Djava.library.path=${natives_directory}".to_string(), "-cp".to_string(), "${classpath}".to_string()]),
+            ArgumentDeclaration::V14(_) => command_arguments.extend(LEGACY_JVM_ARGUMENTS.iter().map(|x| x.to_string())),
             ArgumentDeclaration::V21(decl) => {
                 ArgumentDeclaration::check_rules_and_add(command_arguments, &decl.arguments.jvm, features)?;
             }
@@ -210,32 +448,113 @@ pub struct V14ArgumentDeclaration {
     pub minecraft_arguments: Option<String>
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct V21ArgumentDeclaration {
     pub arguments: Arguments,
 }
 
 impl VersionProfile {
     pub async fn load(url: &String) -> Result<Self> {
+        let json = Self::load_json(url).await?;
+        let version_profile = serde_json::from_value::<VersionProfile>(json)
+            .context(format!("{} responded with not valid format.", url))?;
+
+        Ok(version_profile)
+    }
+
+    /// Loads the raw version profile, which can be cached for offline launches
+    pub async fn load_json(url: &String) -> Result<serde_json::Value> {
         debug!("Loading version profile from {}", url);
 
-        let version_profile = HTTP_CLIENT.get(url)
+        let json = HTTP_CLIENT.get(url)
             .send()
             .await
             .context(format!("failed to pull version profile from {}", url))?
             .error_for_status()
             .context(format!("{} responded with error code.", url))?
-            .json::<VersionProfile>().await
+            .json::<serde_json::Value>().await
             .context(format!("{} responded with not valid format.", url))?;
 
-        Ok(version_profile)
+        Ok(json)
+    }
+
+    /// Follows `inheritsFrom` until a root profile is reached.
+    /// The inherited profiles are returned in order, starting with the direct parent.
+    pub async fn load_inherited_json<F, Fut>(version_json: &serde_json::Value, mut load_parent: F) -> Result<Vec<serde_json::Value>>
+        where F: FnMut(String) -> Fut, Fut: Future<Output = Result<serde_json::Value>> {
+        let mut visited = HashSet::new();
+        if let Some(id) = version_json.get("id").and_then(|x| x.as_str()) {
+            visited.insert(id.to_string());
+        }
+
+        let mut inherited = Vec::new();
+        let mut inherits_from = Self::inherits_from_json(version_json);
+
+        while let Some(id) = inherits_from {
+            if !visited.insert(id.clone()) {
+                return Err(LauncherError::InvalidVersionProfile(format!("version profile {} inherits from itself", id)).into());
+            }
+
+            let parent = load_parent(id).await?;
+            inherits_from = Self::inherits_from_json(&parent);
+            inherited.push(parent);
+        }
+
+        Ok(inherited)
+    }
+
+    /// Parses the version profile and merges each inherited profile into it in turn
+    pub fn from_json_chain(version_json: &serde_json::Value, inherited_json: &[serde_json::Value], library_conflicts: LibraryConflictResolution) -> Result<Self> {
+        let mut version = serde_json::from_value::<VersionProfile>(version_json.clone())
+            .context("Failed to parse version profile")?;
+
+        for parent_json in inherited_json {
+            let parent = serde_json::from_value::<VersionProfile>(parent_json.clone())
+                .context("Failed to parse inherited version profile")?;
+            version.merge(parent, library_conflicts)?;
+        }
+
+        // Profiles further up the chain may still provide missing fields, so only the final result is checked
+        if !inherited_json.is_empty() {
+            version.validate_merged()?;
+        }
+
+        Ok(version)
+    }
+
+    /// Checks that the inherited profiles provided everything required to launch the game,
+    /// a broken parent would otherwise only surface as an obscure error of the JVM
+    fn validate_merged(&self) -> Result<()> {
+        let mut missing = Vec::new();
+
+        if self.main_class.as_deref().map_or(true, |x| x.trim().is_empty()) {
+            missing.push("mainClass");
+        }
+        if self.libraries.is_empty() {
+            missing.push("libraries");
+        }
+        if self.asset_index_location.as_ref().map_or(true, |x| x.id.is_empty() || x.url.is_empty()) {
+            missing.push("assetIndex");
+        }
+
+        if !missing.is_empty() {
+            return Err(LauncherError::InvalidVersionProfile(format!(
+                "version profile {} is missing {} after merging the inherited profiles",
+                self.id, missing.join(", ")
+            )).into());
+        }
+        Ok(())
+    }
+
+    fn inherits_from_json(version_json: &serde_json::Value) -> Option<String> {
+        version_json.get("inheritsFrom").and_then(|x| x.as_str()).map(|x| x.to_string())
     }
 }
 
 // Parsing the arguments was pain, please mojang. What in the hell did you do?
 // https://github.com/serde-rs/serde/issues/723 That's why I've done a workaround using vec_argument
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct Arguments {
     #[serde(default)]
     #[serde(deserialize_with = "vec_argument")]
@@ -328,12 +647,12 @@ pub struct AssetIndexLocation {
 
 impl AssetIndexLocation {
 
-    pub async fn load_asset_index(&self, assets_root: &PathBuf) -> Result<AssetIndex> {
+    pub async fn load_asset_index(&self, assets_root: &PathBuf, download_options: &DownloadOptions) -> Result<AssetIndex> {
         let asset_index = assets_root.join(format!("{}.json", &self.id));
         
         if !asset_index.exists() {
             info!("Downloading assets index of {}", self.id);
-            download_file_untracked(&self.url, &asset_index).await?;
+            download_file_untracked(&self.url, &asset_index, download_options).await?;
             info!("Downloaded {}", self.url);
         }
         
@@ -345,7 +664,13 @@ impl AssetIndexLocation {
 
 #[derive(Deserialize)]
 pub struct AssetIndex {
-    pub objects: HashMap<String, AssetObject>
+    pub objects: HashMap<String, AssetObject>,
+    /// Versions before 1.7.3 read the assets by their name from `assets/virtual/{index}`
+    #[serde(rename = "virtual", default)]
+    pub is_virtual: bool,
+    /// Versions before 1.6 read the assets by their name from the resources of the game directory
+    #[serde(default)]
+    pub map_to_resources: bool,
 }
 
 #[derive(Deserialize, Clone)]
@@ -356,31 +681,55 @@ pub struct AssetObject {
 
 impl AssetObject {
 
-    pub async fn download(&self, assets_objects_folder: impl AsRef<Path>, progress: &impl ProgressReceiver) -> Result<bool> {
+    /// Objects are shared by all versions, the size is enough to catch objects of interrupted downloads
+    pub fn is_present(&self, assets_objects_folder: &Path) -> bool {
+        self.hash.len() >= 2 && std::fs::metadata(assets_objects_folder.join(&self.hash[0..2]).join(&self.hash))
+            .is_ok_and(|metadata| metadata.len() as i64 == self.size)
+    }
+
+    pub async fn download(&self, assets_objects_folder: impl AsRef<Path>, download_options: &DownloadOptions, progress: &impl ProgressReceiver) -> Result<bool> {
+        if self.hash.len() < 2 {
+            return Err(LauncherError::Validation(format!("invalid asset object hash {}", self.hash)).into());
+        }
+
         let assets_objects_folder = assets_objects_folder.as_ref().to_owned();
         let asset_folder = assets_objects_folder.join(&self.hash[0..2]);
 
-        if !asset_folder.exists() {
-            fs::create_dir(&asset_folder).await?;
-        }
+        // Objects sharing the same prefix are downloaded concurrently, creating an existing folder does not fail
+        fs::create_dir_all(&asset_folder).await?;
 
         let asset_path = asset_folder.join(&self.hash);
 
-        return if !asset_path.exists() {
-            progress.progress_update(ProgressUpdate::set_label(format!("Downloading asset object {}", self.hash)));
+        // Objects are shared by all versions, the size is enough to catch objects of interrupted downloads
+        if let Ok(metadata) = fs::metadata(&asset_path).await {
+            if metadata.len() as i64 == self.size {
+                return Ok(false);
+            }
+            debug!("Size of asset object {} does not match, downloading again", self.hash);
+        }
 
-            info!("Downloading {}", self.hash);
-            download_file_untracked(&*format!("https://resources.download.minecraft.net/{}/{}", &self.hash[0..2], &self.hash), asset_path).await?;
-            info!("Downloaded {}", self.hash);
+        progress.progress_update(ProgressUpdate::set_label(format!("Downloading asset object {}", self.hash)));
 
-            Ok(true)
-        } else {
-            Ok(false)
+        info!("Downloading {}", self.hash);
+        download_file_untracked(&*format!("https://resources.download.minecraft.net/{}/{}", &self.hash[0..2], &self.hash), &asset_path, download_options).await?;
+
+        // Objects are named by their hash, a mismatching object would be kept forever otherwise
+        let hash = sha1sum_async(&asset_path).await?;
+        if !hash.eq_ignore_ascii_case(&self.hash) {
+            let _ = fs::remove_file(&asset_path).await;
+            return Err(LauncherError::ChecksumMismatch {
+                expected: self.hash.clone(),
+                actual: hash,
+                name: format!("asset object {}", self.hash),
+            }.into());
         }
+        info!("Downloaded {}", self.hash);
+
+        Ok(true)
     }
 
-    pub async fn download_destructing(self, assets_objects_folder: impl AsRef<Path>, progress: &impl ProgressReceiver) -> Result<bool> {
-        return self.download(assets_objects_folder, progress).await;
+    pub async fn download_destructing(self, assets_objects_folder: impl AsRef<Path>, download_options: &DownloadOptions, progress: &impl ProgressReceiver) -> Result<bool> {
+        return self.download(assets_objects_folder, download_options, progress).await;
     }
 
 }
@@ -404,8 +753,8 @@ pub struct Download {
 
 impl Download {
 
-    pub async fn download(&self, path: impl AsRef<Path>) -> Result<()> {
-        download_file_untracked(&self.url, path).await?;
+    pub async fn download(&self, path: impl AsRef<Path>, download_options: &DownloadOptions) -> Result<()> {
+        download_file_untracked(&self.url, path, download_options).await?;
         info!("Downloaded {}", self.url);
         Ok(())
     }
@@ -417,13 +766,39 @@ pub struct Library {
     pub name: String,
     pub downloads: Option<LibraryDownloads>,
     pub natives: Option<HashMap<String, String>>,
+    pub extract: Option<ExtractRules>,
     #[serde(default)]
     pub rules: Vec<Rule>,
     pub url: Option<String>
 }
 
+#[derive(Deserialize, Clone)]
+pub struct ExtractRules {
+    #[serde(default)]
+    pub exclude: Vec<String>
+}
+
 impl Library  {
 
+    /// Classifier of the natives for the current operating system, `${arch}` is replaced with the pointer width
+    pub fn native_classifier(&self) -> Result<Option<String>> {
+        let Some(natives) = &self.natives else {
+            return Ok(None);
+        };
+
+        Ok(natives.get(OS.get_simple_name()?)
+            .map(|classifier| classifier.replace("${arch}", if cfg!(target_pointer_width = "64") { "64" } else { "32" })))
+    }
+
+    /// Artifact without its version and the version, natives and libraries with rules are never considered the same
+    fn conflict_key(&self) -> Option<(String, String)> {
+        if self.natives.is_some() || !self.rules.is_empty() {
+            return None;
+        }
+
+        split_maven_version(&self.name).map(|(artifact, version)| (artifact, version.to_string()))
+    }
+
     pub fn get_library_download(&self) -> Result<LibraryDownloadInfo> {
         if let Some(artifact) = self.downloads.as_ref().and_then(|x| x.artifact.as_ref()) {
             return Ok(artifact.into());
@@ -500,18 +875,28 @@ impl From<&LibraryArtifact> for LibraryDownloadInfo {
 
 impl LibraryDownloadInfo {
 
+    /// Maven repositories usually end the checksum with a newline
     async fn fetch_sha1(&self) -> Result<String> {
-        HTTP_CLIENT.get(&format!("{}{}", &self.url, ".sha1"))
+        let sha1 = HTTP_CLIENT.get(&format!("{}{}", &self.url, ".sha1"))
             .send().await?
             .error_for_status()?
             .text()
             .await
-            .context("Failed to fetch SHA1 of library")
+            .context("Failed to fetch SHA1 of library")?;
+        Ok(sha1.trim().to_string())
     }
 
-    pub async fn download(&self, name: &str, libraries_folder: PathBuf, progress: &impl ProgressReceiver) -> Result<PathBuf> {
+    pub async fn download(&self, name: &str, libraries_folder: PathBuf, download_options: &DownloadOptions, progress: &impl ProgressReceiver) -> Result<PathBuf> {
         let library_path = libraries_folder.join(&self.path);
         let parent = library_path.parent().context("Failed to get parent of library path")?;
+
+        // Libraries without URL are created by the installer of the loader
+        if self.url.is_empty() {
+            if library_path.exists() {
+                return Ok(library_path);
+            }
+            anyhow::bail!("Library {} is not downloadable and has not been installed.", name);
+        }
         
         // Create parent directories
         fs::create_dir_all(parent).await
@@ -526,7 +911,7 @@ impl LibraryDownloadInfo {
 
             // Fetch sha1 file
             if sha1_path.exists() {
-                Some(fs::read_to_string(&sha1_path).await?)
+                Some(fs::read_to_string(&sha1_path).await?.trim().to_string())
             } else {
                 // If sha1 file doesn't exist, fetch it
                 progress.log(&format!("Fetching SHA1 of library {}", name));
@@ -545,11 +930,11 @@ impl LibraryDownloadInfo {
         // Check if library already exists
         if library_path.exists() {
             // Check if sha1 matches
-            let hash = sha1sum(&library_path)
+            let hash = sha1sum_async(&library_path).await
                 .context("Failed to calculate SHA1 of library")?;
 
             if let Some(sha1) = &sha1 {
-                if hash == *sha1 {
+                if hash.eq_ignore_ascii_case(sha1) {
                     // If sha1 matches, return
                     progress.log(&format!("Library {} already exists and SHA1 matches.", name));
                     return Ok(library_path);
@@ -570,16 +955,34 @@ impl LibraryDownloadInfo {
         progress.progress_update(ProgressUpdate::set_label(format!("Downloading library {}", name)));
         progress.log(&format!("Downloading library {} (sha1: {:?}, size: {:?}) from {} to {:}", name, &self.sha1, &self.size, &self.url, &library_path.display()));
 
-        download_file_untracked(&self.url, &library_path).await
-            .context("Failed to download library")?;
-        
-        // After downloading, check SHA1
-        if let Some(sha1) = &sha1 {
-            let hash = sha1sum(&library_path)
+        let mut attempt = 1;
+        loop {
+            download_file_untracked(&self.url, &library_path, download_options).await
+                .context("Failed to download library")?;
+
+            // After downloading, check SHA1
+            let Some(sha1) = &sha1 else {
+                break;
+            };
+            let hash = sha1sum_async(&library_path).await
                 .context("Failed to calculate SHA1 of library")?;
-            if hash != *sha1 {
-                anyhow::bail!("SHA1 of library {} does not match.", name);
+            if hash.eq_ignore_ascii_case(sha1) {
+                break;
+            }
+
+            // A corrupted file must not end up on the class path or be checked again on the next launch
+            fs::remove_file(&library_path).await
+                .context("Failed to remove library file")?;
+            if attempt >= LIBRARY_DOWNLOAD_ATTEMPTS {
+                return Err(LauncherError::ChecksumMismatch {
+                    expected: sha1.clone(),
+                    actual: hash,
+                    name: name.to_string(),
+                }.into());
             }
+
+            progress.log(&format!("SHA1 of library {} does not match, downloading again.", name));
+            attempt += 1;
         }
 
         Ok(library_path)
@@ -587,7 +990,267 @@ impl LibraryDownloadInfo {
 
 }
 
+/// A library with a mismatching checksum is downloaded once more before giving up
+const LIBRARY_DOWNLOAD_ATTEMPTS: u32 = 2;
+
 #[derive(Deserialize)]
 pub struct Logging {
     // TODO: Add logging configuration
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::utils::sha1_of;
+    use crate::utils::test_support::{ok_response, serve_responses, TempDir};
+
+    use super::*;
+
+    fn profile(json: serde_json::Value) -> VersionProfile {
+        serde_json::from_value(json).unwrap()
+    }
+
+    /// Values of the arguments without any rules
+    fn plain_values(arguments: &[Argument]) -> Vec<&str> {
+        arguments.iter()
+            .filter(|argument| argument.rules.is_none())
+            .filter_map(|argument| match &argument.value {
+                ArgumentValue::SINGLE(value) => Some(value.as_str()),
+                ArgumentValue::VEC(_) => None,
+            })
+            .collect()
+    }
+
+    fn structured(profile: &VersionProfile) -> &Arguments {
+        match &profile.arguments {
+            ArgumentDeclaration::V21(decl) => &decl.arguments,
+            ArgumentDeclaration::V14(_) => panic!("merged arguments are not structured"),
+        }
+    }
+
+    #[test]
+    fn merges_legacy_forge_profile_into_vanilla() {
+        let mut forge = profile(json!({
+            "id": "1.12.2-forge-14.23.5.2859",
+            "inheritsFrom": "1.12.2",
+            "type": "release",
+            "mainClass": "net.minecraft.launchwrapper.Launch",
+            "libraries": [],
+            "minecraftArguments": "--username ${auth_player_name} --version ${version_name} --gameDir ${game_directory} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --uuid ${auth_uuid} --accessToken ${auth_access_token} --userType ${user_type} --tweakClass net.minecraftforge.fml.common.launcher.FMLTweaker --versionType Forge"
+        }));
+        let vanilla = profile(json!({
+            "id": "1.12.2",
+            "type": "release",
+            "mainClass": "net.minecraft.client.main.Main",
+            "libraries": [],
+            "minecraftArguments": "--username ${auth_player_name} --version ${version_name} --gameDir ${game_directory} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --uuid ${auth_uuid} --accessToken ${auth_access_token} --userType ${user_type} --versionType ${version_type}"
+        }));
+
+        forge.merge(vanilla, LibraryConflictResolution::Highest).unwrap();
+
+        let arguments = structured(&forge);
+        let game = plain_values(&arguments.game);
+        assert_eq!(game.iter().filter(|x| **x == "--username").count(), 1);
+        assert_eq!(game.iter().filter(|x| **x == "--versionType").count(), 1);
+        assert!(game.windows(2).any(|x| x == ["--versionType", "Forge"]));
+        assert!(game.windows(2).any(|x| x == ["--tweakClass", "net.minecraftforge.fml.common.launcher.FMLTweaker"]));
+        assert_eq!(plain_values(&arguments.jvm), LEGACY_JVM_ARGUMENTS);
+        assert_eq!(forge.main_class.as_deref(), Some("net.minecraft.launchwrapper.Launch"));
+    }
+
+    #[test]
+    fn merges_fabric_profile_keeping_repeatable_options() {
+        let mut fabric = profile(json!({
+            "id": "fabric-loader-0.15.11-1.20.4",
+            "inheritsFrom": "1.20.4",
+            "type": "release",
+            "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+            "libraries": [],
+            "arguments": {
+                "game": [],
+                "jvm": ["-DFabricMcEmu= net.minecraft.client.main.Main ", "--add-opens", "java.base/java.lang=ALL-UNNAMED"]
+            }
+        }));
+        let vanilla = profile(json!({
+            "id": "1.20.4",
+            "type": "release",
+            "mainClass": "net.minecraft.client.main.Main",
+            "libraries": [],
+            "arguments": {
+                "game": [
+                    "--username", "${auth_player_name}",
+                    "--version", "${version_name}",
+                    {
+                        "rules": [{ "action": "allow", "features": { "has_custom_resolution": true } }],
+                        "value": ["--width", "${resolution_width}", "--height", "${resolution_height}"]
+                    }
+                ],
+                "jvm": [
+                    {
+                        "rules": [{ "action": "allow", "os": { "name": "osx" } }],
+                        "value": ["-XstartOnFirstThread"]
+                    },
+                    "--add-opens", "java.base/java.util=ALL-UNNAMED",
+                    "-Djava.library.path=${natives_directory}",
+                    "-cp", "${classpath}"
+                ]
+            }
+        }));
+
+        fabric.merge(vanilla, LibraryConflictResolution::Highest).unwrap();
+
+        let arguments = structured(&fabric);
+        assert_eq!(plain_values(&arguments.game), ["--username", "${auth_player_name}", "--version", "${version_name}"]);
+        assert_eq!(arguments.game.iter().filter(|argument| argument.rules.is_some()).count(), 1);
+
+        let jvm = plain_values(&arguments.jvm);
+        assert!(jvm.windows(2).any(|x| x == ["--add-opens", "java.base/java.lang=ALL-UNNAMED"]));
+        assert!(jvm.windows(2).any(|x| x == ["--add-opens", "java.base/java.util=ALL-UNNAMED"]));
+        assert!(jvm.windows(2).any(|x| x == ["-cp", "${classpath}"]));
+        assert_eq!(arguments.jvm.iter().filter(|argument| argument.rules.is_some()).count(), 1);
+        assert_eq!(fabric.main_class.as_deref(), Some("net.fabricmc.loader.impl.launch.knot.KnotClient"));
+    }
+
+    fn profile_with_libraries(id: &str, libraries: &[&str]) -> VersionProfile {
+        profile(json!({
+            "id": id,
+            "type": "release",
+            "libraries": libraries.iter().map(|name| json!({ "name": name })).collect::<Vec<_>>(),
+            "arguments": {}
+        }))
+    }
+
+    fn library_names(profile: &VersionProfile) -> Vec<&str> {
+        profile.libraries.iter().map(|library| library.name.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_newer_guava_of_parent() {
+        let mut child = profile_with_libraries("child", &["com.google.guava:guava:17.0", "net.fabricmc:fabric-loader:0.15.11"]);
+        let parent = profile_with_libraries("parent", &["com.google.guava:guava:31.1-jre", "org.lwjgl:lwjgl:3.3.1"]);
+
+        child.merge(parent, LibraryConflictResolution::Highest).unwrap();
+
+        assert_eq!(library_names(&child), ["com.google.guava:guava:31.1-jre", "net.fabricmc:fabric-loader:0.15.11", "org.lwjgl:lwjgl:3.3.1"]);
+    }
+
+    #[test]
+    fn keeps_newer_guava_of_child() {
+        let mut child = profile_with_libraries("child", &["com.google.guava:guava:31.1-jre"]);
+        let parent = profile_with_libraries("parent", &["com.google.guava:guava:17.0"]);
+
+        child.merge(parent, LibraryConflictResolution::Highest).unwrap();
+
+        assert_eq!(library_names(&child), ["com.google.guava:guava:31.1-jre"]);
+    }
+
+    #[test]
+    fn keeps_guava_of_child_if_configured() {
+        let mut child = profile_with_libraries("child", &["com.google.guava:guava:17.0"]);
+        let parent = profile_with_libraries("parent", &["com.google.guava:guava:31.1-jre"]);
+
+        child.merge(parent, LibraryConflictResolution::Child).unwrap();
+
+        assert_eq!(library_names(&child), ["com.google.guava:guava:17.0"]);
+    }
+
+    #[test]
+    fn keeps_libraries_with_different_classifiers() {
+        let mut child = profile_with_libraries("child", &["org.lwjgl:lwjgl:3.3.1"]);
+        let parent = profile_with_libraries("parent", &["org.lwjgl:lwjgl:3.3.1:natives-linux"]);
+
+        child.merge(parent, LibraryConflictResolution::Highest).unwrap();
+
+        assert_eq!(library_names(&child), ["org.lwjgl:lwjgl:3.3.1", "org.lwjgl:lwjgl:3.3.1:natives-linux"]);
+    }
+
+    fn version_manifest() -> VersionManifest {
+        let version = |id: &str, version_type: &str| serde_json::json!({
+            "id": id, "type": version_type, "url": "", "time": "", "releaseTime": "",
+        });
+        serde_json::from_value(serde_json::json!({
+            "latest": { "release": "1.20.4", "snapshot": "24w07a" },
+            "versions": [
+                version("24w07a", "snapshot"),
+                version("1.20.4", "release"),
+                version("1.20.3", "release"),
+                version("1.8.9", "release"),
+                version("b1.7.3", "old_beta"),
+            ],
+        })).unwrap()
+    }
+
+    #[test]
+    fn finds_versions_of_any_type() {
+        let manifest = version_manifest();
+
+        assert_eq!(manifest.get_by_id("b1.7.3").unwrap().version_type, "old_beta");
+        assert_eq!(manifest.latest_release().unwrap().id, "1.20.4");
+        assert_eq!(manifest.latest_snapshot().unwrap().id, "24w07a");
+    }
+
+    #[test]
+    fn suggests_similar_and_latest_versions() {
+        let error = version_manifest().get_by_id("1.8.8").err().unwrap();
+
+        let Some(LauncherError::UnknownVersion { suggestions, .. }) = error.downcast_ref::<LauncherError>() else {
+            panic!("{:?}", error);
+        };
+        assert_eq!(suggestions, &["1.8.9", "1.20.4", "1.20.3", "24w07a"]);
+    }
+
+    struct NoProgress;
+
+    impl ProgressReceiver for NoProgress {
+        fn progress_update(&self, _: ProgressUpdate) {}
+        fn log(&self, _: &str) {}
+    }
+
+    /// Serves the bodies one per connection in order, like a mirror that keeps returning corrupted files
+    async fn download_library(name: &str, sha1: Option<String>, bodies: Vec<&'static [u8]>) -> (Result<PathBuf>, TempDir) {
+        let libraries_folder = TempDir::new(name);
+        let info = LibraryDownloadInfo {
+            path: "org/example/library/1.0/library-1.0.jar".to_string(),
+            sha1,
+            size: Some(7),
+            url: serve_responses(bodies.into_iter().map(ok_response).collect(), "library.jar").await,
+        };
+        let options = DownloadOptions { max_attempts: 1, allow_insecure: true, ..Default::default() };
+
+        let result = info.download("org.example:library:1.0", libraries_folder.path().to_path_buf(), &options, &NoProgress).await;
+        (result, libraries_folder)
+    }
+
+    #[tokio::test]
+    async fn downloads_library_again_if_sha1_does_not_match() {
+        let (result, _libraries_folder) = download_library("library-retry", Some(sha1_of(b"library")), vec![b"corrupt", b"library"]).await;
+
+        let library_path = result.unwrap();
+        assert_eq!(std::fs::read(library_path).unwrap(), b"library");
+    }
+
+    #[tokio::test]
+    async fn fails_if_library_sha1_keeps_mismatching() {
+        let (result, libraries_folder) = download_library("library-mismatch", Some(sha1_of(b"library")), vec![b"corrupt", b"corrupt"]).await;
+
+        let error = result.unwrap_err();
+        let Some(LauncherError::ChecksumMismatch { expected, actual, .. }) = error.downcast_ref::<LauncherError>() else {
+            panic!("{:?}", error);
+        };
+        assert_eq!(expected, &sha1_of(b"library"));
+        assert_eq!(actual, &sha1_of(b"corrupt"));
+        // The corrupted file must not be used by the next launch
+        assert!(!libraries_folder.join("org/example/library/1.0/library-1.0.jar").exists());
+    }
+
+    #[tokio::test]
+    async fn accepts_sha1_file_with_newline_and_uppercase_digits() {
+        // The .sha1 file is requested first, then the library itself
+        let sha1_file = b"00299A408DC3498A3CD7BAE6DB588F3324654D76\n";
+        let (result, _libraries_folder) = download_library("library-sha1-file", None, vec![sha1_file, b"library"]).await;
+
+        let library_path = result.unwrap();
+        assert_eq!(std::fs::read(&library_path).unwrap(), b"library");
+        assert_eq!(std::fs::read_to_string(library_path.with_extension("sha1")).unwrap(), "00299A408DC3498A3CD7BAE6DB588F3324654D76");
+    }
+}
@@ -17,9 +17,16 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
  
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use sha1::{Sha1, Digest};
-use std::path::PathBuf;
+use sha2::Sha256;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Chunks queued for the hashing thread before the download has to wait for it
+const STREAMING_HASH_BUFFER: usize = 32;
 
 pub fn sha1sum(path: &PathBuf) -> Result<String> {
     // get sha1 of library file and check if it matches
@@ -30,4 +37,140 @@ pub fn sha1sum(path: &PathBuf) -> Result<String> {
     let hex_hash = base16ct::lower::encode_string(&hash);
 
     Ok(hex_hash)
-}
\ No newline at end of file
+}
+
+pub fn sha1_of(data: &[u8]) -> String {
+    base16ct::lower::encode_string(&Sha1::digest(data))
+}
+
+pub fn sha256sum(path: &PathBuf) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    let hash = hasher.finalize();
+    let hex_hash = base16ct::lower::encode_string(&hash);
+
+    Ok(hex_hash)
+}
+
+/// Hashes the file on a blocking thread, so large files do not stall the downloads running meanwhile
+pub async fn sha1sum_async(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || sha1sum(&path)).await?
+}
+
+/// Hashes the file on a blocking thread like [sha1sum_async]
+pub async fn sha256sum_async(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || sha256sum(&path)).await?
+}
+
+///
+/// SHA-1 of a download, computed on a blocking thread while its chunks arrive
+///
+pub struct StreamingSha1 {
+    sender: mpsc::Sender<Vec<u8>>,
+    hasher: JoinHandle<Result<String>>,
+}
+
+impl StreamingSha1 {
+
+    /// Starts hashing, a resumed download hashes the first `len` bytes already written to the file first
+    pub fn new(resumed: Option<(PathBuf, u64)>) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<Vec<u8>>(STREAMING_HASH_BUFFER);
+        let hasher = tokio::task::spawn_blocking(move || {
+            let mut hasher = Sha1::new();
+            if let Some((path, len)) = resumed {
+                let file = std::fs::File::open(&path)?;
+                let hashed = std::io::copy(&mut file.take(len), &mut hasher)?;
+                if hashed != len {
+                    return Err(anyhow!("{} is shorter than the resumed {} bytes", path.display(), len));
+                }
+            }
+
+            while let Some(chunk) = receiver.blocking_recv() {
+                hasher.update(&chunk);
+            }
+            Ok(base16ct::lower::encode_string(&hasher.finalize()))
+        });
+
+        Self { sender, hasher }
+    }
+
+    pub async fn update(&self, chunk: Vec<u8>) -> Result<()> {
+        if self.sender.send(chunk).await.is_err() {
+            return Err(anyhow!("Hashing of the download stopped unexpectedly"));
+        }
+        Ok(())
+    }
+
+    /// Waits for the remaining chunks to be hashed
+    pub async fn finish(self) -> Result<String> {
+        drop(self.sender);
+        self.hasher.await?
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::utils::test_support::TempDir;
+
+    use super::*;
+
+    /// How often a task on the same single threaded runtime has run while `work` was in flight.
+    /// Hashing on the runtime itself would keep the task from running until the hash is done.
+    async fn ticks_during<F: std::future::Future>(work: F) -> (F::Output, usize) {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    tokio::task::yield_now().await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        let output = work.await;
+        let ticks = ticks.load(Ordering::SeqCst);
+        ticker.abort();
+        (output, ticks)
+    }
+
+    fn data() -> Vec<u8> {
+        (0..4 * 1024 * 1024).map(|x: u32| x as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn hashing_a_file_does_not_block_the_runtime() {
+        let data = data();
+        let directory = TempDir::new("hash");
+        let path = directory.join("data.bin");
+        std::fs::write(&path, &data).unwrap();
+
+        let (hash, ticks) = ticks_during(sha1sum_async(&path)).await;
+
+        assert_eq!(hash.unwrap(), sha1_of(&data));
+        assert!(ticks > 0, "the runtime was blocked while hashing");
+    }
+
+    #[tokio::test]
+    async fn streaming_hash_does_not_block_the_runtime() {
+        let data = data();
+
+        let (hash, ticks) = ticks_during(async {
+            let hasher = StreamingSha1::new(None);
+            for chunk in data.chunks(64 * 1024) {
+                hasher.update(chunk.to_vec()).await?;
+            }
+            hasher.finish().await
+        }).await;
+
+        assert_eq!(hash.unwrap(), sha1_of(&data));
+        assert!(ticks > 0, "the runtime was blocked while hashing");
+    }
+}
@@ -17,48 +17,669 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
  
-use std::path::Path;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use tokio::fs;
-use tracing::debug;
+use reqwest::header::{HeaderName, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE, USER_AGENT};
+use reqwest::{Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{sleep, timeout};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 use anyhow::Result;
 
+use crate::error::{is_retryable_io_error, is_retryable_network_error, LauncherError};
+use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
+use crate::utils::{sha1sum_async, StreamingSha1};
 use crate::HTTP_CLIENT;
 
+/// How often transfer statistics are being reported
+const STATS_INTERVAL: Duration = Duration::from_millis(250);
+/// Time span the transfer rate is averaged over
+const RATE_WINDOW: Duration = Duration::from_secs(3);
+
+/// Controls how downloads are being retried and when they are cancelled
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Maximum amount of attempts per download
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubles with every further attempt
+    pub retry_delay: Duration,
+    /// Stops the download as soon as it is cancelled
+    pub cancellation: CancellationToken,
+    /// Maximum time to wait for a response or the next chunk of data, before the attempt is considered failed
+    pub idle_timeout: Duration,
+    /// Allows downloading over plain HTTP, which could be tampered with
+    pub allow_insecure: bool,
+    /// Replaces the user agent of the shared client for these downloads
+    pub user_agent: Option<String>,
+    /// Limits the throughput of all downloads sharing the throttle
+    pub throttle: Option<Arc<DownloadThrottle>>,
+    /// Limits the simultaneous connections to a single host
+    pub connection_limit: Option<Arc<HostConnectionLimit>>,
+    /// Hosts the download has to end up on after following redirects, including their subdomains
+    pub allowed_hosts: Option<Vec<String>>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(500),
+            cancellation: CancellationToken::new(),
+            idle_timeout: Duration::from_secs(30),
+            allow_insecure: false,
+            user_agent: None,
+            throttle: None,
+            connection_limit: None,
+            allowed_hosts: None,
+        }
+    }
+}
+
+/// Token bucket limiting the aggregate throughput of concurrent downloads
+#[derive(Debug)]
+pub struct DownloadThrottle {
+    max_bytes_per_sec: f64,
+    bucket: Mutex<TokenBucket>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    /// Might become negative if a chunk is larger than the available tokens
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl DownloadThrottle {
+    pub fn new(max_bytes_per_sec: u64) -> Self {
+        let max_bytes_per_sec = max_bytes_per_sec.max(1) as f64;
+        Self {
+            max_bytes_per_sec,
+            bucket: Mutex::new(TokenBucket { tokens: max_bytes_per_sec, last_refill: Instant::now() }),
+        }
+    }
+
+    /// Takes the bytes from the bucket and waits until the bucket is no longer in debt
+    async fn acquire(&self, bytes: u64, cancellation: &CancellationToken) -> Result<()> {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+
+            // At most one second of bandwidth is saved up, so bursts stay short
+            let refill = now.duration_since(bucket.last_refill).as_secs_f64() * self.max_bytes_per_sec;
+            bucket.tokens = (bucket.tokens + refill).min(self.max_bytes_per_sec) - bytes as f64;
+            bucket.last_refill = now;
+
+            (bucket.tokens < 0.0).then(|| Duration::from_secs_f64(-bucket.tokens / self.max_bytes_per_sec))
+        };
+
+        if let Some(wait) = wait {
+            tokio::select! {
+                _ = sleep(wait) => {},
+                _ = cancellation.cancelled() => return Err(LauncherError::Cancelled.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hosts of Mojang, which are built for many simultaneous connections
+const UNLIMITED_HOSTS: [&str; 4] = [
+    "resources.download.minecraft.net",
+    "libraries.minecraft.net",
+    "piston-data.mojang.com",
+    "launcher.mojang.com",
+];
+
+/// Limits the simultaneous downloads from every host, the pool of the shared client only limits idle connections
+#[derive(Debug)]
+pub struct HostConnectionLimit {
+    max_per_host: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostConnectionLimit {
+    pub fn new(max_per_host: usize) -> Self {
+        Self {
+            max_per_host: max_per_host.max(1),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits for a free connection to the host of the url, which is held as long as the permit is
+    async fn acquire(&self, url: &str, cancellation: &CancellationToken) -> Result<Option<OwnedSemaphorePermit>> {
+        let Some(host) = Url::parse(url.trim()).ok().and_then(|url| url.host_str().map(|host| host.to_lowercase())) else {
+            return Ok(None);
+        };
+        if UNLIMITED_HOSTS.contains(&host.as_str()) {
+            return Ok(None);
+        }
+
+        let semaphore = self.hosts.lock().unwrap_or_else(|e| e.into_inner())
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_host)))
+            .clone();
+
+        tokio::select! {
+            permit = semaphore.acquire_owned() => Ok(Some(permit?)),
+            _ = cancellation.cancelled() => Err(LauncherError::Cancelled.into()),
+        }
+    }
+}
+
+///
+/// Validators of a downloaded file, which allow asking the server whether the file has changed since
+///
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheValidators {
+    /// The validators only apply to the URL they have been received from
+    pub url: String,
+    pub etag: Option<String>,
+    #[serde(rename = "lastModified")]
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+
+    fn from_response(url: &str, response: &Response) -> Self {
+        let header = |name: HeaderName| response.headers().get(name)
+            .and_then(|x| x.to_str().ok())
+            .map(|x| x.to_string());
+
+        Self {
+            url: url.to_string(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// Loads the validators stored next to the file, none if there are none or the file is missing
+    pub async fn load(path: &Path) -> Option<Self> {
+        if !path.exists() {
+            return None;
+        }
+
+        let content = fs::read(validators_path(path)).await.ok()?;
+        serde_json::from_slice::<Self>(&content).ok()
+    }
+
+    /// Stores the validators next to the file, a server without validators removes the previous ones
+    pub async fn store(&self, path: &Path) -> Result<()> {
+        let validators_path = validators_path(path);
+        if self.is_empty() {
+            let _ = fs::remove_file(&validators_path).await;
+            return Ok(());
+        }
+
+        fs::write(&validators_path, serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+
+}
+
+fn validators_path(path: &Path) -> PathBuf {
+    let mut validators_path = path.as_os_str().to_owned();
+    validators_path.push(".meta");
+    PathBuf::from(validators_path)
+}
+
+/// Outcome of a single download
+enum Transfer {
+    /// The server confirmed that the file has not changed since the validators were received
+    NotModified,
+    Complete { sha1: Option<String>, validators: CacheValidators },
+}
+
+impl Transfer {
+    fn sha1(self) -> Option<String> {
+        match self {
+            Transfer::Complete { sha1, .. } => sha1,
+            Transfer::NotModified => None,
+        }
+    }
+}
+
 /// Download file using HTTP_CLIENT without any progress tracking
-pub async fn download_file_untracked(url: &str, path: impl AsRef<Path>) -> Result<()> {
-    let path = path.as_ref().to_owned();
-    let response = HTTP_CLIENT.get(url)
-        .send().await?
-        .error_for_status()?;
-    
-    let content = response.bytes().await?;
-    fs::write(path, content).await?;
+pub async fn download_file_untracked(url: &str, path: impl AsRef<Path>, options: &DownloadOptions) -> Result<()> {
+    download_resumable(url, path.as_ref(), options, false, None, &|_, _| {}, &|_| {}).await?;
     Ok(())
 }
 
-pub async fn download_file<F>(url: &str, on_progress: F) -> Result<Vec<u8>> where F : Fn(u64, u64) {
+/// Identifies the statistics of a transfer among the concurrently running ones
+static NEXT_TRANSFER: AtomicU64 = AtomicU64::new(0);
+
+/// Averages the transfer rate over a short window of samples, so the reported speed does not jitter.
+/// Reports the transfer as finished once dropped, even if it failed.
+struct TransferStats<'a> {
+    transfer: u64,
+    samples: VecDeque<(Instant, u64)>,
+    last_report: Option<Instant>,
+    on_stats: &'a dyn Fn(ProgressUpdate),
+}
+
+impl<'a> TransferStats<'a> {
+    fn new(on_stats: &'a dyn Fn(ProgressUpdate)) -> Self {
+        let transfer = NEXT_TRANSFER.fetch_add(1, Ordering::Relaxed);
+        Self { transfer, samples: VecDeque::new(), last_report: None, on_stats }
+    }
+
+    /// Records the amount of bytes done so far and reports the stats once per [STATS_INTERVAL]
+    fn record(&mut self, bytes_done: u64, bytes_total: u64) {
+        let now = Instant::now();
+        self.samples.push_back((now, bytes_done));
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= RATE_WINDOW {
+            self.samples.pop_front();
+        }
+
+        if self.last_report.is_some_and(|last| now.duration_since(last) < STATS_INTERVAL) {
+            return;
+        }
+        self.last_report = Some(now);
+
+        let (first_time, first_bytes) = self.samples[0];
+        let elapsed = now.duration_since(first_time).as_secs_f64();
+        let bytes_per_sec = if elapsed > 0.0 {
+            (bytes_done.saturating_sub(first_bytes) as f64 / elapsed) as u64
+        } else {
+            0
+        };
+
+        (self.on_stats)(ProgressUpdate::TransferStats {
+            transfer: self.transfer, bytes_done, bytes_total, bytes_per_sec, finished: false,
+        });
+    }
+}
+
+impl Drop for TransferStats<'_> {
+    fn drop(&mut self) {
+        if self.last_report.is_some() {
+            (self.on_stats)(ProgressUpdate::TransferStats {
+                transfer: self.transfer, bytes_done: 0, bytes_total: 0, bytes_per_sec: 0, finished: true,
+            });
+        }
+    }
+}
+
+/// Download file to the given path.
+/// 
+/// The data is written to `{path}.part` first and only moved to the path once the download is complete.
+/// If a previous download has been interrupted, it continues from where it stopped.
+/// Transfer statistics are reported to the progress receiver while downloading.
+pub async fn download_file_resumable<F>(url: &str, path: impl AsRef<Path>, options: &DownloadOptions, progress: &impl ProgressReceiver, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
+    download_resumable(url, path.as_ref(), options, false, None, &on_progress, &|stats| progress.progress_update(stats)).await?;
+    Ok(())
+}
+
+/// Download file like [download_file_resumable] and return its SHA-1,
+/// which is computed while downloading instead of reading the file again afterwards
+pub async fn download_file_hashed<F>(url: &str, path: impl AsRef<Path>, options: &DownloadOptions, progress: &impl ProgressReceiver, on_progress: F) -> Result<String> where F : Fn(u64, u64) {
+    let transfer = download_resumable(url, path.as_ref(), options, true, None, &on_progress, &|stats| progress.progress_update(stats)).await?;
+    transfer.sha1().ok_or_else(|| anyhow::anyhow!("No hash has been computed for {}", url))
+}
+
+async fn download_resumable(url: &str, path: &Path, options: &DownloadOptions, hash: bool, cached: Option<&CacheValidators>, on_progress: &dyn Fn(u64, u64), on_stats: &dyn Fn(ProgressUpdate)) -> Result<Transfer> {
     debug!("Downloading file {:?}", url);
 
-    let mut response = HTTP_CLIENT.get(url.trim())
-        .send().await?
-        .error_for_status()?;
+    let part_path = part_path(path);
+
+    if let Some(source) = local_file_path(url) {
+        copy_local_file(&source, &part_path, on_progress).await?;
+        let sha1 = match hash {
+            true => Some(sha1sum_async(&part_path).await?),
+            false => None,
+        };
+        fs::rename(&part_path, path).await?;
 
-    debug!("Response received from url");
+        debug!("Copied local file");
+        return Ok(Transfer::Complete { sha1, validators: CacheValidators::default() });
+    }
 
-    let max_len = response.content_length().unwrap_or(0);
-    let mut output = Vec::with_capacity(max_len as usize);
-    let mut curr_len = 0;
+    let transfer = with_retry(url, options, || download_part_attempt(url, &part_path, options, hash, cached, on_progress, on_stats)).await?;
+    match transfer {
+        Transfer::NotModified => debug!("File has not been modified"),
+        Transfer::Complete { .. } => {
+            fs::rename(&part_path, path).await?;
+            debug!("Downloaded file");
+        }
+    }
+    Ok(transfer)
+}
 
-    on_progress(0, max_len);
+/// Download file to the given path from the first mirror that works, the mirrors are tried in order.
+///
+/// The download is skipped if the mirror the cached validators have been received from responds that the file
+/// has not been modified since. Returns the validators of the new download, none if it has not been modified.
+pub async fn download_file_from_mirrors_if_modified<F>(urls: &[String], path: impl AsRef<Path>, cached: Option<&CacheValidators>, options: &DownloadOptions, progress: &impl ProgressReceiver, on_progress: F) -> Result<Option<CacheValidators>> where F : Fn(u64, u64) {
+    let path = path.as_ref();
+    let mut last_error = None;
 
-    debug!("Reading data from response chunk...");
-    while let Some(data) = response.chunk().await? {
-        output.extend_from_slice(&data);
-        curr_len += data.len();
-        on_progress(curr_len as u64, max_len);
+    for url in urls {
+        let cached = cached.filter(|cached| cached.url == *url);
+        match download_resumable(url, path, options, false, cached, &on_progress, &|stats| progress.progress_update(stats)).await {
+            Ok(Transfer::NotModified) => return Ok(None),
+            Ok(Transfer::Complete { validators, .. }) => return Ok(Some(validators)),
+            Err(e) if is_cancelled(&e) => return Err(e),
+            Err(e) => {
+                warn!("Download from mirror {} failed: {:?}", url, e);
+                last_error = Some(e);
+            }
+        }
     }
 
-    debug!("Downloaded file");
-    Ok(output)
-}
\ No newline at end of file
+    let error = LauncherError::AllMirrorsFailed { urls: urls.to_vec() };
+    Err(match last_error {
+        Some(e) => e.context(error),
+        None => error.into(),
+    })
+}
+
+async fn download_part_attempt(url: &str, part_path: &Path, options: &DownloadOptions, hash: bool, cached: Option<&CacheValidators>, on_progress: &dyn Fn(u64, u64), on_stats: &dyn Fn(ProgressUpdate)) -> Result<Transfer> {
+    let _connection = match &options.connection_limit {
+        Some(connection_limit) => connection_limit.acquire(url, &options.cancellation).await?,
+        None => None,
+    };
+
+    let mut offset = match fs::metadata(part_path).await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+
+    // A partial download has already been started for a changed file, so it is not revalidated
+    let cached = cached.filter(|_| offset == 0);
+    let mut response = request_from(url, offset, cached, options).await?;
+    if offset > 0 && !resumes_at(&response, offset) {
+        // The server ignored or could not satisfy the range, start from scratch
+        debug!("Unable to resume download of {} at {} bytes, starting over", url, offset);
+        if response.status() != StatusCode::OK {
+            response = request_from(url, 0, None, options).await?;
+        }
+        offset = 0;
+    }
+    if cached.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Transfer::NotModified);
+    }
+    let validators = CacheValidators::from_response(url, &response);
+    let mut response = response.error_for_status().map_err(LauncherError::Network)?;
+
+    let mut file = if offset > 0 {
+        OpenOptions::new().append(true).open(part_path).await?
+    } else {
+        fs::File::create(part_path).await?
+    };
+
+    // Hashed on a blocking thread, so the download and the progress updates keep going meanwhile
+    let hasher = hash.then(|| StreamingSha1::new((offset > 0).then(|| (part_path.to_path_buf(), offset))));
+
+    let expected_len = response.content_length().map(|len| len + offset);
+    let max_len = expected_len.unwrap_or(0);
+    let mut curr_len = offset;
+    let mut stats = TransferStats::new(on_stats);
+
+    on_progress(curr_len, max_len);
+    stats.record(curr_len, max_len);
+
+    while let Some(data) = next_chunk(&mut response, options).await.map_err(|e| truncated(e, expected_len, curr_len))? {
+        if let Some(throttle) = &options.throttle {
+            throttle.acquire(data.len() as u64, &options.cancellation).await?;
+        }
+        file.write_all(&data).await?;
+        curr_len += data.len() as u64;
+        if let Some(hasher) = &hasher {
+            hasher.update(data).await?;
+        }
+        on_progress(curr_len, max_len);
+        stats.record(curr_len, max_len);
+    }
+    file.flush().await?;
+
+    // The connection might have been closed early, the part file is resumed by the next attempt
+    if let Some(expected) = expected_len.filter(|expected| *expected != curr_len) {
+        return Err(LauncherError::IncompleteDownload { expected, received: curr_len }.into());
+    }
+
+    let sha1 = match hasher {
+        Some(hasher) => Some(hasher.finish().await?),
+        None => None,
+    };
+    Ok(Transfer::Complete { sha1, validators })
+}
+
+/// A connection closed before the announced length has been received fails reading the body,
+/// which is reported as an incomplete download if the length is known
+fn truncated(error: anyhow::Error, expected_len: Option<u64>, received: u64) -> anyhow::Error {
+    let is_body_error = matches!(
+        error.downcast_ref::<LauncherError>(),
+        Some(LauncherError::Network(e)) if e.is_body() || e.is_decode()
+    );
+
+    match expected_len {
+        Some(expected) if is_body_error => LauncherError::IncompleteDownload { expected, received }.into(),
+        _ => error,
+    }
+}
+
+/// Reads the next chunk of the response, unless the download has been cancelled or stalled in the meantime
+async fn next_chunk(response: &mut Response, options: &DownloadOptions) -> Result<Option<Vec<u8>>> {
+    tokio::select! {
+        chunk = timeout(options.idle_timeout, response.chunk()) => match chunk {
+            Ok(chunk) => Ok(chunk.map_err(LauncherError::Network)?.map(|data| data.to_vec())),
+            Err(_) => Err(LauncherError::DownloadTimeout { secs: options.idle_timeout.as_secs() }.into()),
+        },
+        _ = options.cancellation.cancelled() => Err(LauncherError::Cancelled.into()),
+    }
+}
+
+/// Request the url, starting at the given byte offset. The request is conditional if validators are given.
+async fn request_from(url: &str, offset: u64, cached: Option<&CacheValidators>, options: &DownloadOptions) -> Result<Response> {
+    ensure_secure_url(url, options.allow_insecure)?;
+
+    let mut request = HTTP_CLIENT.get(url.trim());
+    if let Some(user_agent) = &options.user_agent {
+        request = request.header(USER_AGENT, user_agent);
+    }
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    // Only waiting for the response is limited here, the body is covered by the idle timeout of every chunk
+    let response: Response = tokio::select! {
+        response = timeout(options.idle_timeout, request.send()) => match response {
+            Ok(response) => response.map_err(LauncherError::Network)?,
+            Err(_) => return Err(LauncherError::DownloadTimeout { secs: options.idle_timeout.as_secs() }.into()),
+        },
+        _ = options.cancellation.cancelled() => return Err(LauncherError::Cancelled.into()),
+    };
+
+    let final_url = response.url();
+    if final_url.as_str() != url.trim() {
+        info!("Download of {} has been redirected to {}", url, final_url);
+    }
+    if let Some(allowed_hosts) = &options.allowed_hosts {
+        ensure_allowed_host(final_url, allowed_hosts)?;
+    }
+    Ok(response)
+}
+
+/// Checks the host of the URL, the trusted hosts include their subdomains
+fn ensure_allowed_host(url: &Url, allowed_hosts: &[String]) -> Result<()> {
+    let host = url.host_str().unwrap_or_default().to_lowercase();
+    let allowed = allowed_hosts.iter()
+        .map(|allowed| allowed.trim().to_lowercase())
+        .any(|allowed| host == allowed || host.ends_with(&format!(".{}", allowed)));
+
+    if !allowed {
+        return Err(LauncherError::UnexpectedDownloadHost {
+            url: url.to_string(),
+            host,
+            allowed: allowed_hosts.to_vec(),
+        }.into());
+    }
+    Ok(())
+}
+
+/// Size of the file behind the url according to the Content-Length of a HEAD request.
+/// Best effort only, any failure results in an unknown size.
+pub async fn fetch_content_length(url: &str, options: &DownloadOptions) -> Option<u64> {
+    ensure_secure_url(url, options.allow_insecure).ok()?;
+
+    let mut request = HTTP_CLIENT.head(url.trim());
+    if let Some(user_agent) = &options.user_agent {
+        request = request.header(USER_AGENT, user_agent);
+    }
+    let response = timeout(options.idle_timeout, request.send()).await.ok()?
+        .ok()?
+        .error_for_status()
+        .ok()?;
+
+    // The body of a HEAD response is empty, so the header has to be read directly
+    response.headers().get(CONTENT_LENGTH)?
+        .to_str().ok()?
+        .parse::<u64>().ok()
+        .filter(|len| *len > 0)
+}
+
+/// Rejects anything but HTTPS, unless insecure URLs are explicitly allowed
+pub fn ensure_secure_url(url: &str, allow_insecure: bool) -> Result<()> {
+    if allow_insecure {
+        return Ok(());
+    }
+
+    match Url::parse(url.trim()) {
+        Ok(parsed) if parsed.scheme() == "https" => Ok(()),
+        _ => Err(LauncherError::InsecureUrl { url: url.to_string() }.into()),
+    }
+}
+
+/// Path of a `file://` URL, which are copied from the local filesystem instead of being downloaded
+pub fn local_file_path(url: &str) -> Option<PathBuf> {
+    Url::parse(url.trim()).ok()
+        .filter(|parsed| parsed.scheme() == "file")
+        .and_then(|parsed| parsed.to_file_path().ok())
+}
+
+async fn copy_local_file(source: &Path, part_path: &Path, on_progress: &dyn Fn(u64, u64)) -> Result<()> {
+    if !source.is_file() {
+        return Err(LauncherError::LocalSourceNotFound { path: source.display().to_string() }.into());
+    }
+
+    let size = fs::copy(source, part_path).await?;
+    on_progress(size, size);
+    Ok(())
+}
+
+/// Checks that the response is partial content starting exactly at the offset
+fn resumes_at(response: &Response, offset: u64) -> bool {
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return false;
+    }
+
+    // Content-Range: bytes {start}-{end}/{total}
+    response.headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("bytes "))
+        .and_then(|value| value.split('-').next())
+        .and_then(|start| start.trim().parse::<u64>().ok())
+        .is_some_and(|start| start == offset)
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    PathBuf::from(part_path)
+}
+
+/// Runs the download attempt until it succeeds, fails with a non-retryable error or runs out of attempts
+async fn with_retry<T, F, Fut>(url: &str, options: &DownloadOptions, mut attempt: F) -> Result<T>
+    where F: FnMut() -> Fut, Fut: Future<Output = Result<T>> {
+    let max_attempts = options.max_attempts.max(1);
+    let mut attempts = 0;
+
+    loop {
+        if options.cancellation.is_cancelled() {
+            return Err(LauncherError::Cancelled.into());
+        }
+        attempts += 1;
+
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(e) if is_cancelled(&e) => return Err(e),
+            Err(e) if attempts < max_attempts && is_retryable(&e) => {
+                let delay = options.retry_delay.saturating_mul(2u32.saturating_pow(attempts - 1));
+                warn!("Download of {} failed (attempt {}/{}), retrying in {:?}: {:?}", url, attempts, max_attempts, delay, e);
+                tokio::select! {
+                    _ = sleep(delay) => {},
+                    _ = options.cancellation.cancelled() => return Err(LauncherError::Cancelled.into()),
+                }
+            }
+            Err(e) => return Err(e.context(LauncherError::DownloadFailed {
+                url: url.to_string(),
+                attempts,
+            })),
+        }
+    }
+}
+
+fn is_cancelled(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<LauncherError>(), Some(LauncherError::Cancelled))
+}
+
+/// Errors which have not been turned into a [LauncherError] are judged the same way
+fn is_retryable(error: &anyhow::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<LauncherError>() {
+        return error.is_retryable();
+    }
+    if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+        return is_retryable_network_error(error);
+    }
+    if let Some(error) = error.downcast_ref::<std::io::Error>() {
+        return is_retryable_io_error(error);
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::test_support::{serve_responses, TempDir};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn short_read_is_incomplete() {
+        // Announces more bytes than are sent before the connection is closed
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n0123456789".to_vec();
+        let url = serve_responses(vec![response], "file.jar").await;
+        let directory = TempDir::new("short-read");
+        let path = directory.join("file.jar");
+        let options = DownloadOptions { max_attempts: 1, allow_insecure: true, ..Default::default() };
+
+        let error = download_file_untracked(&url, &path, &options).await.unwrap_err();
+
+        let cause = error.root_cause().downcast_ref::<LauncherError>();
+        assert!(matches!(cause, Some(LauncherError::IncompleteDownload { expected: 100, received: 10 })), "{:?}", error);
+        assert!(cause.unwrap().is_retryable());
+        // Only the part file is left behind, to be resumed by the next attempt
+        assert!(!path.exists());
+    }
+}
@@ -30,11 +30,24 @@ use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, BufReader};
 /// Taken from https://github.com/Majored/rs-async-zip/blob/main/examples/file_extraction.rs
 pub async fn zip_extract<R>(archive: R, out_dir: &Path) -> Result<()>
     where R: AsyncRead + AsyncSeek + Unpin {
+    zip_extract_excluding(archive, out_dir, &[], |_, _, _| {}).await
+}
+
+/// Extracts everything from the ZIP archive to the output directory, except for entries starting with one of the excluded prefixes.
+/// Every extracted entry is reported with its index and the number of entries.
+pub async fn zip_extract_excluding<R, F>(archive: R, out_dir: &Path, exclude: &[String], on_entry: F) -> Result<()>
+    where R: AsyncRead + AsyncSeek + Unpin, F: Fn(&str, usize, usize) {
     let mut reader = ZipFileReader::new(archive).await?;
-    for index in 0..reader.file().entries().len() {
+    let count = reader.file().entries().len();
+    for index in 0..count {
         let entry = &reader.file().entries().get(index).unwrap().entry();
         let file_name = entry.filename();
 
+        if exclude.iter().any(|prefix| file_name.starts_with(prefix.as_str())) {
+            continue;
+        }
+        on_entry(file_name, index, count);
+
         let path = out_dir.join(sanitize_file_path(file_name));
         // If the filename of the entry ends with '/', it is treated as a directory.
         // This is implemented by previous versions of this crate and the Python Standard Library.
@@ -60,6 +73,7 @@ pub async fn zip_extract<R>(archive: R, out_dir: &Path) -> Result<()>
             let mut writer = OpenOptions::new()
                 .write(true)
                 .create(true)
+                .truncate(true)
                 .open(&path).await
                 .context("Failed to create extracted file")?;
             io::copy(&mut entry_reader, &mut writer).await?;
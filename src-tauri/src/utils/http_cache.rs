@@ -0,0 +1,121 @@
+/*
+ * This file is part of LiquidLauncher (https://github.com/CCBlueX/LiquidLauncher)
+ *
+ * Copyright (c) 2015 - 2024 CCBlueX
+ *
+ * LiquidLauncher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * LiquidLauncher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::{debug, warn};
+
+use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheMetadata {
+    etag: Option<String>,
+    /// Seconds since the unix epoch
+    fetched_at: u64,
+}
+
+/// Requests the url, the response is cached on disk under the given name.
+///
+/// The cached response is used without a request within the TTL, if the server responds that it has not
+/// been modified, or with a warning if the request fails.
+pub async fn get_cached(url: &str, name: &str, ttl: Duration) -> Result<Vec<u8>> {
+    request_cached(url, name, ttl, true).await
+}
+
+/// Like [get_cached], but a response is never written to the cache, e.g. during a dry run
+pub async fn get_cached_read_only(url: &str, name: &str, ttl: Duration) -> Result<Vec<u8>> {
+    request_cached(url, name, ttl, false).await
+}
+
+async fn request_cached(url: &str, name: &str, ttl: Duration, update_cache: bool) -> Result<Vec<u8>> {
+    let directory = LAUNCHER_DIRECTORY.cache_dir().join("http");
+    let content_path = directory.join(name);
+    let metadata_path = directory.join(format!("{}.meta", name));
+
+    let cached = fs::read(&content_path).await.ok();
+    let metadata = match fs::read(&metadata_path).await {
+        Ok(metadata) => serde_json::from_slice::<CacheMetadata>(&metadata).unwrap_or_default(),
+        Err(_) => CacheMetadata::default(),
+    };
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    if let Some(cached) = &cached {
+        if now.saturating_sub(metadata.fetched_at) < ttl.as_secs() {
+            debug!("Using cached {} of {}", name, url);
+            return Ok(cached.clone());
+        }
+    }
+
+    let mut request = HTTP_CLIENT.get(url);
+    if let (Some(etag), Some(_)) = (&metadata.etag, &cached) {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    let result = async {
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+
+        let response = response.error_for_status()?;
+        let etag = response.headers().get(ETAG)
+            .and_then(|x| x.to_str().ok())
+            .map(|x| x.to_string());
+        Ok::<_, anyhow::Error>(Some((response.bytes().await?.to_vec(), etag)))
+    }.await;
+
+    match (result, cached) {
+        (Ok(None), Some(cached)) => {
+            debug!("{} has not been modified", url);
+            if update_cache {
+                write_atomically(&metadata_path, &serde_json::to_vec(&CacheMetadata { etag: metadata.etag, fetched_at: now })?).await?;
+            }
+            Ok(cached)
+        }
+        (Ok(Some((content, _))), _) if !update_cache => Ok(content),
+        (Ok(Some((content, etag))), _) => {
+            fs::create_dir_all(&directory).await?;
+            write_atomically(&content_path, &content).await?;
+            write_atomically(&metadata_path, &serde_json::to_vec(&CacheMetadata { etag, fetched_at: now })?).await?;
+            Ok(content)
+        }
+        (Err(e), Some(cached)) => {
+            warn!("Request of {} failed, using the cached response: {:?}", url, e);
+            Ok(cached)
+        }
+        (Ok(None), None) => Err(anyhow::anyhow!("{} has not been modified, but there is no cached response", url)),
+        (Err(e), None) => Err(e),
+    }
+}
+
+/// Writes to a temporary file first, so an interrupted write never leaves a truncated cache behind
+async fn write_atomically(path: &Path, content: &[u8]) -> Result<()> {
+    let mut temporary = PathBuf::from(path.as_os_str());
+    temporary.set_extension("tmp");
+
+    fs::write(&temporary, content).await?;
+    fs::rename(&temporary, path).await?;
+    Ok(())
+}
@@ -17,15 +17,150 @@
  * along with LiquidLauncher. If not, see <https://www.gnu.org/licenses/>.
  */
  
+use std::cmp::Ordering;
+
 use crate::error::LauncherError;
 use anyhow::Result;
 
-pub fn get_maven_artifact_path(artifact_id: &String) -> Result<String> {
-    let split = artifact_id.split(':').collect::<Vec<_>>();
+/// Parsed `group:artifact:version[:classifier][@extension]` coordinate, the extension defaults to `jar`
+struct MavenCoordinate<'a> {
+    group: &'a str,
+    artifact: &'a str,
+    version: &'a str,
+    classifier: Option<&'a str>,
+    extension: &'a str,
+}
+
+impl<'a> MavenCoordinate<'a> {
+
+    fn parse(artifact_id: &'a str) -> Result<Self> {
+        let invalid = |reason: &str| LauncherError::Validation(format!(
+            "Invalid artifact name {}: {}, expected group:artifact:version[:classifier][@extension]",
+            artifact_id, reason
+        ));
+
+        let (coordinates, extension) = artifact_id.split_once('@').unwrap_or((artifact_id, "jar"));
+        if extension.is_empty() {
+            return Err(invalid("empty extension").into());
+        }
+
+        let split = coordinates.split(':').collect::<Vec<_>>();
+        if !(3..=4).contains(&split.len()) {
+            return Err(invalid(&format!("{} parts instead of 3 or 4", split.len())).into());
+        }
+        if split.iter().any(|x| x.is_empty()) {
+            return Err(invalid("empty part").into());
+        }
+
+        Ok(Self {
+            group: split[0],
+            artifact: split[1],
+            version: split[2],
+            classifier: split.get(3).copied(),
+            extension,
+        })
+    }
+
+}
+
+/// Resolves `group:artifact:version[:classifier][@extension]` to its path inside a maven repository,
+/// e.g. `net.neoforged:neoforge:20.4.80:universal` or `de.oceanlabs.mcp:mcp_config:1.20.4@zip`
+pub fn get_maven_artifact_path(artifact_id: &str) -> Result<String> {
+    let coordinate = MavenCoordinate::parse(artifact_id)?;
+
+    let file_name = match coordinate.classifier {
+        Some(classifier) => format!("{}-{}-{}.{}", coordinate.artifact, coordinate.version, classifier, coordinate.extension),
+        None => format!("{}-{}.{}", coordinate.artifact, coordinate.version, coordinate.extension)
+    };
+
+    Ok(format!("{}/{}/{}/{}", coordinate.group.replace('.', "/"), coordinate.artifact, coordinate.version, file_name))
+}
+
+/// Splits `group:artifact:version[:classifier][@extension]` into the artifact without its version and the version,
+/// e.g. `com.google.guava:guava:31.1-jre` to `com.google.guava:guava@jar` and `31.1-jre`
+pub fn split_maven_version(artifact_id: &str) -> Option<(String, &str)> {
+    let coordinate = MavenCoordinate::parse(artifact_id).ok()?;
+
+    let artifact = match coordinate.classifier {
+        Some(classifier) => format!("{}:{}:{}@{}", coordinate.group, coordinate.artifact, classifier, coordinate.extension),
+        None => format!("{}:{}@{}", coordinate.group, coordinate.artifact, coordinate.extension)
+    };
+
+    Some((artifact, coordinate.version))
+}
+
+/// Compares maven versions segment by segment, numeric segments are compared by their value
+pub fn compare_maven_versions(a: &str, b: &str) -> Ordering {
+    let segments = |version: &str| version
+        .split(['.', '-', '_', '+'])
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+
+    let (a, b) = (segments(a), segments(b));
+    for (segment_a, segment_b) in a.iter().zip(b.iter()) {
+        let ordering = match (segment_a.parse::<u64>(), segment_b.parse::<u64>()) {
+            (Ok(number_a), Ok(number_b)) => number_a.cmp(&number_b),
+            _ => segment_a.cmp(segment_b),
+        };
 
-    if split.len() != 3 {
-        return Err(LauncherError::InvalidVersionProfile(format!("Invalid artifact name: {}", artifact_id)).into());
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
     }
 
-    Ok(format!("{}/{name}/{ver}/{name}-{ver}.jar", split[0].replace('.', "/"), name = split[1], ver = split[2]))
-}
\ No newline at end of file
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_group_artifact_version() {
+        let coordinate = MavenCoordinate::parse("com.google.guava:guava:31.1-jre").unwrap();
+        assert_eq!((coordinate.group, coordinate.artifact, coordinate.version), ("com.google.guava", "guava", "31.1-jre"));
+        assert_eq!(coordinate.classifier, None);
+        assert_eq!(coordinate.extension, "jar");
+
+        assert_eq!(get_maven_artifact_path("com.google.guava:guava:31.1-jre").unwrap(), "com/google/guava/guava/31.1-jre/guava-31.1-jre.jar");
+    }
+
+    #[test]
+    fn parses_classifier() {
+        let coordinate = MavenCoordinate::parse("org.lwjgl:lwjgl:3.3.1:natives-linux").unwrap();
+        assert_eq!(coordinate.classifier, Some("natives-linux"));
+        assert_eq!(coordinate.extension, "jar");
+
+        assert_eq!(get_maven_artifact_path("org.lwjgl:lwjgl:3.3.1:natives-linux").unwrap(), "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar");
+    }
+
+    #[test]
+    fn parses_extension() {
+        let coordinate = MavenCoordinate::parse("de.oceanlabs.mcp:mcp_config:1.20.4@zip").unwrap();
+        assert_eq!(coordinate.classifier, None);
+        assert_eq!(coordinate.extension, "zip");
+
+        assert_eq!(get_maven_artifact_path("de.oceanlabs.mcp:mcp_config:1.20.4@zip").unwrap(), "de/oceanlabs/mcp/mcp_config/1.20.4/mcp_config-1.20.4.zip");
+    }
+
+    #[test]
+    fn parses_classifier_and_extension() {
+        let coordinate = MavenCoordinate::parse("net.neoforged:neoform:1.20.4-20231207.154220:mappings@txt").unwrap();
+        assert_eq!(coordinate.version, "1.20.4-20231207.154220");
+        assert_eq!(coordinate.classifier, Some("mappings"));
+        assert_eq!(coordinate.extension, "txt");
+
+        assert_eq!(
+            get_maven_artifact_path("net.neoforged:neoform:1.20.4-20231207.154220:mappings@txt").unwrap(),
+            "net/neoforged/neoform/1.20.4-20231207.154220/neoform-1.20.4-20231207.154220-mappings.txt"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_coordinates() {
+        for artifact_id in ["com.google.guava:guava", "a:b:c:d:e", "com.google.guava::31.1-jre", "com.google.guava:guava:31.1-jre@"] {
+            let error = get_maven_artifact_path(artifact_id).unwrap_err();
+            assert!(matches!(error.downcast_ref::<LauncherError>(), Some(LauncherError::Validation(_))), "{}", artifact_id);
+        }
+    }
+}
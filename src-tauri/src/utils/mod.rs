@@ -20,16 +20,20 @@
 mod sys;
 mod extract;
 mod download;
+mod http_cache;
 mod maven;
 mod checksum;
 mod macros;
 #[cfg(windows)]
 mod hosts;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 pub use {
     sys::*,
     extract::*,
     download::*,
+    http_cache::*,
     maven::*,
     checksum::*,
 };
@@ -18,10 +18,11 @@
  */
  
 use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use anyhow::{bail, Result};
 use once_cell::sync::Lazy;
 use serde::Deserialize;
-use sysinfo::{RefreshKind, System, SystemExt};
+use sysinfo::{DiskExt, RefreshKind, System, SystemExt};
 
 /// Get the total memory of the system in bytes
 pub fn percentage_of_total_memory(memory_percentage: i32) -> i64 {
@@ -30,6 +31,26 @@ pub fn percentage_of_total_memory(memory_percentage: i32) -> i64 {
     ((sys.total_memory() / 1000000) as f64 * (memory_percentage as f64 / 100.0)) as i64
 }
 
+/// Get the total memory of the system in megabytes
+pub fn total_memory_mb() -> u64 {
+    let sys = System::new_with_specifics(RefreshKind::new().with_memory());
+
+    sys.total_memory() / 1000000
+}
+
+/// Get the mount point and the available space in bytes of the disk the path is located on.
+/// The path does not have to exist yet, its closest existing parent is used instead.
+pub fn disk_of(path: &Path) -> Option<(PathBuf, u64)> {
+    let path = path.ancestors().find_map(|ancestor| ancestor.canonicalize().ok())?;
+    let sys = System::new_with_specifics(RefreshKind::new().with_disks_list());
+
+    // The most specific mount point contains the path
+    sys.disks().iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| (disk.mount_point().to_path_buf(), disk.available_space()))
+}
+
 pub const OS: OperatingSystem = if cfg!(target_os = "windows") {
     OperatingSystem::WINDOWS
 } else if cfg!(target_os = "macos") {
@@ -76,9 +97,10 @@ pub enum Architecture {
     X64,
     #[serde(rename = "arm")]
     ARM,
-    #[serde(rename = "aarch64")]
+    #[serde(rename = "aarch64", alias = "arm64")]
     AARCH64,
-    #[serde(rename = "unknown")]
+    /// Rules for other architectures never apply
+    #[serde(rename = "unknown", other)]
     UNKNOWN
 }
 